@@ -1351,7 +1351,7 @@ pub struct CommonArgs {
     #[arg(global = true, long, group = "selector_or_select")]
     pub selector: Option<String>,
 
-    /// Choose which tests to select adjacent to resources: eager (most inclusive), cautious (most exclusive), buildable (inbetween) or empty.
+    /// Choose which tests to select adjacent to resources: eager (most inclusive), cautious (most exclusive), buildable (inbetween), buildany or empty.
     #[arg(global = true, long, env = "DBT_INDIRECT_SELECTION")]
     pub indirect_selection: Option<IndirectSelection>,
 
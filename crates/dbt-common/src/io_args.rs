@@ -59,7 +59,7 @@ use crate::{
     io_utils::StatusReporter,
     logging::LogFormat,
     node_selector::{
-        IndirectSelection, SelectExpression, SelectionCriteria, conjoin_expression,
+        IndirectSelection, MethodName, SelectExpression, SelectionCriteria, conjoin_expression,
         parse_model_specifiers,
     },
     tracing::invocation::with_invocation_mut,
@@ -1134,6 +1134,11 @@ pub fn check_selector(selector: &str) -> Result<String, String> {
     // Convert the single selector to a vector with one element
     let query = vec![selector.to_string()];
     match parse_model_specifiers(&query) {
+        // `selector:` atoms only resolve against selectors.yml, which is not
+        // loaded when `--select`/`--exclude` are turned into `EvalArgs`
+        Ok(expr) if expr.atoms().any(|c| c.method == MethodName::Selector) => Err(format!(
+            "`selector:` is not supported in `{selector}`; use `--selector <name>` instead"
+        )),
         Ok(_) => Ok(selector.to_string()),
         Err(e) => Err(e.pretty()),
     }
@@ -1300,6 +1305,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_check_selector_rejects_selector_method() {
+        assert_eq!(check_selector("tag:nightly").unwrap(), "tag:nightly");
+        for selector in [
+            "selector:nightly",
+            "tag:a,selector:nightly",
+            "+selector:nightly",
+        ] {
+            let err = check_selector(selector).unwrap_err();
+            assert!(err.contains("--selector"), "{err}");
+        }
+    }
+
     #[test]
     fn test_validate_project_name_valid() {
         let valid_names = vec![
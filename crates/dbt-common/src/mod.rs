@@ -22,7 +22,8 @@ pub extern crate dbt_error as error;
 pub use dbt_error::{
     AdapterError, AdapterErrorKind, AdapterResult, AsyncAdapterResult, Cancellable,
     CodeLocationWithFile, ErrContext, ErrorCode, FsError, FsResult, LiftableResult, MacroSpan,
-    Span, ectx, err, fs_err, into_fs_error, not_implemented_err, unexpected_err, unexpected_fs_err,
+    SelectorErrorKind, Span, ectx, err, fs_err, into_fs_error, not_implemented_err, unexpected_err,
+    unexpected_fs_err,
 };
 pub mod behavior_flags;
 pub mod embedded_install_scripts;
@@ -20,12 +20,15 @@
 use dbt_yaml::JsonSchema;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use siphasher::sip::SipHasher13;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
+use std::hash::Hasher;
 use std::str::FromStr;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, RwLock};
 use strum::{Display, EnumIter, EnumString};
 
-use crate::{ErrorCode, FsResult, err, fs_err};
+use crate::{ErrorCode, FsError, FsResult, err, fs_err};
 
 // Common has only the syntax. The rest is in dbt-scheduler
 
@@ -51,10 +54,26 @@ pub enum IndirectSelection {
     Eager,
     Buildable,
     Cautious,
+    /// Select tests whose parents are *any* of the selected nodes (dbt 1.8+).
+    BuildAny,
     Empty,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, EnumIter, Display, Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumString,
+    EnumIter,
+    Display,
+    Serialize,
+    Deserialize,
+)]
 #[strum(serialize_all = "snake_case", ascii_case_insensitive)]
 pub enum MethodName {
     Access,
@@ -65,11 +84,18 @@ pub enum MethodName {
     Fqn,
     Group,
     Metric,
+    // `node_id:<unique_id>`: exactly the node with that unique id, with no
+    // glob matching. Never inferred by `default_for`.
+    NodeId,
     Package,
     Path,
     ResourceType,
     Result,
     SavedQuery,
+    // `selector:<name>`: a reference to a named YAML selector, kept in the
+    // tree until replaced through `SelectExpression::substitute`, as the
+    // selector parser does for every selector string.
+    Selector,
     SemanticModel,
     Source,
     SourceStatus,
@@ -79,11 +105,82 @@ pub enum MethodName {
     TestType,
     UnitTest,
     Version,
+    // `all:<anything>` or `*:<anything>`: every node, whatever the value.
+    // Never inferred by `default_for`.
+    #[strum(to_string = "all", serialize = "*")]
+    Wildcard,
     // Column selector
     // internal only to select column in a table
     // new syntax: column:<node_id>.<column_name>
     // todo: maybe better?: column:<node_id>#<column_name>
     Column,
+    // Method registered at runtime through `register_method`; its name is
+    // the first of the criteria's `method_args`.
+    Custom,
+}
+
+/// The comparison a `state:` selector performs, taken from the head of its
+/// value (`state:modified.body` ⇒ `Modified`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, EnumIter, Display)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum StateMethod {
+    /// New nodes, or nodes whose contents changed since the previous state.
+    Modified,
+    /// Nodes absent from the previous state.
+    New,
+    /// Nodes that already existed in the previous state.
+    #[strum(serialize = "old")]
+    OldRelation,
+}
+
+/// The source freshness outcome a `source_status:` selector matches,
+/// taken from its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, EnumIter, Display)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum SourceStatusKind {
+    /// The source has been loaded since the previous freshness check.
+    Fresher,
+    /// The freshness check passed.
+    Pass,
+    /// The freshness check exceeded its `warn_after` threshold.
+    Warn,
+    /// The freshness check exceeded its `error_after` threshold.
+    Error,
+}
+
+/// The model versions a `version:` selector matches, taken from its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VersionSelector {
+    /// `version:<n>`: exactly version `n`.
+    Number(u32),
+    /// The latest version of a versioned model.
+    Latest,
+    /// Versions newer than the model's latest version.
+    Prerelease,
+    /// Versions older than the model's latest version.
+    Old,
+    /// `version:none`: models without versions.
+    Unversioned,
+}
+
+impl FromStr for VersionSelector {
+    type Err = Box<FsError>;
+
+    fn from_str(s: &str) -> FsResult<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "latest" => Ok(Self::Latest),
+            "prerelease" => Ok(Self::Prerelease),
+            "old" => Ok(Self::Old),
+            "none" => Ok(Self::Unversioned),
+            _ => s.parse().map(Self::Number).map_err(|_| {
+                fs_err!(
+                    ErrorCode::SelectorError,
+                    "Invalid version selector `{}`: expected a version number, `latest`, `prerelease`, `old` or `none`",
+                    s
+                )
+            }),
+        }
+    }
 }
 
 impl MethodName {
@@ -103,7 +200,7 @@ impl MethodName {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SelectionCriteria {
     // qualifier + optional sub‑parts ("config.materialized" ⇒ method="config", args=["materialized"])
     pub method: MethodName,
@@ -145,6 +242,118 @@ impl SelectionCriteria {
             exclude,
         }
     }
+
+    /// Criteria for `method:value` without graph operators, method
+    /// arguments or excludes, using the default indirect selection.
+    pub fn with_method(method: MethodName, value: impl Into<String>) -> Self {
+        Self::builder(method, value).build()
+    }
+
+    /// `fqn:<value>`
+    pub fn new_fqn(value: impl Into<String>) -> Self {
+        Self::with_method(MethodName::Fqn, value)
+    }
+
+    /// `tag:<value>`
+    pub fn new_tag(value: impl Into<String>) -> Self {
+        Self::with_method(MethodName::Tag, value)
+    }
+
+    /// `path:<value>`
+    pub fn new_path(value: impl Into<String>) -> Self {
+        Self::with_method(MethodName::Path, value)
+    }
+
+    /// `package:<value>`
+    pub fn new_package(value: impl Into<String>) -> Self {
+        Self::with_method(MethodName::Package, value)
+    }
+
+    /// Replace the nested exclude, e.g.
+    /// `SelectionCriteria::new_tag("nightly").with_exclude(slow)`.
+    pub fn with_exclude(mut self, exclude: SelectExpression) -> Self {
+        self.exclude = Some(Box::new(exclude));
+        self
+    }
+
+    /// Replace the ancestor depth; `None` selects no ancestors and
+    /// `Some(u32::MAX)` all of them.
+    pub fn with_parents(mut self, depth: Option<u32>) -> Self {
+        self.parents_depth = depth;
+        self
+    }
+
+    /// Replace the descendant depth; `None` selects no descendants and
+    /// `Some(u32::MAX)` all of them.
+    pub fn with_children(mut self, depth: Option<u32>) -> Self {
+        self.children_depth = depth;
+        self
+    }
+
+    pub fn builder(method: MethodName, value: impl Into<String>) -> SelectionCriteriaBuilder {
+        SelectionCriteriaBuilder::new(method, value)
+    }
+}
+
+/// Fluent alternative to [`SelectionCriteria::new`]; every field not set
+/// explicitly takes the value `parse_single_selector` would give it.
+#[derive(Debug, Clone)]
+pub struct SelectionCriteriaBuilder {
+    criteria: SelectionCriteria,
+}
+
+impl SelectionCriteriaBuilder {
+    pub fn new(method: MethodName, value: impl Into<String>) -> Self {
+        Self {
+            criteria: SelectionCriteria::new(
+                method,
+                vec![],
+                value.into(),
+                false,
+                None,
+                None,
+                Some(IndirectSelection::default()),
+                None,
+            ),
+        }
+    }
+
+    pub fn with_method_args(mut self, method_args: Vec<String>) -> Self {
+        self.criteria.method_args = method_args;
+        self
+    }
+
+    /// The `@` operator.
+    pub fn with_childrens_parents(mut self) -> Self {
+        self.criteria.childrens_parents = true;
+        self
+    }
+
+    /// `u32::MAX` selects all ancestors.
+    pub fn with_parents_depth(mut self, depth: u32) -> Self {
+        self.criteria.parents_depth = Some(depth);
+        self
+    }
+
+    /// `u32::MAX` selects all descendants.
+    pub fn with_children_depth(mut self, depth: u32) -> Self {
+        self.criteria.children_depth = Some(depth);
+        self
+    }
+
+    pub fn with_indirect(mut self, indirect: IndirectSelection) -> Self {
+        self.criteria.indirect = Some(indirect);
+        self
+    }
+
+    pub fn with_exclude(mut self, exclude: SelectExpression) -> Self {
+        self.criteria.exclude = Some(Box::new(exclude));
+        self
+    }
+
+    pub fn build(self) -> SelectionCriteria {
+        self.criteria
+    }
 }
 
 impl fmt::Display for SelectionCriteria {
@@ -163,13 +372,8 @@ impl fmt::Display for SelectionCriteria {
             result.push('+');
         }
 
-        if !self.method_args.is_empty() {
-            result.push_str(&format!("{}.", self.method.to_string().to_lowercase()));
-            result.push_str(&self.method_args.join("."));
-            result.push(':');
-        } else {
-            result.push_str(&format!("{}:", self.method.to_string().to_lowercase()));
-        }
+        result.push_str(&self.qualifier());
+        result.push(':');
 
         result.push_str(&self.value);
 
@@ -185,17 +389,132 @@ impl fmt::Display for SelectionCriteria {
     }
 }
 
+impl SelectionCriteria {
+    /// The part of the selector before the `:`, e.g. `config.materialized`.
+    pub fn qualifier(&self) -> String {
+        let method = (self.method != MethodName::Custom).then(|| self.method.to_string());
+        method
+            .into_iter()
+            .chain(self.method_args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// The indirect selection mode, with an unset mode read as the default.
+    pub fn effective_indirect(&self) -> IndirectSelection {
+        self.indirect.unwrap_or_default()
+    }
+
+    /// Whether both criteria select the same nodes, reading an unset
+    /// `indirect` as the default mode.
+    pub fn semantically_eq(&self, other: &Self) -> bool {
+        self.method == other.method
+            && self.method_args == other.method_args
+            && self.value == other.value
+            && self.childrens_parents == other.childrens_parents
+            && self.parents_depth == other.parents_depth
+            && self.children_depth == other.children_depth
+            && self.effective_indirect() == other.effective_indirect()
+            && self.exclude.as_deref().map(SelectExpression::stable_hash)
+                == other.exclude.as_deref().map(SelectExpression::stable_hash)
+    }
+
+    /// For `state:` selectors, the comparison encoded in the value.
+    pub fn state_method(&self) -> Option<StateMethod> {
+        if self.method != MethodName::State {
+            return None;
+        }
+        let head = self.value.split('.').next().unwrap_or_default();
+        StateMethod::from_str(head).ok()
+    }
+
+    /// For `source_status:` selectors, the freshness outcome named by the
+    /// value.
+    pub fn source_status_kind(&self) -> Option<SourceStatusKind> {
+        if self.method != MethodName::SourceStatus {
+            return None;
+        }
+        SourceStatusKind::from_str(&self.value).ok()
+    }
+
+    /// For `version:` selectors, the versions encoded in the value.
+    pub fn version_selector(&self) -> Option<VersionSelector> {
+        if self.method != MethodName::Version {
+            return None;
+        }
+        self.value.parse().ok()
+    }
+
+    /// Render this criterion as a single CLI selector token, which
+    /// `parse_single_selector` parses back to an equal criterion.
+    ///
+    /// Unlike `Display`, the method qualifier is omitted when it is the one
+    /// `MethodName::default_for` would infer from the value, and explicit
+    /// depths (including `0`) are always written out.
+    ///
+    /// Returns `None` if the criterion has a nested exclude or a
+    /// non-default indirect selection, which a selector token cannot carry,
+    /// or if the rendered token would not parse back to it, e.g. because the
+    /// value contains a comma or looks like a graph operator.
+    pub fn to_selector_string(&self) -> Option<String> {
+        if self.exclude.is_some()
+            || self.effective_indirect() != IndirectSelection::default()
+            || self.value.contains(|c: char| c == ',' || c.is_whitespace())
+        {
+            return None;
+        }
+        let mut result = String::new();
+
+        if self.childrens_parents {
+            result.push('@');
+        }
+        if let Some(depth) = self.parents_depth {
+            if depth != u32::MAX {
+                result.push_str(&depth.to_string());
+            }
+            result.push('+');
+        }
+
+        if !self.method_args.is_empty() || self.method != MethodName::default_for(&self.value) {
+            result.push_str(&self.qualifier());
+            result.push(':');
+        }
+        result.push_str(&self.value);
+
+        if let Some(depth) = self.children_depth {
+            result.push('+');
+            if depth != u32::MAX {
+                result.push_str(&depth.to_string());
+            }
+        }
+
+        parse_single_selector(&result)
+            .is_ok_and(|parsed| parsed.semantically_eq(self))
+            .then_some(result)
+    }
+}
+
 /// Represents the AST for model specifiers, which can be combined using logical AND and OR operations.
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+///
+/// Serializes as `{"type": "<variant>", "args": <payload>}`, e.g. for `--explain` output.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "args")]
 pub enum SelectExpression {
     Atom(SelectionCriteria),        // a single model specifier
     And(Vec<SelectExpression>),     // a list of model specifiers, joined by commas
     Or(Vec<SelectExpression>),      // a list of model specifiers, joined by spaces
     Exclude(Box<SelectExpression>), // For nested excludes
+    Not(Box<SelectExpression>),     // every node not selected by the inner expression
+    Empty, // selects nothing; produced by `simplify` for empty operand lists
 }
 
+/// `{}` renders the compact selector syntax; the alternate form `{:#}`
+/// renders the indented tree of [`SelectExpression::to_tree_string`].
 impl fmt::Display for SelectExpression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return write!(f, "{}", self.to_tree_string("  "));
+        }
         match self {
             SelectExpression::And(expressions) => {
                 let expressions_str = expressions
@@ -215,10 +534,24 @@ impl fmt::Display for SelectExpression {
             }
             SelectExpression::Atom(criteria) => write!(f, "{criteria}"),
             SelectExpression::Exclude(expr) => write!(f, "exclude({expr})"),
+            SelectExpression::Not(expr) => write!(f, "not({expr})"),
+            SelectExpression::Empty => Ok(()),
         }
     }
 }
 
+/// Parses the CLI selector syntax: whitespace separates *OR* terms like the
+/// arguments of `--select`, commas separate *AND* terms. Inverse of
+/// `Display` for atoms, `And` and `Or`.
+impl FromStr for SelectExpression {
+    type Err = Box<FsError>;
+
+    fn from_str(s: &str) -> FsResult<Self> {
+        let tokens = s.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+        parse_model_specifiers(&tokens)
+    }
+}
+
 impl SelectExpression {
     /// Set the indirect selection mode for this expression and all nested expressions
     pub fn set_indirect_selection(&mut self, mode: IndirectSelection) {
@@ -231,9 +564,10 @@ impl SelectExpression {
             SelectExpression::Atom(criteria) => {
                 criteria.indirect = Some(mode);
             }
-            SelectExpression::Exclude(expr) => {
+            SelectExpression::Exclude(expr) | SelectExpression::Not(expr) => {
                 expr.set_indirect_selection(mode);
             }
+            SelectExpression::Empty => {}
         }
     }
 
@@ -252,134 +586,922 @@ impl SelectExpression {
                     criteria.indirect = Some(default_mode);
                 }
             }
-            SelectExpression::Exclude(expr) => {
+            SelectExpression::Exclude(expr) | SelectExpression::Not(expr) => {
                 expr.apply_default_indirect_selection(default_mode);
             }
+            SelectExpression::Empty => {}
         }
     }
-}
 
-/// Converts every `column:` selector in the expression into an equivalent `fqn:` selector.
-///
-/// Returns the converted expression along with a boolean indicating whether any
-/// conversion occurred.
-pub fn convert_column_selectors_to_fqn(expr: SelectExpression) -> (SelectExpression, bool) {
-    match expr {
-        SelectExpression::Atom(mut criteria) => {
-            let mut converted = false;
+    /// Render this expression as a dbt CLI selector string.
+    ///
+    /// `Or` operands are separated by spaces and `And` operands by commas.
+    /// The CLI syntax can only express a union of intersections of atoms, so
+    /// the expression is simplified first and `None` is returned if it is
+    /// still nested deeper than that, contains an `Exclude`, `Not` or
+    /// `Empty`, or has an atom that [`SelectionCriteria::to_selector_string`]
+    /// cannot render. Otherwise splitting the result on whitespace and
+    /// passing it to `parse_model_specifiers` yields `self.simplify()`.
+    pub fn to_selector_string(&self) -> Option<String> {
+        match self.simplify() {
+            SelectExpression::Or(exprs) => exprs
+                .iter()
+                .map(SelectExpression::to_selector_token)
+                .collect::<Option<Vec<_>>>()
+                .map(|tokens| tokens.join(" ")),
+            expr => expr.to_selector_token(),
+        }
+    }
 
-            if let Some(inner) = criteria.exclude.take() {
-                let (inner_converted, inner_changed) = convert_column_selectors_to_fqn(*inner);
-                criteria.exclude = Some(Box::new(inner_converted));
-                converted |= inner_changed;
-            }
+    /// A single atom or an `And` of atoms, rendered as one comma-separated
+    /// CLI token.
+    fn to_selector_token(&self) -> Option<String> {
+        let atom = |expr: &SelectExpression| match expr {
+            SelectExpression::Atom(criteria) => criteria.to_selector_string(),
+            _ => None,
+        };
+        match self {
+            SelectExpression::And(exprs) => exprs
+                .iter()
+                .map(atom)
+                .collect::<Option<Vec<_>>>()
+                .map(|atoms| atoms.join(",")),
+            expr => atom(expr),
+        }
+    }
 
-            if criteria.method == MethodName::Column {
-                converted = true;
-                let mut parts = criteria.value.rsplitn(2, '.');
-                let _column_part = parts.next().unwrap_or("");
-                let node_part = parts.next().unwrap_or("");
-                criteria.method = MethodName::Fqn;
-                criteria.value = node_part.to_string();
-            }
+    /// Render the expression as an indented tree, one node per line, e.g.
+    ///
+    /// ```text
+    /// AND
+    ///   tag:nightly
+    ///   EXCLUDE
+    ///     fqn:model_slow
+    /// ```
+    ///
+    /// `indent` is repeated once per nesting level.
+    pub fn to_tree_string(&self, indent: &str) -> String {
+        let mut lines = Vec::new();
+        self.push_tree_lines(indent, 0, &mut lines);
+        lines.join("\n")
+    }
 
-            (SelectExpression::Atom(criteria), converted)
-        }
-        SelectExpression::And(expressions) => {
-            let mut converted = false;
-            let converted_exprs = expressions
-                .into_iter()
-                .map(|expr| {
-                    let (expr_converted, expr_changed) = convert_column_selectors_to_fqn(expr);
-                    converted |= expr_changed;
-                    expr_converted
-                })
-                .collect();
-            (SelectExpression::And(converted_exprs), converted)
+    fn push_tree_lines(&self, indent: &str, level: usize, lines: &mut Vec<String>) {
+        let prefix = indent.repeat(level);
+        match self {
+            SelectExpression::Atom(criteria) => {
+                lines.push(format!("{prefix}{criteria}"));
+                if let Some(exclude) = &criteria.exclude {
+                    lines.push(format!("{prefix}{indent}EXCLUDE"));
+                    exclude.push_tree_lines(indent, level + 2, lines);
+                }
+            }
+            SelectExpression::And(exprs) | SelectExpression::Or(exprs) => {
+                let op = if matches!(self, SelectExpression::And(_)) {
+                    "AND"
+                } else {
+                    "OR"
+                };
+                lines.push(format!("{prefix}{op}"));
+                for expr in exprs {
+                    expr.push_tree_lines(indent, level + 1, lines);
+                }
+            }
+            SelectExpression::Exclude(expr) => {
+                lines.push(format!("{prefix}EXCLUDE"));
+                expr.push_tree_lines(indent, level + 1, lines);
+            }
+            SelectExpression::Not(expr) => {
+                lines.push(format!("{prefix}NOT"));
+                expr.push_tree_lines(indent, level + 1, lines);
+            }
+            SelectExpression::Empty => lines.push(format!("{prefix}EMPTY")),
         }
-        SelectExpression::Or(expressions) => {
-            let mut converted = false;
-            let converted_exprs = expressions
-                .into_iter()
-                .map(|expr| {
-                    let (expr_converted, expr_changed) = convert_column_selectors_to_fqn(expr);
-                    converted |= expr_changed;
-                    expr_converted
-                })
-                .collect();
-            (SelectExpression::Or(converted_exprs), converted)
+    }
+
+    /// Render the expression tree as a Graphviz DOT digraph named
+    /// `graph_name`. Composites are labeled with their variant name and
+    /// atoms with their selector string; an atom's nested exclude hangs off
+    /// the atom as an `Exclude` node. Node ids encode the position in the
+    /// tree: the root is `n0` and the `i`-th operand of `nX` is `nX_i`.
+    pub fn to_dot(&self, graph_name: &str) -> String {
+        let mut lines = vec![format!("digraph \"{}\" {{", dot_escape(graph_name))];
+        self.push_dot_lines("n0", &mut lines);
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    fn push_dot_lines(&self, id: &str, lines: &mut Vec<String>) {
+        let (label, operands): (String, Vec<&SelectExpression>) = match self {
+            SelectExpression::Atom(criteria) => (criteria.to_string(), vec![]),
+            SelectExpression::And(exprs) => ("And".to_string(), exprs.iter().collect()),
+            SelectExpression::Or(exprs) => ("Or".to_string(), exprs.iter().collect()),
+            SelectExpression::Exclude(expr) => ("Exclude".to_string(), vec![expr]),
+            SelectExpression::Not(expr) => ("Not".to_string(), vec![expr]),
+            SelectExpression::Empty => ("Empty".to_string(), vec![]),
+        };
+        lines.push(format!("  {id} [label=\"{}\"];", dot_escape(&label)));
+        for (i, operand) in operands.into_iter().enumerate() {
+            let operand_id = format!("{id}_{i}");
+            lines.push(format!("  {id} -> {operand_id};"));
+            operand.push_dot_lines(&operand_id, lines);
         }
-        SelectExpression::Exclude(expr) => {
-            let (converted_expr, converted) = convert_column_selectors_to_fqn(*expr);
-            (
-                SelectExpression::Exclude(Box::new(converted_expr)),
-                converted,
-            )
+        if let SelectExpression::Atom(criteria) = self
+            && let Some(exclude) = &criteria.exclude
+        {
+            let exclude_id = format!("{id}_0");
+            lines.push(format!("  {exclude_id} [label=\"Exclude\"];"));
+            lines.push(format!("  {id} -> {exclude_id};"));
+            lines.push(format!("  {exclude_id} -> {exclude_id}_0;"));
+            exclude.push_dot_lines(&format!("{exclude_id}_0"), lines);
         }
     }
-}
 
-/// Checks if a `SelectExpression` contains any `state:modified` or `state:new` selectors.
-///
-/// This is useful for determining whether loading the manifest.json is required for
-/// a given selector. If the selector only uses methods like `source_status:fresher+`,
-/// then the manifest is not needed and we should not warn if it fails to load.
-pub fn contains_state_modified_or_new_selector(expr: &SelectExpression) -> bool {
-    match expr {
-        SelectExpression::Atom(criteria) => {
-            if criteria.method == MethodName::State {
-                let value_lower = criteria.value.to_lowercase();
-                value_lower.starts_with("modified") || value_lower.starts_with("new")
-            } else {
-                // Also check nested excludes
-                criteria
-                    .exclude
-                    .as_ref()
-                    .map(|e| contains_state_modified_or_new_selector(e))
-                    .unwrap_or(false)
+    /// Content hash that is equal for structurally equal expressions,
+    /// independent of the order of `And`/`Or` operands, and stable across
+    /// runs and platforms, so it can key persisted caches.
+    pub fn stable_hash(&self) -> u64 {
+        let mut visitor = StableHashVisitor::default();
+        walk(&mut visitor, self);
+        visitor
+            .operands
+            .pop()
+            .expect("walk leaves the hash of the root expression")
+    }
+
+    /// Iterate over every `SelectionCriteria` leaf in the tree, depth first
+    /// and left to right, including those nested in excludes.
+    pub fn atoms(&self) -> impl Iterator<Item = &SelectionCriteria> {
+        #[derive(Default)]
+        struct AtomsVisitor<'a>(Vec<&'a SelectionCriteria>);
+
+        impl<'a> SelectExpressionVisitor<'a> for AtomsVisitor<'a> {
+            fn visit_atom(&mut self, criteria: &'a SelectionCriteria) {
+                self.0.push(criteria);
             }
         }
-        SelectExpression::And(expressions) | SelectExpression::Or(expressions) => expressions
-            .iter()
-            .any(contains_state_modified_or_new_selector),
-        SelectExpression::Exclude(expr) => contains_state_modified_or_new_selector(expr),
+
+        let mut visitor = AtomsVisitor::default();
+        walk(&mut visitor, self);
+        visitor.0.into_iter()
     }
-}
 
-/// Checks if any of the provided optional select expressions contain `state:modified` or `state:new`.
-pub fn selectors_require_manifest(
-    select: Option<&SelectExpression>,
-    exclude: Option<&SelectExpression>,
-) -> bool {
-    let select_requires = select.is_some_and(contains_state_modified_or_new_selector);
-    let exclude_requires = exclude.is_some_and(contains_state_modified_or_new_selector);
-    select_requires || exclude_requires
-}
+    /// Whether any atom in the tree uses the `state:` method, including atoms
+    /// that only appear inside excludes. Such selectors need a `--state`
+    /// directory to compare against.
+    pub fn contains_state_selector(&self) -> bool {
+        self.atoms()
+            .any(|criteria| criteria.method == MethodName::State)
+    }
 
-// ------------------------------------------------------------------------------------------------
-pub fn conjoin_expression(
-    maybe_select_expression: Option<SelectExpression>,
-    maybe_select_expression2: Option<SelectExpression>,
-) -> Option<SelectExpression> {
-    match (maybe_select_expression, maybe_select_expression2) {
-        (Some(select), Some(select2)) => Some(SelectExpression::And(vec![select, select2])),
-        (Some(select), None) => Some(select),
-        (None, Some(select)) => Some(select),
-        (None, None) => None,
+    /// Every distinct selector method used by an atom of the tree, including
+    /// atoms inside excludes, so callers can skip loading node attributes
+    /// that no atom looks at.
+    pub fn methods_referenced(&self) -> BTreeSet<MethodName> {
+        self.atoms().map(|criteria| criteria.method).collect()
     }
-}
 
-/// ----------------------------------
-/// parsing
-/// ----------------------------------
-static RAW_SELECTOR_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(
-        r"(?x)
-^
-(?P<childrens_parents>@)?
-(?P<parents>(?P<parents_depth>\d*)\+)?   # optional leading +N
-(?:(?P<method>[\w.]+):)?                 # optional qualifier
-(?P<value>.*?)
+    /// Number of `Atom` criteria in the tree, including those inside
+    /// excludes. Unlike [`Self::node_count_estimate`], repeated criteria are
+    /// counted every time they appear.
+    pub fn count_atoms(&self) -> usize {
+        self.atoms().count()
+    }
+
+    /// Number of exclude branches in the tree: `Exclude` nodes plus atoms
+    /// with a nested `exclude`. A tree without any can skip exclude
+    /// evaluation entirely.
+    pub fn count_excludes(&self) -> usize {
+        #[derive(Default)]
+        struct ExcludeCounter(usize);
+
+        impl SelectExpressionVisitor<'_> for ExcludeCounter {
+            fn visit_atom(&mut self, criteria: &SelectionCriteria) {
+                if criteria.exclude.is_some() {
+                    self.0 += 1;
+                }
+            }
+            fn visit_exclude(&mut self, _expr: &SelectExpression) {
+                self.0 += 1;
+            }
+        }
+
+        let mut visitor = ExcludeCounter::default();
+        walk(&mut visitor, self);
+        visitor.0
+    }
+
+    /// Number of distinct `Atom` criteria in the tree, including those inside
+    /// excludes. Structurally equal criteria are counted once, so the result
+    /// is a cheap upper bound for pre-sizing per-criterion state.
+    pub fn node_count_estimate(&self) -> usize {
+        fn collect<'a>(expr: &'a SelectExpression, seen: &mut HashSet<&'a SelectionCriteria>) {
+            match expr {
+                SelectExpression::Atom(criteria) => {
+                    seen.insert(criteria);
+                    if let Some(exclude) = &criteria.exclude {
+                        collect(exclude, seen);
+                    }
+                }
+                SelectExpression::And(exprs) | SelectExpression::Or(exprs) => {
+                    for expr in exprs {
+                        collect(expr, seen);
+                    }
+                }
+                SelectExpression::Exclude(expr) | SelectExpression::Not(expr) => {
+                    collect(expr, seen)
+                }
+                SelectExpression::Empty => {}
+            }
+        }
+
+        let mut seen = HashSet::new();
+        collect(self, &mut seen);
+        seen.len()
+    }
+
+    /// Maximum nesting depth of the tree: `0` for an atom or `Empty`, and one
+    /// more than the deepest operand for `And`, `Or`, `Exclude` and `Not`.
+    /// Excludes nested inside an atom's criteria are not counted.
+    pub fn depth(&self) -> usize {
+        #[derive(Default)]
+        struct DepthVisitor {
+            current: usize,
+            max: usize,
+            /// Number of atoms whose nested exclude is being walked.
+            in_atom: usize,
+        }
+
+        impl DepthVisitor {
+            fn enter(&mut self) {
+                if self.in_atom == 0 {
+                    self.current += 1;
+                    self.max = self.max.max(self.current);
+                }
+            }
+        }
+
+        impl SelectExpressionVisitor<'_> for DepthVisitor {
+            fn visit_atom(&mut self, _criteria: &SelectionCriteria) {
+                self.in_atom += 1;
+            }
+            fn visit_and(&mut self, _exprs: &[SelectExpression]) {
+                self.enter();
+            }
+            fn visit_or(&mut self, _exprs: &[SelectExpression]) {
+                self.enter();
+            }
+            fn visit_exclude(&mut self, _expr: &SelectExpression) {
+                self.enter();
+            }
+            fn visit_not(&mut self, _expr: &SelectExpression) {
+                self.enter();
+            }
+            fn leave(&mut self, expr: &SelectExpression) {
+                match expr {
+                    SelectExpression::Atom(_) => self.in_atom -= 1,
+                    SelectExpression::Empty => {}
+                    _ if self.in_atom == 0 => self.current -= 1,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut visitor = DepthVisitor::default();
+        walk(&mut visitor, self);
+        visitor.max
+    }
+
+    /// Replace every `selector:<name>` atom, including those inside
+    /// excludes, with `replacement`, e.g. the resolved expression of the
+    /// selector `name`. References to other selectors are left as they are,
+    /// so a tree can be resolved one selector at a time. Graph operators on a
+    /// replaced atom are dropped, as they are for selector inheritance.
+    pub fn substitute(self, name: &str, replacement: SelectExpression) -> SelectExpression {
+        match self {
+            SelectExpression::Atom(criteria)
+                if criteria.method == MethodName::Selector && criteria.value == name =>
+            {
+                replacement
+            }
+            SelectExpression::Atom(mut criteria) => {
+                criteria.exclude = criteria
+                    .exclude
+                    .map(|exclude| Box::new(exclude.substitute(name, replacement)));
+                SelectExpression::Atom(criteria)
+            }
+            SelectExpression::And(exprs) => SelectExpression::And(
+                exprs
+                    .into_iter()
+                    .map(|expr| expr.substitute(name, replacement.clone()))
+                    .collect(),
+            ),
+            SelectExpression::Or(exprs) => SelectExpression::Or(
+                exprs
+                    .into_iter()
+                    .map(|expr| expr.substitute(name, replacement.clone()))
+                    .collect(),
+            ),
+            SelectExpression::Exclude(expr) => {
+                SelectExpression::Exclude(Box::new(expr.substitute(name, replacement)))
+            }
+            SelectExpression::Not(expr) => {
+                SelectExpression::Not(Box::new(expr.substitute(name, replacement)))
+            }
+            SelectExpression::Empty => SelectExpression::Empty,
+        }
+    }
+
+    /// Apply `f` to every node of the tree bottom-up: the operands of a
+    /// node, and the exclude nested in an atom, are rewritten before `f`
+    /// sees the node itself, e.g. to prefix `fqn` values with a package
+    /// name or swap one method for another.
+    pub fn rewrite<F>(self, f: F) -> SelectExpression
+    where
+        F: Fn(SelectExpression) -> SelectExpression + Clone,
+    {
+        let rewritten = match self {
+            SelectExpression::Atom(mut criteria) => {
+                criteria.exclude = criteria
+                    .exclude
+                    .map(|exclude| Box::new(exclude.rewrite(f.clone())));
+                SelectExpression::Atom(criteria)
+            }
+            SelectExpression::And(exprs) => SelectExpression::And(
+                exprs
+                    .into_iter()
+                    .map(|expr| expr.rewrite(f.clone()))
+                    .collect(),
+            ),
+            SelectExpression::Or(exprs) => SelectExpression::Or(
+                exprs
+                    .into_iter()
+                    .map(|expr| expr.rewrite(f.clone()))
+                    .collect(),
+            ),
+            SelectExpression::Exclude(expr) => {
+                SelectExpression::Exclude(Box::new(expr.rewrite(f.clone())))
+            }
+            SelectExpression::Not(expr) => SelectExpression::Not(Box::new(expr.rewrite(f.clone()))),
+            SelectExpression::Empty => SelectExpression::Empty,
+        };
+        f(rewritten)
+    }
+
+    /// Reorder the operands of every `And` so that the most selective come
+    /// first, letting evaluation stop as soon as the intersection is empty.
+    /// `selectivity_hints` maps a method to the expected fraction of nodes
+    /// its atoms match, from `0.0` (very selective) to `1.0`; methods
+    /// without a hint count as `1.0`. Composite operands are estimated from
+    /// their own operands: the smallest for `And`, the sum (at most `1.0`)
+    /// for `Or`, and the remainder for `Exclude` and `Not`. Operands with
+    /// equal estimates keep their order, and the selected nodes are the
+    /// same.
+    pub fn optimize(self, selectivity_hints: &BTreeMap<MethodName, f64>) -> SelectExpression {
+        self.rewrite(|expr| match expr {
+            SelectExpression::And(mut exprs) => {
+                exprs.sort_by(|a, b| {
+                    a.estimated_selectivity(selectivity_hints)
+                        .total_cmp(&b.estimated_selectivity(selectivity_hints))
+                });
+                SelectExpression::And(exprs)
+            }
+            expr => expr,
+        })
+    }
+
+    fn estimated_selectivity(&self, hints: &BTreeMap<MethodName, f64>) -> f64 {
+        match self {
+            SelectExpression::Atom(criteria) => hints.get(&criteria.method).copied().unwrap_or(1.0),
+            SelectExpression::And(exprs) => exprs
+                .iter()
+                .map(|expr| expr.estimated_selectivity(hints))
+                .fold(1.0, f64::min),
+            SelectExpression::Or(exprs) => exprs
+                .iter()
+                .map(|expr| expr.estimated_selectivity(hints))
+                .sum::<f64>()
+                .min(1.0),
+            SelectExpression::Exclude(expr) | SelectExpression::Not(expr) => {
+                1.0 - expr.estimated_selectivity(hints)
+            }
+            SelectExpression::Empty => 0.0,
+        }
+    }
+
+    /// True if the expression selects no nodes by its structure alone:
+    /// `Empty`, an `Or` whose operands are all empty (including `Or([])`),
+    /// or an `And` with an empty operand (including `And([])`); all of these
+    /// [`simplify`](Self::simplify) to `Empty`. Atoms, `Exclude` and `Not`
+    /// are never empty, as only evaluation can tell.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            SelectExpression::Empty => true,
+            SelectExpression::Or(exprs) => exprs.iter().all(SelectExpression::is_empty),
+            SelectExpression::And(exprs) => {
+                exprs.is_empty() || exprs.iter().any(SelectExpression::is_empty)
+            }
+            SelectExpression::Atom(_) | SelectExpression::Exclude(_) | SelectExpression::Not(_) => {
+                false
+            }
+        }
+    }
+
+    /// Return an equivalent expression with redundant structure removed.
+    ///
+    /// * `And([x])` / `Or([x])` collapse to `x`.
+    /// * Nested `And(And(..))` / `Or(Or(..))` are flattened into their parent.
+    /// * `And([])` / `Or([])` become `Empty`; `Empty` operands are dropped from
+    ///   an `Or` and make an enclosing `And` empty.
+    /// * `Not(Not(x))` collapses to `x`.
+    pub fn simplify(&self) -> SelectExpression {
+        match self {
+            SelectExpression::Atom(criteria) => {
+                let mut criteria = criteria.clone();
+                if let Some(inner) = criteria.exclude.take() {
+                    criteria.exclude = match inner.simplify() {
+                        SelectExpression::Empty => None,
+                        simplified => Some(Box::new(simplified)),
+                    };
+                }
+                SelectExpression::Atom(criteria)
+            }
+            SelectExpression::And(exprs) => {
+                let mut flat = Vec::with_capacity(exprs.len());
+                for expr in exprs {
+                    match expr.simplify() {
+                        SelectExpression::Empty => return SelectExpression::Empty,
+                        SelectExpression::And(inner) => flat.extend(inner),
+                        simplified => flat.push(simplified),
+                    }
+                }
+                match flat.len() {
+                    0 => SelectExpression::Empty,
+                    1 => flat.pop().unwrap(),
+                    _ => SelectExpression::And(flat),
+                }
+            }
+            SelectExpression::Or(exprs) => {
+                let mut flat = Vec::with_capacity(exprs.len());
+                for expr in exprs {
+                    match expr.simplify() {
+                        SelectExpression::Empty => {}
+                        SelectExpression::Or(inner) => flat.extend(inner),
+                        simplified => flat.push(simplified),
+                    }
+                }
+                match flat.len() {
+                    0 => SelectExpression::Empty,
+                    1 => flat.pop().unwrap(),
+                    _ => SelectExpression::Or(flat),
+                }
+            }
+            SelectExpression::Exclude(expr) => SelectExpression::Exclude(Box::new(expr.simplify())),
+            SelectExpression::Not(expr) => match expr.simplify() {
+                SelectExpression::Not(inner) => *inner,
+                simplified => SelectExpression::Not(Box::new(simplified)),
+            },
+            SelectExpression::Empty => SelectExpression::Empty,
+        }
+    }
+}
+
+/// Length-prefixed, so that consecutive strings cannot run into each other.
+fn write_str(hasher: &mut SipHasher13, s: &str) {
+    hasher.write(&(s.len() as u64).to_le_bytes());
+    hasher.write(s.as_bytes());
+}
+
+/// Callbacks invoked by [`walk`] for each node of a [`SelectExpression`].
+/// Every method does nothing by default, so a visitor only implements the
+/// nodes it cares about.
+///
+/// ```
+/// use dbt_common::node_selector::{
+///     SelectExpression, SelectExpressionVisitor, SelectionCriteria, parse_model_specifiers, walk,
+/// };
+///
+/// /// Counts the atoms and the boolean operators of an expression.
+/// #[derive(Default)]
+/// struct CountingVisitor {
+///     atoms: usize,
+///     operators: usize,
+/// }
+///
+/// impl SelectExpressionVisitor<'_> for CountingVisitor {
+///     fn visit_atom(&mut self, _criteria: &SelectionCriteria) {
+///         self.atoms += 1;
+///     }
+///     fn visit_and(&mut self, _exprs: &[SelectExpression]) {
+///         self.operators += 1;
+///     }
+///     fn visit_or(&mut self, _exprs: &[SelectExpression]) {
+///         self.operators += 1;
+///     }
+/// }
+///
+/// let expr = parse_model_specifiers(&["tag:a,tag:b".to_string(), "c".to_string()]).unwrap();
+/// let mut visitor = CountingVisitor::default();
+/// walk(&mut visitor, &expr);
+/// assert_eq!((visitor.atoms, visitor.operators), (3, 2));
+/// ```
+pub trait SelectExpressionVisitor<'a> {
+    /// An atom; the exclude nested in its criteria, if any, is walked next.
+    fn visit_atom(&mut self, _criteria: &'a SelectionCriteria) {}
+    fn visit_and(&mut self, _exprs: &'a [SelectExpression]) {}
+    fn visit_or(&mut self, _exprs: &'a [SelectExpression]) {}
+    fn visit_exclude(&mut self, _expr: &'a SelectExpression) {}
+    fn visit_not(&mut self, _expr: &'a SelectExpression) {}
+    fn visit_empty(&mut self) {}
+    /// Called once `expr` and everything below it has been walked.
+    fn leave(&mut self, _expr: &'a SelectExpression) {}
+}
+
+/// Walk `expr` depth first and left to right, calling the `visit_*` method
+/// of `visitor` for each node before its operands, and `leave` after them.
+/// Excludes nested in an atom's criteria are walked too.
+pub fn walk<'a, V: SelectExpressionVisitor<'a> + ?Sized>(
+    visitor: &mut V,
+    expr: &'a SelectExpression,
+) {
+    match expr {
+        SelectExpression::Atom(criteria) => {
+            visitor.visit_atom(criteria);
+            if let Some(exclude) = &criteria.exclude {
+                walk(visitor, exclude);
+            }
+        }
+        SelectExpression::And(exprs) => {
+            visitor.visit_and(exprs);
+            for expr in exprs {
+                walk(visitor, expr);
+            }
+        }
+        SelectExpression::Or(exprs) => {
+            visitor.visit_or(exprs);
+            for expr in exprs {
+                walk(visitor, expr);
+            }
+        }
+        SelectExpression::Exclude(inner) => {
+            visitor.visit_exclude(inner);
+            walk(visitor, inner);
+        }
+        SelectExpression::Not(inner) => {
+            visitor.visit_not(inner);
+            walk(visitor, inner);
+        }
+        SelectExpression::Empty => visitor.visit_empty(),
+    }
+    visitor.leave(expr);
+}
+
+/// Computes [`SelectExpression::stable_hash`] bottom up: on leaving a node,
+/// the hashes of its operands are on top of the stack.
+#[derive(Default)]
+struct StableHashVisitor {
+    operands: Vec<u64>,
+}
+
+impl StableHashVisitor {
+    fn pop_operands(&mut self, count: usize) -> Vec<u64> {
+        self.operands.split_off(self.operands.len() - count)
+    }
+}
+
+impl SelectExpressionVisitor<'_> for StableHashVisitor {
+    fn leave(&mut self, expr: &SelectExpression) {
+        let mut hasher = SipHasher13::new();
+        match expr {
+            SelectExpression::Atom(criteria) => {
+                let exclude = self.pop_operands(criteria.exclude.iter().count());
+                write_str(&mut hasher, "atom");
+                write_str(&mut hasher, &criteria.qualifier());
+                write_str(&mut hasher, &criteria.value);
+                hasher.write_u8(criteria.childrens_parents as u8);
+                for depth in [criteria.parents_depth, criteria.children_depth] {
+                    match depth {
+                        Some(depth) => hasher.write(&(u64::from(depth) + 1).to_le_bytes()),
+                        None => hasher.write(&0u64.to_le_bytes()),
+                    }
+                }
+                write_str(&mut hasher, &criteria.effective_indirect().to_string());
+                for hash in exclude {
+                    hasher.write(&hash.to_le_bytes());
+                }
+            }
+            SelectExpression::And(exprs) | SelectExpression::Or(exprs) => {
+                let op = if matches!(expr, SelectExpression::And(_)) {
+                    "and"
+                } else {
+                    "or"
+                };
+                write_str(&mut hasher, op);
+                let mut operands = self.pop_operands(exprs.len());
+                operands.sort_unstable();
+                for operand in operands {
+                    hasher.write(&operand.to_le_bytes());
+                }
+            }
+            SelectExpression::Exclude(_) | SelectExpression::Not(_) => {
+                let op = if matches!(expr, SelectExpression::Exclude(_)) {
+                    "exclude"
+                } else {
+                    "not"
+                };
+                write_str(&mut hasher, op);
+                for operand in self.pop_operands(1) {
+                    hasher.write(&operand.to_le_bytes());
+                }
+            }
+            SelectExpression::Empty => write_str(&mut hasher, "empty"),
+        }
+        self.operands.push(hasher.finish());
+    }
+}
+
+/// Escape `s` for use inside a double-quoted DOT string.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Converts every `column:` selector in the expression into an equivalent `fqn:` selector.
+///
+/// Returns the converted expression along with a boolean indicating whether any
+/// conversion occurred.
+pub fn convert_column_selectors_to_fqn(expr: SelectExpression) -> (SelectExpression, bool) {
+    match expr {
+        SelectExpression::Atom(mut criteria) => {
+            let mut converted = false;
+
+            if let Some(inner) = criteria.exclude.take() {
+                let (inner_converted, inner_changed) = convert_column_selectors_to_fqn(*inner);
+                criteria.exclude = Some(Box::new(inner_converted));
+                converted |= inner_changed;
+            }
+
+            if criteria.method == MethodName::Column {
+                converted = true;
+                let mut parts = criteria.value.rsplitn(2, '.');
+                let _column_part = parts.next().unwrap_or("");
+                let node_part = parts.next().unwrap_or("");
+                criteria.method = MethodName::Fqn;
+                criteria.value = node_part.to_string();
+            }
+
+            (SelectExpression::Atom(criteria), converted)
+        }
+        SelectExpression::And(expressions) => {
+            let mut converted = false;
+            let converted_exprs = expressions
+                .into_iter()
+                .map(|expr| {
+                    let (expr_converted, expr_changed) = convert_column_selectors_to_fqn(expr);
+                    converted |= expr_changed;
+                    expr_converted
+                })
+                .collect();
+            (SelectExpression::And(converted_exprs), converted)
+        }
+        SelectExpression::Or(expressions) => {
+            let mut converted = false;
+            let converted_exprs = expressions
+                .into_iter()
+                .map(|expr| {
+                    let (expr_converted, expr_changed) = convert_column_selectors_to_fqn(expr);
+                    converted |= expr_changed;
+                    expr_converted
+                })
+                .collect();
+            (SelectExpression::Or(converted_exprs), converted)
+        }
+        SelectExpression::Exclude(expr) => {
+            let (converted_expr, converted) = convert_column_selectors_to_fqn(*expr);
+            (
+                SelectExpression::Exclude(Box::new(converted_expr)),
+                converted,
+            )
+        }
+        SelectExpression::Not(expr) => {
+            let (converted_expr, converted) = convert_column_selectors_to_fqn(*expr);
+            (SelectExpression::Not(Box::new(converted_expr)), converted)
+        }
+        SelectExpression::Empty => (SelectExpression::Empty, false),
+    }
+}
+
+/// The attributes of a node passed to a [`SelectorMethod`].
+#[derive(Debug, Clone, Copy)]
+pub struct NodeMeta<'a> {
+    pub unique_id: &'a str,
+    pub resource_type: &'a str,
+    pub package_name: &'a str,
+    pub fqn: &'a [String],
+    pub path: &'a str,
+    pub tags: &'a [String],
+}
+
+/// A selector method provided outside this crate, e.g. `org:acme`.
+pub trait SelectorMethod: Send + Sync {
+    /// Whether `node` is selected by `<method>.<args>:<value>`.
+    fn matches(&self, node: &NodeMeta<'_>, value: &str, args: &[String]) -> bool;
+}
+
+/// Selector methods registered in addition to the built-in [`MethodName`]s.
+#[derive(Default)]
+pub struct CustomMethodRegistry {
+    methods: BTreeMap<String, Box<dyn SelectorMethod>>,
+}
+
+impl CustomMethodRegistry {
+    /// Fails if `name` is a built-in method or already registered.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        method: impl SelectorMethod + 'static,
+    ) -> FsResult<()> {
+        let name = name.into();
+        if MethodName::from_str(&name).is_ok() {
+            return err!(
+                ErrorCode::SelectorError,
+                "Cannot register `{}`: it is a built-in selector method",
+                name
+            );
+        }
+        if self.methods.contains_key(&name) {
+            return err!(
+                ErrorCode::SelectorError,
+                "Selector method `{}` is already registered",
+                name
+            );
+        }
+        self.methods.insert(name, Box::new(method));
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.methods.contains_key(name)
+    }
+
+    /// Apply the method `name` to `node`; unknown methods match nothing.
+    pub fn matches(&self, name: &str, node: &NodeMeta<'_>, value: &str, args: &[String]) -> bool {
+        self.methods
+            .get(name)
+            .is_some_and(|method| method.matches(node, value, args))
+    }
+}
+
+static CUSTOM_METHODS: LazyLock<RwLock<CustomMethodRegistry>> = LazyLock::new(Default::default);
+
+/// Register a selector method for the whole process, so that `name:value`
+/// parses in `--select` strings and YAML selectors.
+pub fn register_method(
+    name: impl Into<String>,
+    method: impl SelectorMethod + 'static,
+) -> FsResult<()> {
+    CUSTOM_METHODS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .register(name, method)
+}
+
+pub fn is_custom_method(name: &str) -> bool {
+    CUSTOM_METHODS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .contains(name)
+}
+
+/// Evaluate a `MethodName::Custom` criteria against `node` using the
+/// registered method named by its first method argument.
+pub fn custom_method_matches(criteria: &SelectionCriteria, node: &NodeMeta<'_>) -> bool {
+    let Some((name, args)) = criteria.method_args.split_first() else {
+        return false;
+    };
+    CUSTOM_METHODS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .matches(name, node, &criteria.value, args)
+}
+
+/// Checks if a `SelectExpression` contains any `state:modified` or `state:new` selectors.
+///
+/// This is useful for determining whether loading the manifest.json is required for
+/// a given selector. If the selector only uses methods like `source_status:fresher+`,
+/// then the manifest is not needed and we should not warn if it fails to load.
+pub fn contains_state_modified_or_new_selector(expr: &SelectExpression) -> bool {
+    match expr {
+        SelectExpression::Atom(criteria) => {
+            if criteria.method == MethodName::State {
+                matches!(
+                    criteria.state_method(),
+                    Some(StateMethod::Modified | StateMethod::New)
+                )
+            } else {
+                // Also check nested excludes
+                criteria
+                    .exclude
+                    .as_ref()
+                    .map(|e| contains_state_modified_or_new_selector(e))
+                    .unwrap_or(false)
+            }
+        }
+        SelectExpression::And(expressions) | SelectExpression::Or(expressions) => expressions
+            .iter()
+            .any(contains_state_modified_or_new_selector),
+        SelectExpression::Exclude(expr) | SelectExpression::Not(expr) => {
+            contains_state_modified_or_new_selector(expr)
+        }
+        SelectExpression::Empty => false,
+    }
+}
+
+/// Checks if any of the provided optional select expressions contain `state:modified` or `state:new`.
+pub fn selectors_require_manifest(
+    select: Option<&SelectExpression>,
+    exclude: Option<&SelectExpression>,
+) -> bool {
+    let select_requires = select.is_some_and(contains_state_modified_or_new_selector);
+    let exclude_requires = exclude.is_some_and(contains_state_modified_or_new_selector);
+    select_requires || exclude_requires
+}
+
+// ------------------------------------------------------------------------------------------------
+pub fn conjoin_expression(
+    maybe_select_expression: Option<SelectExpression>,
+    maybe_select_expression2: Option<SelectExpression>,
+) -> Option<SelectExpression> {
+    match (maybe_select_expression, maybe_select_expression2) {
+        (Some(select), Some(select2)) => Some(SelectExpression::And(vec![select, select2])),
+        (Some(select), None) => Some(select),
+        (None, Some(select)) => Some(select),
+        (None, None) => None,
+    }
+}
+
+/// Everything selected by `include` that is not selected by `exclude`.
+pub fn difference(include: SelectExpression, exclude: SelectExpression) -> SelectExpression {
+    SelectExpression::And(vec![include, SelectExpression::Exclude(Box::new(exclude))])
+}
+
+/// Everything selected by both `a` and `b`. Operands that are themselves
+/// intersections are flattened into the result.
+pub fn intersection(a: SelectExpression, b: SelectExpression) -> SelectExpression {
+    n_ary_intersection(vec![a, b])
+}
+
+/// Everything selected by `a` or `b`. Operands that are themselves unions
+/// are flattened into the result.
+pub fn union(a: SelectExpression, b: SelectExpression) -> SelectExpression {
+    n_ary_union(vec![a, b])
+}
+
+/// Intersection of all of `exprs`, flattening nested intersections. A
+/// single operand is returned as is and no operands give `Empty`.
+pub fn n_ary_intersection(exprs: Vec<SelectExpression>) -> SelectExpression {
+    let mut operands = Vec::with_capacity(exprs.len());
+    for expr in exprs {
+        match expr {
+            // An empty `And` selects nothing, so it must stay an operand.
+            SelectExpression::And(inner) if !inner.is_empty() => operands.extend(inner),
+            expr => operands.push(expr),
+        }
+    }
+    match operands.len() {
+        0 => SelectExpression::Empty,
+        1 => operands.pop().unwrap(),
+        _ => SelectExpression::And(operands),
+    }
+}
+
+/// Union of all of `exprs`, flattening nested unions. A single operand is
+/// returned as is and no operands give `Empty`.
+pub fn n_ary_union(exprs: Vec<SelectExpression>) -> SelectExpression {
+    let mut operands = Vec::with_capacity(exprs.len());
+    for expr in exprs {
+        match expr {
+            SelectExpression::Or(inner) => operands.extend(inner),
+            expr => operands.push(expr),
+        }
+    }
+    match operands.len() {
+        0 => SelectExpression::Empty,
+        1 => operands.pop().unwrap(),
+        _ => SelectExpression::Or(operands),
+    }
+}
+
+/// ----------------------------------
+/// parsing
+/// ----------------------------------
+static RAW_SELECTOR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)
+^
+(?P<childrens_parents>@)?
+(?P<parents>(?P<parents_depth>\d*)\+)?   # optional leading +N
+(?:(?P<method>[\w.]+|\*):)?              # optional qualifier, or `*`
+(?P<value>.*?)
 (?P<children>\+(?P<children_depth>\d*) )?  # optional trailing +N
 $
 ",
@@ -387,711 +1509,1799 @@ $
     .unwrap()
 });
 
-pub fn parse_single_selector(raw: &str) -> FsResult<SelectionCriteria> {
-    let caps = RAW_SELECTOR_RE
-        .captures(raw)
-        .ok_or_else(|| fs_err!(ErrorCode::SelectorError, "Invalid selector spec: `{}`", raw))?;
+/// Split a selector qualifier such as `config.materialized` into its method
+/// and arguments. Built-in methods take precedence over methods registered
+/// with [`register_method`]; returns `None` if the method is neither.
+pub fn parse_method_qualifier(qualifier: &str) -> Option<(MethodName, Vec<String>)> {
+    let mut parts = qualifier.split('.').map(str::to_string);
+    let head = parts.next()?;
+    match MethodName::from_str(&head) {
+        Ok(name) if name != MethodName::Custom => Some((name, parts.collect())),
+        _ if is_custom_method(&head) => Some((
+            MethodName::Custom,
+            std::iter::once(head).chain(parts).collect(),
+        )),
+        _ => None,
+    }
+}
+
+pub fn parse_single_selector(raw: &str) -> FsResult<SelectionCriteria> {
+    let caps = RAW_SELECTOR_RE
+        .captures(raw)
+        .ok_or_else(|| fs_err!(ErrorCode::SelectorError, "Invalid selector spec: `{}`", raw))?;
+
+    // ------------------------------------------------------------------
+    // Reject selectors whose <value> is empty (e.g. "++", "@", "+:").
+    // ------------------------------------------------------------------
+    let val = caps.name("value").unwrap().as_str();
+    if val.is_empty() {
+        return err!(ErrorCode::SelectorError, "Invalid selector spec: `{}`", raw);
+    }
+
+    // ---------------- method / args
+    let (method, method_args) = if let Some(m) = caps.name("method") {
+        parse_method_qualifier(m.as_str()).ok_or_else(|| {
+            fs_err!(
+                ErrorCode::SelectorError,
+                "Invalid node selector method: `{}`",
+                m.as_str().split('.').next().unwrap_or_default()
+            )
+        })?
+    } else {
+        let v = caps.name("value").unwrap().as_str();
+        (MethodName::default_for(v), vec![])
+    };
+
+    // ---------------- depths & flags
+    let parents_depth = if caps.name("parents").is_some() {
+        caps.name("parents_depth")
+            .map(|m| {
+                if m.as_str().is_empty() {
+                    u32::MAX
+                } else {
+                    m.as_str().parse::<u32>().unwrap()
+                }
+            })
+            .or(Some(u32::MAX))
+    } else {
+        None
+    };
+
+    let children_depth = if caps.name("children").is_some() {
+        caps.name("children_depth")
+            .map(|m| {
+                if m.as_str().is_empty() {
+                    u32::MAX
+                } else {
+                    m.as_str().parse::<u32>().unwrap()
+                }
+            })
+            .or(Some(u32::MAX))
+    } else {
+        None
+    };
+
+    let criteria = SelectionCriteria::new(
+        method,
+        method_args,
+        caps.name("value").unwrap().as_str().to_string(),
+        caps.name("childrens_parents").is_some(),
+        parents_depth,
+        children_depth,
+        Some(IndirectSelection::default()), // CLI flag can override later
+        None,
+    );
+
+    //---------------------------------------------------------------
+    // `@foo+` is illegal
+    //---------------------------------------------------------------
+    if criteria.childrens_parents && criteria.children_depth.is_some() {
+        return err!(
+            ErrorCode::SelectorError,
+            "Invalid selector `{}` - \"@\" and trailing \"+\" are incompatible",
+            raw
+        );
+    }
+
+    //---------------------------------------------------------------
+    // `column:` must have a column name after the dot
+    //     e.g.  column:node123.foo_col   ✔
+    //           column:model.node123.col ✔
+    //           column:node123.          ✘
+    //---------------------------------------------------------------
+    if criteria.method == MethodName::Column {
+        // we only need to make sure there is *some* column name
+        match criteria.value.rfind('.') {
+            // a dot exists and it's not the last char ⇒ we're good
+            Some(ix) if ix < criteria.value.len() - 1 => {}
+            _ => {
+                return err!(ErrorCode::SelectorError, "Invalid selector spec: `{}`", raw);
+            }
+        }
+    }
+    //---------------------------------------------------------------
+    // If we see a '+' inside <value> but *didn't* set either the
+    // `parents_depth` or `children_depth`, then the user wrote something
+    // like  `identifier+abc`, i.e. a "depth" that isn't numeric.
+    //---------------------------------------------------------------
+    if criteria.parents_depth.is_none()
+        && criteria.children_depth.is_none()
+        && criteria.value.contains('+')
+    {
+        return err!(
+            ErrorCode::SelectorError,
+            "Invalid model specifier near: {}",
+            raw
+        );
+    }
+
+    Ok(criteria)
+}
+
+/// Turn the tokenised CLI list (already split on whitespace by Clap)
+/// into a `SelectExpression` tree.
+///
+/// * **Outer level**: each whitespace‑separated token is an *OR* term.
+/// * **Inner level**: inside each token, a comma `,` separates *AND* terms.
+///
+/// A few helper invariants make the logic easy to follow:
+///
+/// * A single criterion becomes `SelectExpression::Atom`.
+/// * A list of N criteria joined by the same operator collapses to
+///   `SelectExpression::And(vec)` or `SelectExpression::Or(vec)` where `vec.len() == N`.
+/// * We never wrap a single Atom in an unnecessary `And/Or`.
+pub fn parse_model_specifiers(tokens: &[String]) -> FsResult<SelectExpression> {
+    if tokens.is_empty() {
+        return Err(fs_err!(
+            ErrorCode::SelectorError,
+            "empty selector list passed to --select/--exclude"
+        ));
+    }
+
+    // ----------- build OR level -------------------------------------------------------------
+    let mut or_terms: Vec<SelectExpression> = Vec::new();
+
+    for token in tokens {
+        // Skip completely empty tokens (can happen after user writes two spaces in a row)
+        if token.trim().is_empty() {
+            continue;
+        }
+
+        // ----------- build AND level --------------------------------------------------------
+        let mut and_terms: Vec<SelectExpression> = Vec::new();
+        for piece in token.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let criteria = parse_single_selector(piece)?;
+            and_terms.push(SelectExpression::Atom(criteria));
+        }
+
+        // collapse AND level
+        let inner_expr = match and_terms.len() {
+            0 => continue,                 // nothing in this token → ignore it
+            1 => and_terms.pop().unwrap(), // single item – no extra wrapper
+            _ => SelectExpression::And(and_terms),
+        };
+
+        or_terms.push(inner_expr);
+    }
+
+    // collapse OR level
+    let root = match or_terms.len() {
+        0 => {
+            return Err(fs_err!(
+                ErrorCode::SelectorError,
+                "selector contained only delimiters but no actual criteria"
+            ));
+        }
+        1 => or_terms.pop().unwrap(),
+        _ => SelectExpression::Or(or_terms),
+    };
+
+    Ok(root)
+}
+
+/// helper used by the CLI to add an extra criterion (resource_type etc.)
+pub fn conjoin_predicate(
+    maybe_sel: Option<SelectExpression>,
+    extra: Option<SelectionCriteria>,
+) -> Option<SelectExpression> {
+    match (maybe_sel, extra) {
+        (None, None) => None,
+        (Some(sel), None) => Some(sel),
+        (None, Some(c)) => Some(SelectExpression::Atom(c)),
+        (Some(sel), Some(c)) => {
+            let atom = SelectExpression::Atom(c);
+            Some(match sel {
+                SelectExpression::And(mut v) => {
+                    v.push(atom);
+                    SelectExpression::And(v)
+                }
+                _ => SelectExpression::And(vec![sel, atom]),
+            })
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_identifier() -> FsResult<()> {
+        let result = parse_single_selector("identifier")?;
+        assert_eq!(result, SelectionCriteria::new_fqn("identifier"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_at_pattern() -> FsResult<()> {
+        let result = parse_single_selector("@identifier")?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                .with_childrens_parents()
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plus_pattern_leading_number() -> FsResult<()> {
+        let result = parse_single_selector("2+identifier")?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                .with_parents_depth(2)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plus_pattern_trailing_plus() -> FsResult<()> {
+        let result = parse_single_selector("identifier+")?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                .with_children_depth(u32::MAX)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plus_pattern_trailing_number() -> FsResult<()> {
+        let input = "identifier+8";
+        let result = parse_model_specifiers(&[input.to_string()])?;
+        assert_eq!(
+            result,
+            SelectExpression::Atom(
+                SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                    .with_children_depth(8)
+                    .build()
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plus_pattern_both_plus() -> FsResult<()> {
+        let input = "+identifier+";
+        let result = parse_single_selector(input)?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                .with_parents_depth(u32::MAX)
+                .with_children_depth(u32::MAX)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plus_path_pattern_both_plus() -> FsResult<()> {
+        let input = "+path:identifier/rest+";
+        let result = parse_single_selector(input)?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Path, "identifier/rest")
+                .with_parents_depth(u32::MAX)
+                .with_children_depth(u32::MAX)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plus_pattern_leading_number_trailing_plus() -> FsResult<()> {
+        let input = "5+identifier+";
+        let result = parse_single_selector(input)?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                .with_parents_depth(5)
+                .with_children_depth(u32::MAX)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plus_pattern_leading_plus_trailing_number() -> FsResult<()> {
+        let input = "+identifier+6";
+        let result = parse_single_selector(input)?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                .with_parents_depth(u32::MAX)
+                .with_children_depth(6)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_named_constructors_match_parser() -> FsResult<()> {
+        assert_eq!(
+            SelectionCriteria::new_fqn("model_a"),
+            parse_single_selector("model_a")?
+        );
+        assert_eq!(
+            SelectionCriteria::new_tag("nightly"),
+            parse_single_selector("tag:nightly")?
+        );
+        assert_eq!(
+            SelectionCriteria::new_path("models/staging"),
+            parse_single_selector("models/staging")?
+        );
+        assert_eq!(
+            SelectionCriteria::new_package("dbt_utils"),
+            parse_single_selector("package:dbt_utils")?
+        );
+        assert_eq!(
+            SelectionCriteria::builder(MethodName::Config, "table")
+                .with_method_args(vec!["materialized".to_string()])
+                .with_parents_depth(2)
+                .with_children_depth(u32::MAX)
+                .with_indirect(IndirectSelection::Cautious)
+                .build(),
+            SelectionCriteria::new(
+                MethodName::Config,
+                vec!["materialized".to_string()],
+                "table".to_string(),
+                false,
+                Some(2),
+                Some(u32::MAX),
+                Some(IndirectSelection::Cautious),
+                None,
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_criteria_with_methods() -> FsResult<()> {
+        let slow = SelectExpression::Atom(SelectionCriteria::new_tag("slow"));
+        let criteria = SelectionCriteria::new_path("models/marts")
+            .with_parents(Some(2))
+            .with_children(Some(u32::MAX))
+            .with_exclude(slow.clone());
+        let mut parsed = parse_single_selector("2+path:models/marts+")?;
+        parsed.exclude = Some(Box::new(slow));
+        assert_eq!(criteria, parsed);
+
+        let cleared = criteria.with_parents(None).with_children(None);
+        assert_eq!(cleared.parents_depth, None);
+        assert_eq!(cleared.children_depth, None);
+        assert!(cleared.exclude.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_method_registry() -> FsResult<()> {
+        struct HasTag;
+        impl SelectorMethod for HasTag {
+            fn matches(&self, node: &NodeMeta<'_>, value: &str, args: &[String]) -> bool {
+                args.is_empty() && node.tags.iter().any(|tag| tag == value)
+            }
+        }
+
+        let mut registry = CustomMethodRegistry::default();
+        registry.register("has_tag", HasTag)?;
+        assert!(registry.contains("has_tag"));
+        assert!(registry.register("has_tag", HasTag).is_err());
+        assert!(registry.register("config", HasTag).is_err());
+
+        let tags = vec!["nightly".to_string()];
+        let node = NodeMeta {
+            unique_id: "model.pkg.a",
+            resource_type: "model",
+            package_name: "pkg",
+            fqn: &[],
+            path: "models/a.sql",
+            tags: &tags,
+        };
+        assert!(registry.matches("has_tag", &node, "nightly", &[]));
+        assert!(!registry.matches("has_tag", &node, "hourly", &[]));
+        assert!(!registry.matches("unknown", &node, "nightly", &[]));
+
+        // only methods registered globally parse
+        assert!(parse_single_selector("has_tag:nightly").is_err());
+        assert_eq!(parse_method_qualifier("custom"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_method() -> FsResult<()> {
+        let result = parse_single_selector("group:finance")?;
+        assert_eq!(result.method, MethodName::Group);
+        assert_eq!(result.value, "finance");
+
+        // unqualified values never infer `group:`
+        for value in ["group", "finance", "finance.*", "group.finance"] {
+            assert_eq!(MethodName::default_for(value), MethodName::Fqn);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_id_method() -> FsResult<()> {
+        assert_eq!(MethodName::from_str("node_id")?, MethodName::NodeId);
+        assert_eq!(MethodName::NodeId.to_string(), "node_id");
+
+        let result = parse_single_selector("node_id:model.my_project.my_model")?;
+        assert_eq!(result.method, MethodName::NodeId);
+        assert_eq!(result.value, "model.my_project.my_model");
+        assert_eq!(
+            result.to_selector_string().as_deref(),
+            Some("node_id:model.my_project.my_model")
+        );
+
+        // unique ids and plain names never infer `node_id:`
+        for value in ["my_model", "model.my_project.my_model", "node_id"] {
+            assert_eq!(MethodName::default_for(value), MethodName::Fqn);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_wildcard_method() -> FsResult<()> {
+        for name in ["all", "ALL", "*"] {
+            assert_eq!(MethodName::from_str(name)?, MethodName::Wildcard);
+        }
+        assert_eq!(MethodName::Wildcard.to_string(), "all");
+
+        let result = parse_single_selector("all:*")?;
+        assert_eq!(result.method, MethodName::Wildcard);
+        assert_eq!(result.value, "*");
+        assert_eq!(result.to_selector_string().as_deref(), Some("all:*"));
+
+        let result = parse_single_selector("*:anything")?;
+        assert_eq!(result.method, MethodName::Wildcard);
+        assert_eq!(result.value, "anything");
+        assert_eq!(result.to_selector_string().as_deref(), Some("all:anything"));
+        let result = parse_single_selector("+*:x")?;
+        assert_eq!(result.method, MethodName::Wildcard);
+        assert_eq!(result.parents_depth, Some(u32::MAX));
+
+        // unqualified values never infer `all:`
+        for value in ["*", "all", "wildcard"] {
+            assert_eq!(MethodName::default_for(value), MethodName::Fqn);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_semantic_layer_methods() -> FsResult<()> {
+        let result = parse_single_selector("semantic_model:orders_by_status")?;
+        assert_eq!(result.method, MethodName::SemanticModel);
+        assert_eq!(result.value, "orders_by_status");
+        assert_eq!(
+            parse_single_selector("saved_query:weekly_orders")?.method,
+            MethodName::SavedQuery
+        );
+
+        // unqualified values never infer a semantic layer method
+        for value in ["semantic_model", "orders_by_status", "saved_query"] {
+            assert_eq!(MethodName::default_for(value), MethodName::Fqn);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_trailing_number() {
+        let input = "identifier+abc";
+        let result = parse_single_selector(input);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(
+                e.to_string().trim(),
+                "Invalid model specifier near: identifier+abc"
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_string() {
+        let input = "";
+        let result = parse_single_selector(input);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(e.to_string().trim(), "Invalid selector spec: ``");
+        }
+    }
+
+    #[test]
+    fn test_only_plus_signs() {
+        let input = "++";
+        let result = parse_single_selector(input);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(e.to_string().trim(), "Invalid selector spec: `++`");
+        }
+    }
+
+    #[test]
+    fn invalid_at_pattern_with_plus() {
+        let input = "@identifier+";
+        let result = parse_single_selector(input);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(
+                e.to_string().trim(),
+                "Invalid selector `@identifier+` - \"@\" and trailing \"+\" are incompatible"
+            );
+        }
+    }
+
+    #[test]
+    fn test_and_specifier() -> FsResult<()> {
+        let input = vec!["identifier,@identifier".to_string()];
+        let result = parse_model_specifiers(&input)?;
+        match result {
+            SelectExpression::And(vec) if vec.len() == 2 => match (&vec[0], &vec[1]) {
+                (SelectExpression::Atom(x), SelectExpression::Atom(y)) => {
+                    assert_eq!(x, &SelectionCriteria::new_fqn("identifier"));
+                    assert_eq!(
+                        y,
+                        &SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                            .with_childrens_parents()
+                            .build()
+                    );
+                }
+                _ => panic!("Expected SelectExpr::Or variant with two Atom elements"),
+            },
+            _ => panic!("Expected SelectExpr::And variant with one element"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_or_specifier() -> FsResult<()> {
+        let input = vec!["identifier".to_string(), "@identifier".to_string()];
+        let result = parse_model_specifiers(&input)?;
+        match result {
+            SelectExpression::Or(vec) if vec.len() == 2 => match (&vec[0], &vec[1]) {
+                (SelectExpression::Atom(x), SelectExpression::Atom(y)) => {
+                    assert_eq!(x, &SelectionCriteria::new_fqn("identifier"));
+                    assert_eq!(
+                        y,
+                        &SelectionCriteria::builder(MethodName::Fqn, "identifier")
+                            .with_childrens_parents()
+                            .build()
+                    );
+                }
+                _ => panic!("Expected SelectExpr::Or variant with two Atom elements"),
+            },
+            _ => panic!("Expected SelectExpr::Or variant with two elements"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_selector_identifier() -> FsResult<()> {
+        let input = "column:node123.foo_col";
+        let result = parse_single_selector(input)?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Column, "node123.foo_col").build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_selector_with_leading_plus() -> FsResult<()> {
+        let input = "+column:node123.foo_col";
+        let result = parse_single_selector(input)?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Column, "node123.foo_col")
+                .with_parents_depth(u32::MAX)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_selector_with_trailing_plus() -> FsResult<()> {
+        let input = "column:node123.foo_col+";
+        let result = parse_single_selector(input)?;
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Column, "node123.foo_col")
+                .with_children_depth(u32::MAX)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_selector_with_both_plus() -> FsResult<()> {
+        let input = "+column:node123.foo_col+";
+        let result = parse_single_selector(input)?;
+
+        assert_eq!(
+            result,
+            SelectionCriteria::builder(MethodName::Column, "node123.foo_col")
+                .with_parents_depth(u32::MAX)
+                .with_children_depth(u32::MAX)
+                .build()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_column_selector_with_missing_column_name() {
+        let input = "column:node123.";
+        let result = parse_single_selector(input);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(
+                e.to_string().trim(),
+                "Invalid selector spec: `column:node123.`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_column_selectors_with_trailing_plus() -> FsResult<()> {
+        let input = vec![
+            "column:node123.foo_col+".to_string(),
+            "column:node123.bar_col+".to_string(),
+        ];
+        let result = parse_model_specifiers(&input)?;
+
+        match result {
+            SelectExpression::Or(vec) if vec.len() == 2 => match (&vec[0], &vec[1]) {
+                (SelectExpression::Atom(a), SelectExpression::Atom(b)) => {
+                    assert_eq!(
+                        a,
+                        &SelectionCriteria::builder(MethodName::Column, "node123.foo_col")
+                            .with_children_depth(u32::MAX)
+                            .build()
+                    );
+                    assert_eq!(
+                        b,
+                        &SelectionCriteria::builder(MethodName::Column, "node123.bar_col")
+                            .with_children_depth(u32::MAX)
+                            .build()
+                    );
+                }
+                _ => panic!("Expected Atom variants"),
+            },
+            _ => panic!("Expected SelectExpr::Or expression with 2 atoms"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_selector_with_unbounded_plus() -> FsResult<()> {
+        // Test that u32::MAX is not displayed as a number (should just be "+")
+        let criteria = SelectionCriteria::builder(MethodName::Fqn, "model_a")
+            .with_children_depth(u32::MAX)
+            .build();
+        assert_eq!(criteria.to_string(), "fqn:model_a+");
+
+        // Test with explicit depth number
+        let criteria_with_depth = SelectionCriteria::builder(MethodName::Fqn, "model_a")
+            .with_children_depth(3)
+            .build();
+        assert_eq!(criteria_with_depth.to_string(), "fqn:model_a+3");
+
+        // Test with unbounded parents
+        let criteria_parents = SelectionCriteria::builder(MethodName::Fqn, "model_a")
+            .with_parents_depth(u32::MAX)
+            .build();
+        assert_eq!(criteria_parents.to_string(), "+fqn:model_a");
+
+        // Test with both unbounded
+        let criteria_both = SelectionCriteria::builder(MethodName::Fqn, "model_a")
+            .with_parents_depth(u32::MAX)
+            .with_children_depth(u32::MAX)
+            .build();
+        assert_eq!(criteria_both.to_string(), "+fqn:model_a+");
 
-    // ------------------------------------------------------------------
-    // Reject selectors whose <value> is empty (e.g. "++", "@", "+:").
-    // ------------------------------------------------------------------
-    let val = caps.name("value").unwrap().as_str();
-    if val.is_empty() {
-        return err!(ErrorCode::SelectorError, "Invalid selector spec: `{}`", raw);
+        Ok(())
     }
 
-    // ---------------- method / args
-    let (method, method_args) = if let Some(m) = caps.name("method") {
-        let mut parts = m.as_str().split('.').map(|s| s.to_string());
-        let head = parts.next().unwrap();
-        let name = MethodName::from_str(&head).map_err(|_| {
-            fs_err!(
-                ErrorCode::SelectorError,
-                "Invalid node selector method: `{}`",
-                head
-            )
-        })?;
-        (name, parts.collect())
-    } else {
-        let v = caps.name("value").unwrap().as_str();
-        (MethodName::default_for(v), vec![])
-    };
+    fn fqn(value: &str) -> SelectExpression {
+        SelectExpression::Atom(parse_single_selector(value).unwrap())
+    }
 
-    // ---------------- depths & flags
-    let parents_depth = if caps.name("parents").is_some() {
-        caps.name("parents_depth")
-            .map(|m| {
-                if m.as_str().is_empty() {
-                    u32::MAX
-                } else {
-                    m.as_str().parse::<u32>().unwrap()
-                }
-            })
-            .or(Some(u32::MAX))
-    } else {
-        None
-    };
+    #[test]
+    fn test_simplify_collapses_single_item_wrappers() {
+        let expr = SelectExpression::And(vec![
+            SelectExpression::Or(vec![fqn("a")]),
+            SelectExpression::And(vec![fqn("b")]),
+        ]);
+        assert_eq!(
+            expr.simplify(),
+            SelectExpression::And(vec![fqn("a"), fqn("b")])
+        );
 
-    let children_depth = if caps.name("children").is_some() {
-        caps.name("children_depth")
-            .map(|m| {
-                if m.as_str().is_empty() {
-                    u32::MAX
-                } else {
-                    m.as_str().parse::<u32>().unwrap()
-                }
-            })
-            .or(Some(u32::MAX))
-    } else {
-        None
-    };
+        let single = SelectExpression::Or(vec![SelectExpression::And(vec![fqn("a")])]);
+        assert_eq!(single.simplify(), fqn("a"));
+    }
 
-    let criteria = SelectionCriteria::new(
-        method,
-        method_args,
-        caps.name("value").unwrap().as_str().to_string(),
-        caps.name("childrens_parents").is_some(),
-        parents_depth,
-        children_depth,
-        Some(IndirectSelection::default()), // CLI flag can override later
-        None,
-    );
+    #[test]
+    fn test_simplify_flattens_nested_operators() {
+        let expr = SelectExpression::Or(vec![
+            fqn("a"),
+            SelectExpression::Or(vec![
+                fqn("b"),
+                SelectExpression::Or(vec![fqn("c"), fqn("d")]),
+            ]),
+        ]);
+        assert_eq!(
+            expr.simplify(),
+            SelectExpression::Or(vec![fqn("a"), fqn("b"), fqn("c"), fqn("d")])
+        );
 
-    //---------------------------------------------------------------
-    // `@foo+` is illegal
-    //---------------------------------------------------------------
-    if criteria.childrens_parents && criteria.children_depth.is_some() {
-        return err!(
-            ErrorCode::SelectorError,
-            "Invalid selector `{}` - \"@\" and trailing \"+\" are incompatible",
-            raw
+        let expr = SelectExpression::And(vec![
+            SelectExpression::And(vec![fqn("a"), fqn("b")]),
+            SelectExpression::Exclude(Box::new(SelectExpression::Or(vec![fqn("c")]))),
+        ]);
+        assert_eq!(
+            expr.simplify(),
+            SelectExpression::And(vec![
+                fqn("a"),
+                fqn("b"),
+                SelectExpression::Exclude(Box::new(fqn("c"))),
+            ])
         );
     }
 
-    //---------------------------------------------------------------
-    // `column:` must have a column name after the dot
-    //     e.g.  column:node123.foo_col   ✔
-    //           column:model.node123.col ✔
-    //           column:node123.          ✘
-    //---------------------------------------------------------------
-    if criteria.method == MethodName::Column {
-        // we only need to make sure there is *some* column name
-        match criteria.value.rfind('.') {
-            // a dot exists and it's not the last char ⇒ we're good
-            Some(ix) if ix < criteria.value.len() - 1 => {}
-            _ => {
-                return err!(ErrorCode::SelectorError, "Invalid selector spec: `{}`", raw);
-            }
-        }
+    #[test]
+    fn test_simplify_empty_operands() {
+        assert_eq!(
+            SelectExpression::And(vec![]).simplify(),
+            SelectExpression::Empty
+        );
+        assert_eq!(
+            SelectExpression::Or(vec![]).simplify(),
+            SelectExpression::Empty
+        );
+        assert_eq!(
+            SelectExpression::Or(vec![SelectExpression::And(vec![]), fqn("a")]).simplify(),
+            fqn("a")
+        );
+        assert_eq!(
+            SelectExpression::And(vec![fqn("a"), SelectExpression::Or(vec![])]).simplify(),
+            SelectExpression::Empty
+        );
     }
-    //---------------------------------------------------------------
-    // If we see a '+' inside <value> but *didn't* set either the
-    // `parents_depth` or `children_depth`, then the user wrote something
-    // like  `identifier+abc`, i.e. a "depth" that isn't numeric.
-    //---------------------------------------------------------------
-    if criteria.parents_depth.is_none()
-        && criteria.children_depth.is_none()
-        && criteria.value.contains('+')
-    {
-        return err!(
-            ErrorCode::SelectorError,
-            "Invalid model specifier near: {}",
-            raw
+
+    #[test]
+    fn test_tree_display() {
+        let expr = SelectExpression::And(vec![
+            SelectExpression::Atom(SelectionCriteria::new_tag("nightly")),
+            SelectExpression::Exclude(Box::new(fqn("model_slow"))),
+        ]);
+        assert_eq!(
+            format!("{expr:#}"),
+            "AND\n  tag:nightly\n  EXCLUDE\n    fqn:model_slow"
+        );
+        // the compact form is unchanged
+        assert_eq!(expr.to_string(), "tag:nightly,exclude(fqn:model_slow)");
+
+        let nested = SelectExpression::Or(vec![
+            SelectExpression::Atom(
+                SelectionCriteria::builder(MethodName::Path, "models")
+                    .with_exclude(fqn("a"))
+                    .build(),
+            ),
+            SelectExpression::Not(Box::new(SelectExpression::Empty)),
+        ]);
+        assert_eq!(
+            nested.to_tree_string("--"),
+            [
+                "OR",
+                "--path:models",
+                "----EXCLUDE",
+                "------fqn:a",
+                "--NOT",
+                "----EMPTY"
+            ]
+            .join("\n")
         );
     }
 
-    Ok(criteria)
-}
+    #[test]
+    fn test_to_dot() -> FsResult<()> {
+        let expr = parse_model_specifiers(&[
+            "tag:nightly,+orders".to_string(),
+            "path:models/\"odd\"".to_string(),
+        ])?;
+        let dot = expr.to_dot("nightly");
+        assert!(dot.starts_with("digraph \"nightly\" {\n"));
+        assert!(dot.ends_with("\n}"));
+        for line in [
+            "  n0 [label=\"Or\"];",
+            "  n0 -> n0_0;",
+            "  n0_0 [label=\"And\"];",
+            "  n0_0 -> n0_0_0;",
+            "  n0_0_0 [label=\"tag:nightly\"];",
+            "  n0_0_1 [label=\"+fqn:orders\"];",
+            "  n0 -> n0_1;",
+            "  n0_1 [label=\"path:models/\\\"odd\\\"\"];",
+        ] {
+            assert!(dot.lines().any(|l| l == line), "missing {line:?} in\n{dot}");
+        }
 
-/// Turn the tokenised CLI list (already split on whitespace by Clap)
-/// into a `SelectExpression` tree.
-///
-/// * **Outer level**: each whitespace‑separated token is an *OR* term.
-/// * **Inner level**: inside each token, a comma `,` separates *AND* terms.
-///
-/// A few helper invariants make the logic easy to follow:
-///
-/// * A single criterion becomes `SelectExpression::Atom`.
-/// * A list of N criteria joined by the same operator collapses to
-///   `SelectExpression::And(vec)` or `SelectExpression::Or(vec)` where `vec.len() == N`.
-/// * We never wrap a single Atom in an unnecessary `And/Or`.
-pub fn parse_model_specifiers(tokens: &[String]) -> FsResult<SelectExpression> {
-    if tokens.is_empty() {
-        return Err(fs_err!(
-            ErrorCode::SelectorError,
-            "empty selector list passed to --select/--exclude"
-        ));
+        let atom = SelectExpression::Atom(
+            SelectionCriteria::builder(MethodName::Tag, "a")
+                .with_exclude(SelectExpression::Not(Box::new(SelectExpression::Empty)))
+                .build(),
+        );
+        assert_eq!(
+            atom.to_dot("g"),
+            [
+                "digraph \"g\" {",
+                "  n0 [label=\"tag:a\"];",
+                "  n0_0 [label=\"Exclude\"];",
+                "  n0 -> n0_0;",
+                "  n0_0 -> n0_0_0;",
+                "  n0_0_0 [label=\"Not\"];",
+                "  n0_0_0 -> n0_0_0_0;",
+                "  n0_0_0_0 [label=\"Empty\"];",
+                "}",
+            ]
+            .join("\n")
+        );
+        Ok(())
     }
 
-    // ----------- build OR level -------------------------------------------------------------
-    let mut or_terms: Vec<SelectExpression> = Vec::new();
+    #[test]
+    fn test_stable_hash() -> FsResult<()> {
+        let a = fqn("a");
+        let b = SelectExpression::Atom(parse_single_selector("tag:b")?);
+        let or_ab = SelectExpression::Or(vec![a.clone(), b.clone()]);
+        let or_ba = SelectExpression::Or(vec![b.clone(), a.clone()]);
+        assert_eq!(or_ab.stable_hash(), or_ba.stable_hash());
+        assert_eq!(
+            SelectExpression::And(vec![a.clone(), b.clone()]).stable_hash(),
+            SelectExpression::And(vec![b.clone(), a.clone()]).stable_hash()
+        );
 
-    for token in tokens {
-        // Skip completely empty tokens (can happen after user writes two spaces in a row)
-        if token.trim().is_empty() {
-            continue;
-        }
+        // an implicit method hashes like the explicit one
+        assert_eq!(fqn("fqn:a").stable_hash(), a.stable_hash());
 
-        // ----------- build AND level --------------------------------------------------------
-        let mut and_terms: Vec<SelectExpression> = Vec::new();
-        for piece in token.split(',').map(str::trim).filter(|s| !s.is_empty()) {
-            let criteria = parse_single_selector(piece)?;
-            and_terms.push(SelectExpression::Atom(criteria));
-        }
+        assert_ne!(
+            or_ab.stable_hash(),
+            SelectExpression::And(vec![a.clone(), b.clone()]).stable_hash()
+        );
+        assert_ne!(fqn("a+").stable_hash(), a.stable_hash());
+        assert_ne!(fqn("0+a").stable_hash(), a.stable_hash());
+        assert_ne!(
+            SelectExpression::Exclude(Box::new(a.clone())).stable_hash(),
+            SelectExpression::Not(Box::new(a)).stable_hash()
+        );
 
-        // collapse AND level
-        let inner_expr = match and_terms.len() {
-            0 => continue,                 // nothing in this token → ignore it
-            1 => and_terms.pop().unwrap(), // single item – no extra wrapper
-            _ => SelectExpression::And(and_terms),
-        };
+        // an unset indirect mode hashes like the default one
+        let mut unset = parse_single_selector("tag:b")?;
+        unset.indirect = None;
+        assert_eq!(SelectExpression::Atom(unset).stable_hash(), b.stable_hash());
+        Ok(())
+    }
 
-        or_terms.push(inner_expr);
+    #[test]
+    fn test_criteria_semantically_eq_ignores_default_indirect() -> FsResult<()> {
+        let explicit = parse_single_selector("tag:nightly")?;
+        assert_eq!(explicit.indirect, Some(IndirectSelection::default()));
+        let mut unset = explicit.clone();
+        unset.indirect = None;
+        assert_ne!(unset, explicit);
+        assert!(unset.semantically_eq(&explicit));
+        assert_eq!(HashSet::from([unset.clone(), explicit.clone()]).len(), 2);
+
+        let mut cautious = explicit.clone();
+        cautious.indirect = Some(IndirectSelection::Cautious);
+        assert!(!cautious.semantically_eq(&explicit));
+        assert!(!cautious.semantically_eq(&unset));
+        Ok(())
     }
 
-    // collapse OR level
-    let root = match or_terms.len() {
-        0 => {
-            return Err(fs_err!(
-                ErrorCode::SelectorError,
-                "selector contained only delimiters but no actual criteria"
-            ));
+    #[test]
+    fn test_criteria_hash_set_dedup() -> FsResult<()> {
+        let expr = parse_model_specifiers(&[
+            "tag:a".to_string(),
+            "tag:a,tag:b".to_string(),
+            "2+tag:a".to_string(),
+            "config.materialized:table".to_string(),
+            "config.schema:table".to_string(),
+            "tag:b".to_string(),
+        ])?;
+        let distinct = expr.atoms().cloned().collect::<HashSet<_>>();
+        assert_eq!(expr.atoms().count(), 7);
+        assert_eq!(distinct.len(), 5);
+        assert!(distinct.contains(&parse_single_selector("2+tag:a")?));
+
+        let plain = parse_single_selector("tag:a")?;
+        let mut excluding = plain.clone();
+        excluding.exclude = Some(Box::new(fqn("tag:b")));
+        let mut unset = excluding.clone();
+        unset.indirect = None;
+        assert_eq!(HashSet::from([plain, excluding, unset]).len(), 3);
+        Ok(())
+    }
+
+    /// Every expression of at most `depth` levels over a small set of atoms,
+    /// with `And`/`Or` of up to two operands.
+    fn all_expressions(depth: usize) -> Vec<SelectExpression> {
+        if depth == 0 {
+            return vec![fqn("a"), fqn("b"), SelectExpression::Empty];
         }
-        1 => or_terms.pop().unwrap(),
-        _ => SelectExpression::Or(or_terms),
-    };
+        let smaller = all_expressions(depth - 1);
+        let mut operand_lists = vec![vec![]];
+        for x in &smaller {
+            operand_lists.push(vec![x.clone()]);
+            for y in &smaller {
+                operand_lists.push(vec![x.clone(), y.clone()]);
+            }
+        }
+        let mut exprs = smaller.clone();
+        for operands in operand_lists {
+            exprs.push(SelectExpression::And(operands.clone()));
+            exprs.push(SelectExpression::Or(operands));
+        }
+        for x in smaller {
+            exprs.push(SelectExpression::Exclude(Box::new(x.clone())));
+            exprs.push(SelectExpression::Not(Box::new(x)));
+        }
+        exprs
+    }
 
-    Ok(root)
-}
+    /// Selector tokens that `to_selector_string` renders exactly as written.
+    const CLI_ATOMS: &[&str] = &[
+        "model_a",
+        "tag:nightly",
+        "+tag:nightly",
+        "2+tag:nightly+1",
+        "0+model_a+0",
+        "@model_a",
+        "config.materialized:table",
+        "path/to/models",
+        "source:raw.orders+",
+    ];
+
+    /// Arbitrary expression trees over a small set of atoms.
+    fn arb_expression() -> impl Strategy<Value = SelectExpression> {
+        let leaf = prop_oneof![
+            prop::sample::select(CLI_ATOMS).prop_map(fqn),
+            Just(SelectExpression::Empty),
+        ];
+        leaf.prop_recursive(5, 64, 3, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(SelectExpression::And),
+                prop::collection::vec(inner.clone(), 0..4).prop_map(SelectExpression::Or),
+                inner
+                    .clone()
+                    .prop_map(|expr| SelectExpression::Exclude(Box::new(expr))),
+                inner.prop_map(|expr| SelectExpression::Not(Box::new(expr))),
+            ]
+        })
+    }
 
-/// helper used by the CLI to add an extra criterion (resource_type etc.)
-pub fn conjoin_predicate(
-    maybe_sel: Option<SelectExpression>,
-    extra: Option<SelectionCriteria>,
-) -> Option<SelectExpression> {
-    match (maybe_sel, extra) {
-        (None, None) => None,
-        (Some(sel), None) => Some(sel),
-        (None, Some(c)) => Some(SelectExpression::Atom(c)),
-        (Some(sel), Some(c)) => {
-            let atom = SelectExpression::Atom(c);
-            Some(match sel {
-                SelectExpression::And(mut v) => {
-                    v.push(atom);
-                    SelectExpression::And(v)
-                }
-                _ => SelectExpression::And(vec![sel, atom]),
-            })
+    #[test]
+    fn test_algebraic_constructors() {
+        let (a, b, c) = (fqn("a"), fqn("b"), fqn("c"));
+        assert_eq!(
+            intersection(intersection(a.clone(), b.clone()), c.clone()),
+            SelectExpression::And(vec![a.clone(), b.clone(), c.clone()])
+        );
+        assert_eq!(
+            union(a.clone(), union(b.clone(), c.clone())),
+            SelectExpression::Or(vec![a.clone(), b.clone(), c.clone()])
+        );
+        // only like operators are flattened
+        assert_eq!(
+            union(intersection(a.clone(), b.clone()), c.clone()),
+            SelectExpression::Or(vec![
+                SelectExpression::And(vec![a.clone(), b.clone()]),
+                c.clone()
+            ])
+        );
+        assert_eq!(n_ary_intersection(vec![a.clone()]), a);
+        assert_eq!(n_ary_union(vec![]), SelectExpression::Empty);
+
+        // constructing and then simplifying gives the same tree as simplifying
+        // the naive construction
+        for x in all_expressions(1) {
+            for y in all_expressions(1) {
+                let pair = vec![x.clone(), y.clone()];
+                assert_eq!(
+                    intersection(x.clone(), y.clone()).simplify(),
+                    SelectExpression::And(pair.clone()).simplify()
+                );
+                assert_eq!(
+                    union(x.clone(), y.clone()).simplify(),
+                    SelectExpression::Or(pair.clone()).simplify()
+                );
+                assert_eq!(
+                    n_ary_intersection(vec![x.clone(), y.clone(), x.clone()]).simplify(),
+                    SelectExpression::And(vec![x.clone(), y.clone(), x.clone()]).simplify()
+                );
+                assert_eq!(
+                    n_ary_union(pair.clone()).simplify(),
+                    SelectExpression::Or(pair).simplify()
+                );
+            }
         }
     }
-}
-
-// ------------------------------------------------------------------------------------------------
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_contains_state_selector() {
+        assert!(fqn("state:modified").contains_state_selector());
+        assert!(!fqn("tag:nightly").contains_state_selector());
+        assert!(!SelectExpression::Empty.contains_state_selector());
+
+        let nested = SelectExpression::And(vec![
+            fqn("tag:nightly"),
+            SelectExpression::Or(vec![fqn("a"), fqn("state:new")]),
+        ]);
+        assert!(nested.contains_state_selector());
+
+        let only_in_exclude = SelectExpression::And(vec![
+            fqn("tag:nightly"),
+            SelectExpression::Exclude(Box::new(fqn("state:modified.body"))),
+        ]);
+        assert!(only_in_exclude.contains_state_selector());
+
+        let mut criteria = parse_single_selector("tag:nightly").unwrap();
+        criteria.exclude = Some(Box::new(fqn("state:old")));
+        assert!(SelectExpression::Atom(criteria).contains_state_selector());
+    }
 
     #[test]
-    fn test_identifier() -> FsResult<()> {
-        let result = parse_single_selector("identifier")?;
+    fn test_methods_referenced() {
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Fqn,
-                method_args: vec![],
-                value: "identifier".to_string(),
-                childrens_parents: false,
-                parents_depth: None,
-                children_depth: None,
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
+            fqn("tag:nightly").methods_referenced(),
+            BTreeSet::from([MethodName::Tag])
+        );
+        assert!(SelectExpression::Empty.methods_referenced().is_empty());
+
+        let mut criteria = parse_single_selector("package:acme").unwrap();
+        criteria.exclude = Some(Box::new(fqn("state:modified")));
+        let expr = SelectExpression::Or(vec![
+            SelectExpression::And(vec![
+                fqn("tag:nightly"),
+                fqn("path:models/staging"),
+                SelectExpression::Exclude(Box::new(fqn("tag:slow"))),
+            ]),
+            SelectExpression::Atom(criteria),
+            SelectExpression::Not(Box::new(fqn("orders"))),
+        ]);
+        assert_eq!(
+            expr.methods_referenced(),
+            BTreeSet::from([
+                MethodName::Fqn,
+                MethodName::Package,
+                MethodName::Path,
+                MethodName::State,
+                MethodName::Tag,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_count_atoms_and_excludes() {
+        assert_eq!(SelectExpression::Empty.count_atoms(), 0);
+        assert_eq!(SelectExpression::Empty.count_excludes(), 0);
+
+        let atom = fqn("tag:nightly");
+        assert_eq!((atom.count_atoms(), atom.count_excludes()), (1, 0));
+
+        // repeated atoms are counted each time
+        let union = SelectExpression::Or(vec![fqn("a"), fqn("a"), fqn("b")]);
+        assert_eq!((union.count_atoms(), union.count_excludes()), (3, 0));
+
+        let mut criteria = parse_single_selector("package:acme").unwrap();
+        criteria.exclude = Some(Box::new(SelectExpression::Exclude(Box::new(fqn(
+            "tag:slow",
+        )))));
+        let expr = SelectExpression::And(vec![
+            SelectExpression::Atom(criteria),
+            SelectExpression::Not(Box::new(fqn("orders"))),
+            SelectExpression::Exclude(Box::new(SelectExpression::Or(vec![
+                fqn("tag:wip"),
+                fqn("tag:deprecated"),
+            ]))),
+        ]);
+        assert_eq!(expr.count_atoms(), 5);
+        assert_eq!(expr.count_excludes(), 3);
+    }
+
+    #[test]
+    fn test_walk_visits_in_order() {
+        #[derive(Default)]
+        struct Trace(Vec<String>);
+
+        impl SelectExpressionVisitor<'_> for Trace {
+            fn visit_atom(&mut self, criteria: &SelectionCriteria) {
+                self.0.push(criteria.value.clone());
+            }
+            fn visit_and(&mut self, _exprs: &[SelectExpression]) {
+                self.0.push("and".to_string());
             }
+            fn visit_or(&mut self, _exprs: &[SelectExpression]) {
+                self.0.push("or".to_string());
+            }
+            fn visit_exclude(&mut self, _expr: &SelectExpression) {
+                self.0.push("exclude".to_string());
+            }
+            fn visit_not(&mut self, _expr: &SelectExpression) {
+                self.0.push("not".to_string());
+            }
+            fn visit_empty(&mut self) {
+                self.0.push("empty".to_string());
+            }
+            fn leave(&mut self, expr: &SelectExpression) {
+                if !matches!(expr, SelectExpression::Atom(_) | SelectExpression::Empty) {
+                    self.0.push("/".to_string());
+                }
+            }
+        }
+
+        let mut criteria = parse_single_selector("c").unwrap();
+        criteria.exclude = Some(Box::new(fqn("d")));
+        let expr = SelectExpression::Or(vec![
+            SelectExpression::And(vec![fqn("a"), SelectExpression::Not(Box::new(fqn("b")))]),
+            SelectExpression::Atom(criteria),
+            SelectExpression::Exclude(Box::new(SelectExpression::Empty)),
+        ]);
+        let mut trace = Trace::default();
+        walk(&mut trace, &expr);
+        assert_eq!(
+            trace.0,
+            vec![
+                "or", "and", "a", "not", "b", "/", "/", "c", "d", "exclude", "empty", "/", "/"
+            ]
         );
-        Ok(())
     }
 
     #[test]
-    fn test_at_pattern() -> FsResult<()> {
-        let result = parse_single_selector("@identifier")?;
+    fn test_substitute_selector_references() {
+        let nightly = SelectExpression::Or(vec![fqn("tag:nightly"), fqn("tag:hourly")]);
+
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Fqn,
-                method_args: vec![],
-                value: "identifier".to_string(),
-                childrens_parents: true,
-                parents_depth: None,
-                children_depth: None,
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
+            fqn("selector:nightly").substitute("nightly", nightly.clone()),
+            nightly
+        );
+        assert_eq!(
+            fqn("selector:weekly").substitute("nightly", nightly.clone()),
+            fqn("selector:weekly")
         );
-        Ok(())
-    }
 
-    #[test]
-    fn test_plus_pattern_leading_number() -> FsResult<()> {
-        let result = parse_single_selector("2+identifier")?;
+        let mut criteria = parse_single_selector("tag:finance").unwrap();
+        criteria.exclude = Some(Box::new(fqn("selector:nightly")));
+        let expr = SelectExpression::And(vec![
+            SelectExpression::Or(vec![fqn("selector:nightly"), fqn("selector:weekly")]),
+            SelectExpression::Exclude(Box::new(SelectExpression::Not(Box::new(fqn(
+                "selector:nightly",
+            ))))),
+            SelectExpression::Atom(criteria.clone()),
+        ]);
+
+        let mut substituted_criteria = criteria;
+        substituted_criteria.exclude = Some(Box::new(nightly.clone()));
+        let partially_resolved = SelectExpression::And(vec![
+            SelectExpression::Or(vec![nightly.clone(), fqn("selector:weekly")]),
+            SelectExpression::Exclude(Box::new(SelectExpression::Not(Box::new(nightly.clone())))),
+            SelectExpression::Atom(substituted_criteria.clone()),
+        ]);
+        let expr = expr.substitute("nightly", nightly.clone());
+        assert_eq!(expr, partially_resolved);
+
+        let weekly = fqn("tag:weekly");
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Fqn,
-                method_args: vec![],
-                value: "identifier".to_string(),
-                childrens_parents: false,
-                parents_depth: Some(2),
-                children_depth: None,
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
+            expr.substitute("weekly", weekly.clone()),
+            SelectExpression::And(vec![
+                SelectExpression::Or(vec![nightly.clone(), weekly]),
+                SelectExpression::Exclude(Box::new(SelectExpression::Not(Box::new(nightly)))),
+                SelectExpression::Atom(substituted_criteria),
+            ])
         );
-        Ok(())
     }
 
     #[test]
-    fn test_plus_pattern_trailing_plus() -> FsResult<()> {
-        let result = parse_single_selector("identifier+")?;
+    fn test_rewrite() -> FsResult<()> {
+        let expr = |selectors: &[&str]| {
+            parse_model_specifiers(&selectors.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        };
+        let mut criteria = parse_single_selector("path:models")?;
+        criteria.exclude = Some(Box::new(fqn("tag:wip")));
+        let tree = SelectExpression::And(vec![
+            expr(&["tag:nightly,orders", "tag:hourly"])?,
+            SelectExpression::Atom(criteria),
+            SelectExpression::Not(Box::new(fqn("+customers"))),
+        ]);
+
+        let uppercase_tags = |expr: SelectExpression| match expr {
+            SelectExpression::Atom(mut criteria) if criteria.method == MethodName::Tag => {
+                criteria.value = criteria.value.to_uppercase();
+                SelectExpression::Atom(criteria)
+            }
+            other => other,
+        };
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Fqn,
-                method_args: vec![],
-                value: "identifier".to_string(),
-                childrens_parents: false,
-                parents_depth: None,
-                children_depth: Some(u32::MAX),
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
+            tree.clone().rewrite(uppercase_tags).to_string(),
+            tree.to_string()
+                .replace("tag:nightly", "tag:NIGHTLY")
+                .replace("tag:hourly", "tag:HOURLY")
+        );
+        assert_eq!(
+            tree.clone()
+                .rewrite(uppercase_tags)
+                .atoms()
+                .find(|criteria| criteria.method == MethodName::Path)
+                .and_then(|criteria| criteria.exclude.clone()),
+            Some(Box::new(fqn("tag:WIP")))
+        );
+
+        let fqn_to_tag = |expr: SelectExpression| match expr {
+            SelectExpression::Atom(criteria) if criteria.method == MethodName::Fqn => {
+                SelectExpression::Atom(SelectionCriteria {
+                    method: MethodName::Tag,
+                    ..criteria
+                })
             }
+            other => other,
+        };
+        let rewritten = tree.rewrite(fqn_to_tag);
+        assert!(!rewritten.methods_referenced().contains(&MethodName::Fqn));
+        assert!(rewritten.to_string().contains("tag:orders"));
+        assert!(rewritten.to_string().contains("+tag:customers"));
+
+        // post-order: operands are rewritten before their parent sees them
+        let collapse = |expr: SelectExpression| match expr {
+            SelectExpression::Or(exprs) if exprs.iter().all(|e| *e == SelectExpression::Empty) => {
+                SelectExpression::Empty
+            }
+            SelectExpression::Atom(_) => SelectExpression::Empty,
+            other => other,
+        };
+        assert_eq!(
+            expr(&["a", "b"])?.rewrite(collapse),
+            SelectExpression::Empty
         );
         Ok(())
     }
 
     #[test]
-    fn test_plus_pattern_trailing_number() -> FsResult<()> {
-        let input = "identifier+8";
-        let result = parse_model_specifiers(&[input.to_string()])?;
+    fn test_depth() {
+        assert_eq!(fqn("a").depth(), 0);
+        assert_eq!(SelectExpression::Empty.depth(), 0);
+        assert_eq!(SelectExpression::Or(vec![]).depth(), 1);
         assert_eq!(
-            result,
-            SelectExpression::Atom(SelectionCriteria::new(
-                MethodName::Fqn,
-                vec![],
-                "identifier".to_string(),
-                false,
-                None,
-                Some(8),
-                Some(IndirectSelection::default()),
-                None,
-            ))
+            SelectExpression::Or(vec![
+                fqn("a"),
+                SelectExpression::Exclude(Box::new(SelectExpression::And(vec![fqn("b")]))),
+            ])
+            .depth(),
+            3
         );
-        Ok(())
+
+        for n in 0..20 {
+            let mut expr = fqn("a");
+            for _ in 0..n {
+                expr = SelectExpression::And(vec![expr]);
+            }
+            assert_eq!(expr.depth(), n);
+        }
     }
 
     #[test]
-    fn test_plus_pattern_both_plus() -> FsResult<()> {
-        let input = "+identifier+";
-        let result = parse_single_selector(input)?;
+    fn test_optimize_orders_and_operands_by_selectivity() {
+        let hints = BTreeMap::from([
+            (MethodName::Path, 0.5),
+            (MethodName::Tag, 0.2),
+            (MethodName::State, 0.01),
+        ]);
+        let expr = parse_model_specifiers(&[
+            "path:models,tag:nightly,state:modified".to_string(),
+            "fqn:a,tag:b".to_string(),
+        ])
+        .unwrap();
+
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Fqn,
-                method_args: vec![],
-                value: "identifier".to_string(),
-                childrens_parents: false,
-                parents_depth: Some(u32::MAX),
-                children_depth: Some(u32::MAX),
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
+            expr.optimize(&hints),
+            SelectExpression::Or(vec![
+                SelectExpression::And(vec![
+                    fqn("state:modified"),
+                    fqn("tag:nightly"),
+                    fqn("path:models")
+                ]),
+                // `fqn` has no hint and counts as matching everything
+                SelectExpression::And(vec![fqn("tag:b"), fqn("fqn:a")]),
+            ])
         );
-        Ok(())
     }
 
     #[test]
-    fn test_plus_path_pattern_both_plus() -> FsResult<()> {
-        let input = "+path:identifier/rest+";
-        let result = parse_single_selector(input)?;
+    fn test_optimize_recurses_and_keeps_ties() {
+        use SelectExpression::{And, Exclude, Or};
+
+        let hints = BTreeMap::from([(MethodName::Tag, 0.1), (MethodName::Path, 0.3)]);
+        let expr = And(vec![
+            // estimated at 1.0 - 0.1
+            Exclude(Box::new(fqn("tag:slow"))),
+            // 0.3 + 0.3
+            Or(vec![fqn("path:a"), fqn("path:b")]),
+            // min(1.0, 0.3)
+            And(vec![fqn("fqn:x"), fqn("path:c")]),
+        ]);
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Path,
-                method_args: vec![],
-                value: "identifier/rest".to_string(),
-                childrens_parents: false,
-                parents_depth: Some(u32::MAX),
-                children_depth: Some(u32::MAX),
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
+            expr.optimize(&hints),
+            And(vec![
+                And(vec![fqn("path:c"), fqn("fqn:x")]),
+                Or(vec![fqn("path:a"), fqn("path:b")]),
+                Exclude(Box::new(fqn("tag:slow"))),
+            ])
         );
-        Ok(())
-    }
 
-    #[test]
-    fn test_plus_pattern_leading_number_trailing_plus() -> FsResult<()> {
-        let input = "5+identifier+";
-        let result = parse_single_selector(input)?;
+        // nested excludes are optimized too
+        let mut criteria = parse_single_selector("fqn:*").unwrap();
+        criteria.exclude = Some(Box::new(And(vec![fqn("path:a"), fqn("tag:a")])));
+        let SelectExpression::Atom(optimized) = SelectExpression::Atom(criteria).optimize(&hints)
+        else {
+            panic!("expected an atom");
+        };
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Fqn,
-                method_args: vec![],
-                value: "identifier".to_string(),
-                childrens_parents: false,
-                parents_depth: Some(5),
-                children_depth: Some(u32::MAX),
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
+            optimized.exclude.as_deref(),
+            Some(&And(vec![fqn("tag:a"), fqn("path:a")]))
         );
-        Ok(())
+
+        // without hints every operand ties, so nothing moves
+        let expr = And(vec![fqn("path:a"), fqn("tag:a"), fqn("fqn:a")]);
+        assert_eq!(expr.clone().optimize(&BTreeMap::new()), expr);
     }
 
     #[test]
-    fn test_plus_pattern_leading_plus_trailing_number() -> FsResult<()> {
-        let input = "+identifier+6";
-        let result = parse_single_selector(input)?;
-        assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Fqn,
-                method_args: vec![],
-                value: "identifier".to_string(),
-                childrens_parents: false,
-                parents_depth: Some(u32::MAX),
-                children_depth: Some(6),
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
-        );
-        Ok(())
+    fn test_is_empty() {
+        use SelectExpression::{And, Empty, Exclude, Not, Or};
+
+        for expr in [
+            Empty,
+            Or(vec![]),
+            And(vec![]),
+            Or(vec![Empty, And(vec![])]),
+            And(vec![fqn("a"), Empty]),
+            And(vec![fqn("a"), Or(vec![Empty])]),
+        ] {
+            assert!(expr.is_empty(), "{expr:?}");
+            assert_eq!(expr.simplify(), Empty, "{expr:?}");
+        }
+        for expr in [
+            fqn("a"),
+            Or(vec![Empty, fqn("a")]),
+            And(vec![fqn("a"), fqn("b")]),
+            Exclude(Box::new(Empty)),
+            Not(Box::new(fqn("a"))),
+            Not(Box::new(Empty)),
+        ] {
+            assert!(!expr.is_empty(), "{expr:?}");
+            assert_ne!(expr.simplify(), Empty, "{expr:?}");
+        }
     }
 
     #[test]
-    fn test_invalid_trailing_number() {
-        let input = "identifier+abc";
-        let result = parse_single_selector(input);
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert_eq!(
-                e.to_string().trim(),
-                "Invalid model specifier near: identifier+abc"
+    fn test_simplify_never_increases_depth() {
+        for expr in all_expressions(2) {
+            assert!(
+                expr.simplify().depth() <= expr.depth(),
+                "simplify increased the depth of {expr:?}"
             );
         }
     }
 
-    #[test]
-    fn test_empty_string() {
-        let input = "";
-        let result = parse_single_selector(input);
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert_eq!(e.to_string().trim(), "Invalid selector spec: ``");
+    proptest! {
+        #[test]
+        fn prop_simplify_never_increases_depth(expr in arb_expression()) {
+            prop_assert!(
+                expr.simplify().depth() <= expr.depth(),
+                "simplify increased the depth of {:?}",
+                expr
+            );
+        }
+
+        #[test]
+        fn prop_to_selector_string_round_trips(expr in arb_expression()) {
+            if let Some(rendered) = expr.to_selector_string() {
+                let tokens = rendered
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                prop_assert_eq!(parse_model_specifiers(&tokens).unwrap(), expr.simplify());
+            }
+        }
+
+        #[test]
+        fn prop_cli_selectors_render_back(
+            tokens in prop::collection::vec(
+                prop::collection::vec(prop::sample::select(CLI_ATOMS), 1..4)
+                    .prop_map(|atoms| atoms.join(",")),
+                1..4,
+            )
+        ) {
+            let parsed = parse_model_specifiers(&tokens).unwrap();
+            prop_assert_eq!(parsed.to_selector_string(), Some(tokens.join(" ")));
+        }
+
+        #[test]
+        fn prop_nested_and_depth(n in 0usize..64) {
+            let mut expr = fqn("a");
+            for _ in 0..n {
+                expr = SelectExpression::And(vec![expr]);
+            }
+            prop_assert_eq!(expr.depth(), n);
         }
     }
 
     #[test]
-    fn test_only_plus_signs() {
-        let input = "++";
-        let result = parse_single_selector(input);
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert_eq!(e.to_string().trim(), "Invalid selector spec: `++`");
-        }
+    fn test_simplify_double_negation() {
+        let not = |expr| SelectExpression::Not(Box::new(expr));
+        assert_eq!(not(not(fqn("a"))).simplify(), fqn("a"));
+        assert_eq!(
+            not(not(not(SelectExpression::Or(vec![fqn("a")])))).simplify(),
+            not(fqn("a"))
+        );
+        assert_eq!(not(fqn("a")).to_string(), "not(fqn:a)");
     }
 
     #[test]
-    fn invalid_at_pattern_with_plus() {
-        let input = "@identifier+";
-        let result = parse_single_selector(input);
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert_eq!(
-                e.to_string().trim(),
-                "Invalid selector `@identifier+` - \"@\" and trailing \"+\" are incompatible"
-            );
+    fn test_to_selector_string_round_trip() -> FsResult<()> {
+        let cases = [
+            "model_a",
+            "tag:nightly",
+            "+tag:nightly",
+            "2+tag:nightly+1",
+            "0+model_a+0",
+            "@model_a",
+            "config.materialized:table",
+            "path/to/models",
+            "fqn:path/to/models+",
+            "source:raw.orders+",
+            "tag:a,tag:b",
+            "tag:a model_b,+model_c+3 config.materialized:view",
+        ];
+        for case in cases {
+            let tokens = case
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            let parsed = parse_model_specifiers(&tokens)?;
+            let rendered = parsed.to_selector_string().unwrap();
+            assert_eq!(rendered, case);
+
+            let reparsed = parse_model_specifiers(
+                &rendered
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>(),
+            )?;
+            assert_eq!(reparsed, parsed, "round trip failed for `{case}`");
         }
+        Ok(())
     }
 
     #[test]
-    fn test_and_specifier() -> FsResult<()> {
-        let input = vec!["identifier,@identifier".to_string()];
-        let result = parse_model_specifiers(&input)?;
-        match result {
-            SelectExpression::And(vec) if vec.len() == 2 => match (&vec[0], &vec[1]) {
-                (SelectExpression::Atom(x), SelectExpression::Atom(y)) => {
-                    assert_eq!(
-                        x,
-                        &SelectionCriteria {
-                            method: MethodName::Fqn,
-                            method_args: vec![],
-                            value: "identifier".to_string(),
-                            childrens_parents: false,
-                            parents_depth: None,
-                            children_depth: None,
-                            indirect: Some(IndirectSelection::default()),
-                            exclude: None,
-                        }
-                    );
-                    assert_eq!(
-                        y,
-                        &SelectionCriteria {
-                            method: MethodName::Fqn,
-                            method_args: vec![],
-                            value: "identifier".to_string(),
-                            childrens_parents: true,
-                            parents_depth: None,
-                            children_depth: None,
-                            indirect: Some(IndirectSelection::default()),
-                            exclude: None,
-                        }
-                    );
-                }
-                _ => panic!("Expected SelectExpr::Or variant with two Atom elements"),
-            },
-            _ => panic!("Expected SelectExpr::And variant with one element"),
+    fn test_to_selector_string_exclude() {
+        let expr = SelectExpression::And(vec![
+            fqn("tag:nightly"),
+            SelectExpression::Exclude(Box::new(fqn("model_a"))),
+        ]);
+        assert_eq!(expr.to_selector_string(), None);
+        assert_eq!(SelectExpression::Empty.to_selector_string(), None);
+        assert_eq!(
+            SelectExpression::Not(Box::new(fqn("model_a"))).to_selector_string(),
+            None
+        );
+
+        // an `Or` nested in an `And` cannot be written on the CLI
+        let nested = SelectExpression::And(vec![
+            fqn("tag:nightly"),
+            SelectExpression::Or(vec![fqn("model_a"), fqn("model_b")]),
+        ]);
+        assert_eq!(nested.to_selector_string(), None);
+        // but redundant nesting is simplified away
+        let redundant = SelectExpression::Or(vec![SelectExpression::And(vec![
+            fqn("tag:nightly"),
+            SelectExpression::And(vec![fqn("model_a")]),
+        ])]);
+        assert_eq!(
+            redundant.to_selector_string().as_deref(),
+            Some("tag:nightly,model_a")
+        );
+
+        let mut criteria = parse_single_selector("tag:nightly").unwrap();
+        criteria.exclude = Some(Box::new(fqn("model_a")));
+        assert_eq!(criteria.to_selector_string(), None);
+        let mut criteria = parse_single_selector("tag:nightly").unwrap();
+        criteria.indirect = Some(IndirectSelection::Cautious);
+        assert_eq!(criteria.to_selector_string(), None);
+        // values that would parse back with another method or graph operators
+        for value in ["tag:nightly", "+model_a", "model_a,model_b", ""] {
+            let criteria = SelectionCriteria::new_fqn(value);
+            assert_eq!(criteria.to_selector_string(), None, "{value}");
         }
+        assert_eq!(
+            SelectionCriteria::new_fqn("model_a")
+                .to_selector_string()
+                .as_deref(),
+            Some("model_a")
+        );
+    }
+
+    #[test]
+    fn test_indirect_selection_build_any_round_trip() {
+        assert_eq!(
+            IndirectSelection::from_str("buildany").unwrap(),
+            IndirectSelection::BuildAny
+        );
+        assert_eq!(
+            IndirectSelection::from_str("BuildAny").unwrap(),
+            IndirectSelection::BuildAny
+        );
+        assert_eq!(IndirectSelection::BuildAny.to_string(), "buildany");
+        assert_eq!(
+            serde_json::to_string(&IndirectSelection::BuildAny).unwrap(),
+            "\"buildany\""
+        );
+    }
 
+    #[test]
+    fn test_node_count_estimate() -> FsResult<()> {
+        let flat_or = parse_model_specifiers(&[
+            "tag:a".to_string(),
+            "tag:b".to_string(),
+            "tag:c".to_string(),
+        ])?;
+        assert_eq!(flat_or.node_count_estimate(), 3);
+
+        let nested_and = SelectExpression::And(vec![
+            fqn("a"),
+            SelectExpression::Or(vec![
+                fqn("b"),
+                SelectExpression::And(vec![fqn("c"), fqn("d")]),
+            ]),
+            SelectExpression::Exclude(Box::new(fqn("e"))),
+        ]);
+        assert_eq!(nested_and.node_count_estimate(), 5);
+
+        // the same criteria reused in several places is only counted once
+        let shared = fqn("tag:shared");
+        let reused = SelectExpression::Or(vec![
+            shared.clone(),
+            SelectExpression::And(vec![shared.clone(), fqn("b")]),
+            SelectExpression::Exclude(Box::new(shared)),
+        ]);
+        assert_eq!(reused.node_count_estimate(), 2);
+
+        assert_eq!(SelectExpression::Empty.node_count_estimate(), 0);
         Ok(())
     }
 
     #[test]
-    fn test_or_specifier() -> FsResult<()> {
-        let input = vec!["identifier".to_string(), "@identifier".to_string()];
-        let result = parse_model_specifiers(&input)?;
-        match result {
-            SelectExpression::Or(vec) if vec.len() == 2 => match (&vec[0], &vec[1]) {
-                (SelectExpression::Atom(x), SelectExpression::Atom(y)) => {
-                    assert_eq!(
-                        x,
-                        &SelectionCriteria {
-                            method: MethodName::Fqn,
-                            method_args: vec![],
-                            value: "identifier".to_string(),
-                            childrens_parents: false,
-                            parents_depth: None,
-                            children_depth: None,
-                            indirect: Some(IndirectSelection::default()),
-                            exclude: None,
-                        }
-                    );
-                    assert_eq!(
-                        y,
-                        &SelectionCriteria {
-                            method: MethodName::Fqn,
-                            method_args: vec![],
-                            value: "identifier".to_string(),
-                            childrens_parents: true,
-                            parents_depth: None,
-                            children_depth: None,
-                            indirect: Some(IndirectSelection::default()),
-                            exclude: None,
-                        }
-                    );
-                }
-                _ => panic!("Expected SelectExpr::Or variant with two Atom elements"),
-            },
-            _ => panic!("Expected SelectExpr::Or variant with two elements"),
+    fn test_select_expression_serde_round_trip() -> FsResult<()> {
+        let mut expr = parse_model_specifiers(&[
+            "+tag:nightly,config.materialized:table".to_string(),
+            "@model_a".to_string(),
+        ])?;
+        if let SelectExpression::Or(exprs) = &mut expr {
+            exprs.push(SelectExpression::Exclude(Box::new(fqn("model_b"))));
+            exprs.push(SelectExpression::Empty);
         }
 
+        let json = serde_json::to_string(&expr).unwrap();
+        let back: SelectExpression = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, expr);
         Ok(())
     }
 
     #[test]
-    fn test_column_selector_identifier() -> FsResult<()> {
-        let input = "column:node123.foo_col";
-        let result = parse_single_selector(input)?;
+    fn test_select_expression_json_snapshot() {
+        let mut not_selected = SelectionCriteria::builder(MethodName::Package, "pkg")
+            .with_childrens_parents()
+            .build();
+        not_selected.indirect = None;
+        let expr = SelectExpression::Or(vec![
+            SelectExpression::And(vec![
+                SelectExpression::Atom(
+                    SelectionCriteria::builder(MethodName::Tag, "nightly")
+                        .with_parents_depth(u32::MAX)
+                        .build(),
+                ),
+                SelectExpression::Atom(
+                    SelectionCriteria::builder(MethodName::Config, "table")
+                        .with_method_args(vec!["materialized".to_string()])
+                        .with_children_depth(1)
+                        .with_exclude(SelectExpression::Atom(SelectionCriteria::new_fqn("legacy")))
+                        .build(),
+                ),
+            ]),
+            SelectExpression::Exclude(Box::new(SelectExpression::Atom(
+                SelectionCriteria::new_fqn("model_b"),
+            ))),
+            SelectExpression::Not(Box::new(SelectExpression::Atom(not_selected))),
+            SelectExpression::Empty,
+        ]);
+
+        let fixture: serde_json::Value = serde_json::from_str(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/select_expression.json"
+        )))
+        .unwrap();
+        assert_eq!(serde_json::to_value(&expr).unwrap(), fixture);
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Column,
-                method_args: vec![],
-                value: "node123.foo_col".to_string(),
-                childrens_parents: false,
-                parents_depth: None,
-                children_depth: None,
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
+            serde_json::from_value::<SelectExpression>(fixture).unwrap(),
+            expr
         );
-        Ok(())
     }
 
     #[test]
-    fn test_column_selector_with_leading_plus() -> FsResult<()> {
-        let input = "+column:node123.foo_col";
-        let result = parse_single_selector(input)?;
-        assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Column,
-                method_args: vec![],
-                value: "node123.foo_col".to_string(),
-                childrens_parents: false,
-                parents_depth: Some(u32::MAX),
-                children_depth: None,
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
-        );
+    fn test_display_selector_round_trip() -> FsResult<()> {
+        for token in [
+            "fqn:model_a",
+            "+tag:nightly",
+            "2+tag:nightly+1",
+            "@fqn:model_a",
+            "config.materialized:table+",
+            "source:raw.orders",
+        ] {
+            let criteria = parse_single_selector(token)?;
+            assert_eq!(criteria.to_string(), token);
+            assert_eq!(parse_single_selector(&criteria.to_string())?, criteria);
+        }
         Ok(())
     }
 
     #[test]
-    fn test_column_selector_with_trailing_plus() -> FsResult<()> {
-        let input = "column:node123.foo_col+";
-        let result = parse_single_selector(input)?;
+    fn test_select_expression_from_str() -> FsResult<()> {
         assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Column,
-                method_args: vec![],
-                value: "node123.foo_col".to_string(),
-                childrens_parents: false,
-                parents_depth: None,
-                children_depth: Some(u32::MAX),
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
+            "tag:nightly".parse::<SelectExpression>()?,
+            fqn("tag:nightly")
         );
+        assert_eq!(
+            "tag:a,tag:b +orders".parse::<SelectExpression>()?,
+            parse_model_specifiers(&["tag:a,tag:b".to_string(), "+orders".to_string()])?
+        );
+        assert_eq!(
+            "  tag:a \t tag:b\n".parse::<SelectExpression>()?,
+            SelectExpression::Or(vec![fqn("tag:a"), fqn("tag:b")])
+        );
+
+        assert_eq!("tag:a,,".parse::<SelectExpression>()?, fqn("tag:a"));
+        for input in ["", "   ", ",", "tag:"] {
+            let err = input.parse::<SelectExpression>().unwrap_err();
+            assert_eq!(err.code, ErrorCode::SelectorError, "{input:?}");
+        }
         Ok(())
     }
 
     #[test]
-    fn test_column_selector_with_both_plus() -> FsResult<()> {
-        let input = "+column:node123.foo_col+";
-        let result = parse_single_selector(input)?;
+    fn test_select_expression_display_from_str_round_trip() -> FsResult<()> {
+        for input in [
+            "tag:nightly",
+            "2+path:models/marts+",
+            "tag:a,config.materialized:table",
+            "@fqn:orders source:raw.orders+",
+            "tag:a,tag:b state:modified+1,tag:c",
+        ] {
+            let expr = input.parse::<SelectExpression>()?;
+            assert_eq!(expr.to_string(), input);
+            assert_eq!(expr.to_string().parse::<SelectExpression>()?, expr);
+        }
+        Ok(())
+    }
 
-        assert_eq!(
-            result,
-            SelectionCriteria {
-                method: MethodName::Column,
-                method_args: vec![],
-                value: "node123.foo_col".to_string(),
-                childrens_parents: false,
-                parents_depth: Some(u32::MAX),
-                children_depth: Some(u32::MAX),
-                indirect: Some(IndirectSelection::default()),
-                exclude: None,
-            }
-        );
+    #[test]
+    fn test_state_method() -> FsResult<()> {
+        let cases = [
+            ("state:modified", Some(StateMethod::Modified)),
+            ("state:modified.body", Some(StateMethod::Modified)),
+            ("state:NEW", Some(StateMethod::New)),
+            ("state:old", Some(StateMethod::OldRelation)),
+            ("state:unknown", None),
+            ("tag:modified", None),
+        ];
+        for (selector, expected) in cases {
+            assert_eq!(parse_single_selector(selector)?.state_method(), expected);
+        }
         Ok(())
     }
 
     #[test]
-    fn test_invalid_column_selector_with_missing_column_name() {
-        let input = "column:node123.";
-        let result = parse_single_selector(input);
-        assert!(result.is_err());
-        if let Err(e) = result {
+    fn test_source_status_kind() -> FsResult<()> {
+        let cases = [
+            ("source_status:fresher", Some(SourceStatusKind::Fresher)),
+            ("source_status:pass", Some(SourceStatusKind::Pass)),
+            ("source_status:WARN", Some(SourceStatusKind::Warn)),
+            ("source_status:error", Some(SourceStatusKind::Error)),
+            ("source_status:stale", None),
+            ("tag:fresher", None),
+        ];
+        for (selector, expected) in cases {
             assert_eq!(
-                e.to_string().trim(),
-                "Invalid selector spec: `column:node123.`"
+                parse_single_selector(selector)?.source_status_kind(),
+                expected
             );
         }
+        assert_eq!(
+            parse_single_selector("source_status:fresher")?.method,
+            MethodName::SourceStatus
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_column_selectors_with_trailing_plus() -> FsResult<()> {
-        let input = vec![
-            "column:node123.foo_col+".to_string(),
-            "column:node123.bar_col+".to_string(),
+    fn test_version_selector() -> FsResult<()> {
+        let cases = [
+            ("version:2", Some(VersionSelector::Number(2))),
+            ("version:latest", Some(VersionSelector::Latest)),
+            ("version:Prerelease", Some(VersionSelector::Prerelease)),
+            ("version:old", Some(VersionSelector::Old)),
+            ("version:none", Some(VersionSelector::Unversioned)),
+            ("version:v2", None),
+            ("tag:2", None),
         ];
-        let result = parse_model_specifiers(&input)?;
-
-        match result {
-            SelectExpression::Or(vec) if vec.len() == 2 => match (&vec[0], &vec[1]) {
-                (SelectExpression::Atom(a), SelectExpression::Atom(b)) => {
-                    assert_eq!(
-                        a,
-                        &SelectionCriteria {
-                            method: MethodName::Column,
-                            method_args: vec![],
-                            value: "node123.foo_col".to_string(),
-                            childrens_parents: false,
-                            parents_depth: None,
-                            children_depth: Some(u32::MAX),
-                            indirect: Some(IndirectSelection::default()),
-                            exclude: None,
-                        }
-                    );
-                    assert_eq!(
-                        b,
-                        &SelectionCriteria {
-                            method: MethodName::Column,
-                            method_args: vec![],
-                            value: "node123.bar_col".to_string(),
-                            childrens_parents: false,
-                            parents_depth: None,
-                            children_depth: Some(u32::MAX),
-                            indirect: Some(IndirectSelection::default()),
-                            exclude: None,
-                        }
-                    );
-                }
-                _ => panic!("Expected Atom variants"),
-            },
-            _ => panic!("Expected SelectExpr::Or expression with 2 atoms"),
+        for (selector, expected) in cases {
+            assert_eq!(
+                parse_single_selector(selector)?.version_selector(),
+                expected
+            );
         }
-
         Ok(())
     }
 
     #[test]
-    fn test_display_selector_with_unbounded_plus() -> FsResult<()> {
-        // Test that u32::MAX is not displayed as a number (should just be "+")
-        let criteria = SelectionCriteria {
-            method: MethodName::Fqn,
-            method_args: vec![],
-            value: "model_a".to_string(),
-            childrens_parents: false,
-            parents_depth: None,
-            children_depth: Some(u32::MAX),
-            indirect: Some(IndirectSelection::default()),
-            exclude: None,
-        };
-        assert_eq!(criteria.to_string(), "fqn:model_a+");
-
-        // Test with explicit depth number
-        let criteria_with_depth = SelectionCriteria {
-            method: MethodName::Fqn,
-            method_args: vec![],
-            value: "model_a".to_string(),
-            childrens_parents: false,
-            parents_depth: None,
-            children_depth: Some(3),
-            indirect: Some(IndirectSelection::default()),
-            exclude: None,
-        };
-        assert_eq!(criteria_with_depth.to_string(), "fqn:model_a+3");
-
-        // Test with unbounded parents
-        let criteria_parents = SelectionCriteria {
-            method: MethodName::Fqn,
-            method_args: vec![],
-            value: "model_a".to_string(),
-            childrens_parents: false,
-            parents_depth: Some(u32::MAX),
-            children_depth: None,
-            indirect: Some(IndirectSelection::default()),
-            exclude: None,
-        };
-        assert_eq!(criteria_parents.to_string(), "+fqn:model_a");
-
-        // Test with both unbounded
-        let criteria_both = SelectionCriteria {
-            method: MethodName::Fqn,
-            method_args: vec![],
-            value: "model_a".to_string(),
-            childrens_parents: false,
-            parents_depth: Some(u32::MAX),
-            children_depth: Some(u32::MAX),
-            indirect: Some(IndirectSelection::default()),
-            exclude: None,
-        };
-        assert_eq!(criteria_both.to_string(), "+fqn:model_a+");
-
-        Ok(())
+    fn test_atoms_deeply_nested() {
+        let mut nested_criteria = parse_single_selector("tag:nested").unwrap();
+        nested_criteria.exclude = Some(Box::new(fqn("nested_exclude")));
+
+        let expr = SelectExpression::Or(vec![
+            fqn("a"),
+            SelectExpression::And(vec![
+                SelectExpression::Or(vec![
+                    SelectExpression::And(vec![fqn("b"), fqn("state:modified")]),
+                    SelectExpression::Atom(nested_criteria),
+                ]),
+                SelectExpression::Exclude(Box::new(SelectExpression::Or(vec![fqn("c")]))),
+                SelectExpression::Empty,
+            ]),
+            fqn("d"),
+        ]);
+
+        let values = expr.atoms().map(|c| c.value.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec!["a", "b", "modified", "nested", "nested_exclude", "c", "d"]
+        );
+        assert!(expr.atoms().any(|c| c.method == MethodName::State));
+        assert_eq!(SelectExpression::Empty.atoms().count(), 0);
     }
 }
@@ -209,6 +209,9 @@ pub enum ErrorCode {
     InvalidUserInput = 8997,
     InvalidOptions = 8996,
     OperationCanceled = 8995,
+    /// Wraps several independent errors reported together; the individual
+    /// errors are chained after the head error (see [FsError::flatten]).
+    MultiError = 8994,
 
     // -----------------  ---------------------
     // CLI Internal errors [9000, 9899]
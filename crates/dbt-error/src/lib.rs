@@ -18,7 +18,7 @@ pub use codes::Warnings;
 pub use preprocessor_location::MacroSpan;
 pub use types::{
     ContextableResult, ErrContext, FsError, FsResult, GenericNameError, LiftableResult,
-    MAX_DISPLAY_TOKENS, NameError, WrappedError,
+    MAX_DISPLAY_TOKENS, NameError, SelectorErrorKind, WrappedError,
 };
 
 // Re-export Cancellable from dbt-cancel for convenience
@@ -518,6 +518,29 @@ impl FsError {
         Box::new(err)
     }
 
+    /// Create an [ErrorCode::SelectorError] whose cause is `kind`, so that
+    /// callers can match on [Self::selector_kind] instead of the message.
+    #[cold]
+    pub fn selector(kind: SelectorErrorKind) -> Box<Self> {
+        let err = FsError {
+            code: ErrorCode::SelectorError,
+            location: None,
+            context: String::new(),
+            cause: Some(WrappedError::Selector(kind)),
+            backtrace: Backtrace::capture(),
+            next: None,
+        };
+        Box::new(err)
+    }
+
+    /// The reason for a selector error built with [Self::selector].
+    pub fn selector_kind(&self) -> Option<&SelectorErrorKind> {
+        match &self.cause {
+            Some(WrappedError::Selector(kind)) => Some(kind),
+            _ => None,
+        }
+    }
+
     /// If this error represents an exit-with-status request, returns the
     /// status code. Returns [None] for all other errors.
     pub fn exit_status(&self) -> Option<i32> {
@@ -653,6 +676,42 @@ pub enum WrappedError {
     Cli(Box<FsError>),
     // RemoteExecution(reqwest::Error),
     ExitCode(i32),
+    Selector(SelectorErrorKind),
+}
+
+/// Machine-readable reason for an [ErrorCode::SelectorError], carried as the
+/// cause of errors built with [FsError::selector].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorErrorKind {
+    /// A `selector:` reference or a requested selector name is not defined.
+    UnknownSelector(String),
+    /// A `union`/`intersection` map without any key.
+    EmptyComposite,
+    /// Selector inheritance loops back on itself; the chain of selector
+    /// names, outermost first, ending with the repeated name.
+    CircularReference(Vec<String>),
+    /// A selector method that does not exist.
+    UnsupportedMethod(String),
+    /// An `exclude:` list without any value.
+    EmptyExclude,
+}
+
+impl Display for SelectorErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SelectorErrorKind::UnknownSelector(name) => write!(f, "Unknown selector `{name}`"),
+            SelectorErrorKind::EmptyComposite => write!(f, "Empty composite expression"),
+            SelectorErrorKind::CircularReference(chain) => write!(
+                f,
+                "Circular selector inheritance detected: {}",
+                chain.join(" -> ")
+            ),
+            SelectorErrorKind::UnsupportedMethod(method) => {
+                write!(f, "Invalid node selector method: `{method}`")
+            }
+            SelectorErrorKind::EmptyExclude => write!(f, "Empty exclude list"),
+        }
+    }
 }
 
 impl Display for WrappedError {
@@ -677,6 +736,7 @@ impl Display for WrappedError {
             // WrappedError::RemoteExecution(e) => write!(f, "{}", e),
             WrappedError::Fmt(e) => write!(f, "{e}"),
             WrappedError::ExitCode(code) => write!(f, "exit code {code}"),
+            WrappedError::Selector(kind) => write!(f, "{kind}"),
         }
     }
 }
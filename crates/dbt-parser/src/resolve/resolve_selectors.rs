@@ -1,4 +1,4 @@
-use dbt_common::node_selector::{IndirectSelection, SelectExpression};
+use dbt_common::node_selector::{IndirectSelection, SelectExpression, SelectionCriteria};
 use dbt_common::once_cell_vars::DISPATCH_CONFIG;
 use dbt_common::{ErrorCode, FsResult, err, fs_err};
 use dbt_jinja_utils::jinja_environment::JinjaEnv;
@@ -37,7 +37,22 @@ pub fn resolve_manifest_selectors(
     let manifest_selectors = resolved_selectors
         .into_iter()
         .map(|(name, entry)| {
-            let definition_value = select_expression_to_yaml(&entry.include);
+            let definition_value = match (&entry.include, &entry.exclude) {
+                (Some(include), _) => select_expression_to_yaml(include),
+                // written back as the `exclude:` block it was defined as
+                (None, Some(exclude)) => {
+                    select_expression_to_yaml(&SelectExpression::Exclude(Box::new(exclude.clone())))
+                }
+                (None, None) => select_expression_to_yaml(&SelectExpression::Empty),
+            }
+            .map_err(|e| {
+                fs_err!(
+                    ErrorCode::SelectorError,
+                    "Cannot write selector `{}` to the manifest: {}",
+                    name,
+                    e
+                )
+            })?;
 
             let selector = DbtSelector {
                 name: name.clone(),
@@ -45,9 +60,9 @@ pub fn resolve_manifest_selectors(
                 __definition__: definition_value,
                 __other__: BTreeMap::new(),
             };
-            (name, selector)
+            Ok((name, selector))
         })
-        .collect();
+        .collect::<FsResult<_>>()?;
 
     Ok(manifest_selectors)
 }
@@ -64,7 +79,8 @@ pub fn resolve_manifest_selectors(
 ///    - CLI include/exclude flags
 ///    - CLI indirect selection mode (fallback if not specified in YAML)
 ///
-/// Returns the final include and exclude expressions to be used by the scheduler.
+/// Returns the final include and exclude expressions to be used by the scheduler,
+/// simplified so that redundant single-item composites are not re-evaluated.
 pub fn resolve_final_selectors(
     resolved_selectors: HashMap<String, SelectorEntry>,
     arg: &ResolveArgs,
@@ -92,27 +108,33 @@ pub fn resolve_final_selectors(
             )
         })?;
 
-        // Use selector's include and apply CLI indirect selection as fallback
-        let mut include = entry.include.clone();
-        if let Some(cli_mode) = arg.indirect_selection {
-            include.set_indirect_selection(cli_mode);
+        // Use selector's include and apply CLI indirect selection as fallback;
+        // an exclude-only selector includes every node
+        let mut include = entry.include.as_ref().map(SelectExpression::simplify);
+        if let (Some(cli_mode), Some(inc)) = (arg.indirect_selection, include.as_mut()) {
+            inc.set_indirect_selection(cli_mode);
         }
 
-        // Set exclude to CLI exclude and apply CLI indirect selection as fallback
-        let mut exclude = arg.exclude.clone();
+        // Merge the selector's exclude with the CLI exclude and apply CLI
+        // indirect selection as fallback
+        let exclude = match (&entry.exclude, &arg.exclude) {
+            (Some(selector_exclude), Some(cli_exclude)) => Some(SelectExpression::Or(vec![
+                selector_exclude.clone(),
+                cli_exclude.clone(),
+            ])),
+            (selector_exclude, cli_exclude) => selector_exclude.clone().or(cli_exclude.clone()),
+        };
+        let mut exclude = exclude.as_ref().map(SelectExpression::simplify);
         if let (Some(cli_mode), Some(exc)) = (arg.indirect_selection, exclude.as_mut()) {
             exc.set_indirect_selection(cli_mode);
         }
 
-        Ok(ResolvedSelector {
-            include: Some(include),
-            exclude,
-        })
+        Ok(ResolvedSelector { include, exclude })
     } else {
         // No selector chosen → use CLI flags and apply CLI indirect selection
         let mut resolved = ResolvedSelector {
-            include: arg.select.clone(),
-            exclude: arg.exclude.clone(),
+            include: arg.select.as_ref().map(SelectExpression::simplify),
+            exclude: arg.exclude.as_ref().map(SelectExpression::simplify),
         };
 
         let default_mode = if arg.indirect_selection.is_some() {
@@ -197,11 +219,17 @@ fn resolve_selector_definitions(
     let mut resolved_selectors = HashMap::new();
 
     for def in yaml.selectors {
-        let resolved = parser.parse_definition(&def.definition)?;
+        let (include, exclude) = match parser.parse_definition(&def.definition)? {
+            // A definition that is only an `exclude:` block removes nodes
+            // from the run's selection rather than selecting any.
+            SelectExpression::Exclude(excluded) => (None, Some(*excluded)),
+            include => (Some(include), None),
+        };
         resolved_selectors.insert(
             def.name.clone(),
             SelectorEntry {
-                include: resolved,
+                include,
+                exclude,
                 is_default: def.default.unwrap_or(false),
                 description: def.description,
             },
@@ -212,60 +240,15 @@ fn resolve_selector_definitions(
 }
 
 /// Converts a SelectExpression to the normalized YAML format expected by the manifest.
-fn select_expression_to_yaml(expr: &SelectExpression) -> YmlValue {
-    match expr {
-        SelectExpression::Atom(criteria) => {
-            let mut map = dbt_yaml::Mapping::new();
-            map.insert(
-                YmlValue::String("method".to_string(), Default::default()),
-                YmlValue::String(criteria.method.to_string(), Default::default()),
-            );
-            map.insert(
-                YmlValue::String("value".to_string(), Default::default()),
-                YmlValue::String(criteria.value.clone(), Default::default()),
-            );
-
-            if criteria.parents_depth.is_some() {
-                map.insert(
-                    YmlValue::String("parents".to_string(), Default::default()),
-                    YmlValue::Bool(true, Default::default()),
-                );
-                // include the depth value if it's not unlimited
-                if let Some(depth) = criteria.parents_depth
-                    && depth != u32::MAX
-                {
-                    map.insert(
-                        YmlValue::String("parents_depth".to_string(), Default::default()),
-                        YmlValue::String(depth.to_string(), Default::default()),
-                    );
-                }
-            }
-            if criteria.children_depth.is_some() {
-                map.insert(
-                    YmlValue::String("children".to_string(), Default::default()),
-                    YmlValue::Bool(true, Default::default()),
-                );
-                // include the depth value if it's not unlimited
-                if let Some(depth) = criteria.children_depth
-                    && depth != u32::MAX
-                {
-                    map.insert(
-                        YmlValue::String("children_depth".to_string(), Default::default()),
-                        YmlValue::String(depth.to_string(), Default::default()),
-                    );
-                }
-            }
-            if criteria.childrens_parents {
-                map.insert(
-                    YmlValue::String("childrens_parents".to_string(), Default::default()),
-                    YmlValue::Bool(true, Default::default()),
-                );
-            }
-
-            YmlValue::Mapping(map, Default::default())
-        }
+/// Fails on `Empty`, which no selector definition can express.
+fn select_expression_to_yaml(expr: &SelectExpression) -> FsResult<YmlValue> {
+    Ok(match expr {
+        SelectExpression::Atom(criteria) => criteria_to_yaml(criteria, ""),
         SelectExpression::Or(expressions) => {
-            let values: Vec<YmlValue> = expressions.iter().map(select_expression_to_yaml).collect();
+            let values = expressions
+                .iter()
+                .map(select_expression_to_yaml)
+                .collect::<FsResult<Vec<_>>>()?;
 
             let mut union_map = dbt_yaml::Mapping::new();
             union_map.insert(
@@ -275,7 +258,10 @@ fn select_expression_to_yaml(expr: &SelectExpression) -> YmlValue {
             YmlValue::Mapping(union_map, Default::default())
         }
         SelectExpression::And(expressions) => {
-            let values: Vec<YmlValue> = expressions.iter().map(select_expression_to_yaml).collect();
+            let values = expressions
+                .iter()
+                .map(select_expression_to_yaml)
+                .collect::<FsResult<Vec<_>>>()?;
 
             let mut intersection_map = dbt_yaml::Mapping::new();
             intersection_map.insert(
@@ -285,14 +271,89 @@ fn select_expression_to_yaml(expr: &SelectExpression) -> YmlValue {
             YmlValue::Mapping(intersection_map, Default::default())
         }
         SelectExpression::Exclude(expr) => {
+            // one entry per excluded selector, as `exclude:` lists them
+            let values = match expr.as_ref() {
+                SelectExpression::Or(expressions) => expressions
+                    .iter()
+                    .map(select_expression_to_yaml)
+                    .collect::<FsResult<Vec<_>>>()?,
+                expr => vec![select_expression_to_yaml(expr)?],
+            };
+
             let mut exclude_map = dbt_yaml::Mapping::new();
             exclude_map.insert(
                 YmlValue::String("exclude".to_string(), Default::default()),
-                select_expression_to_yaml(expr),
+                YmlValue::Sequence(values, Default::default()),
             );
             YmlValue::Mapping(exclude_map, Default::default())
         }
+        SelectExpression::Not(expr) => match expr.as_ref() {
+            // written back with the `not:` method prefix it was parsed from
+            SelectExpression::Atom(criteria) => criteria_to_yaml(criteria, "not:"),
+            // only method atoms take the prefix; an `exclude:` block selects
+            // the same nodes
+            _ => select_expression_to_yaml(&SelectExpression::Exclude(expr.clone()))?,
+        },
+        SelectExpression::Empty => {
+            return err!(
+                ErrorCode::SelectorError,
+                "An empty selection has no selector definition"
+            );
+        }
+    })
+}
+
+/// Converts a method atom to its manifest mapping, with `prefix` (e.g.
+/// `not:`) prepended to the method name.
+fn criteria_to_yaml(criteria: &SelectionCriteria, prefix: &str) -> YmlValue {
+    let mut map = dbt_yaml::Mapping::new();
+    map.insert(
+        YmlValue::String("method".to_string(), Default::default()),
+        YmlValue::String(format!("{prefix}{}", criteria.method), Default::default()),
+    );
+    map.insert(
+        YmlValue::String("value".to_string(), Default::default()),
+        YmlValue::String(criteria.value.clone(), Default::default()),
+    );
+
+    if criteria.parents_depth.is_some() {
+        map.insert(
+            YmlValue::String("parents".to_string(), Default::default()),
+            YmlValue::Bool(true, Default::default()),
+        );
+        // include the depth value if it's not unlimited
+        if let Some(depth) = criteria.parents_depth
+            && depth != u32::MAX
+        {
+            map.insert(
+                YmlValue::String("parents_depth".to_string(), Default::default()),
+                YmlValue::Number(u64::from(depth).into(), Default::default()),
+            );
+        }
     }
+    if criteria.children_depth.is_some() {
+        map.insert(
+            YmlValue::String("children".to_string(), Default::default()),
+            YmlValue::Bool(true, Default::default()),
+        );
+        // include the depth value if it's not unlimited
+        if let Some(depth) = criteria.children_depth
+            && depth != u32::MAX
+        {
+            map.insert(
+                YmlValue::String("children_depth".to_string(), Default::default()),
+                YmlValue::Number(u64::from(depth).into(), Default::default()),
+            );
+        }
+    }
+    if criteria.childrens_parents {
+        map.insert(
+            YmlValue::String("childrens_parents".to_string(), Default::default()),
+            YmlValue::Bool(true, Default::default()),
+        );
+    }
+
+    YmlValue::Mapping(map, Default::default())
 }
 
 fn validate_default_selectors(resolved_selectors: &HashMap<String, SelectorEntry>) -> FsResult<()> {
@@ -304,3 +365,205 @@ fn validate_default_selectors(resolved_selectors: &HashMap<String, SelectorEntry
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbt_common::node_selector::parse_model_specifiers;
+
+    fn resolve(yaml: &str) -> FsResult<HashMap<String, SelectorEntry>> {
+        let yaml: SelectorFile = dbt_yaml::from_str(yaml).unwrap();
+        resolve_selector_definitions(yaml, &ResolveArgs::default())
+    }
+
+    #[test]
+    fn test_string_definition_resolves_selector_references() -> FsResult<()> {
+        let resolved = resolve(
+            r#"
+selectors:
+  - name: nightly
+    definition:
+      tag: nightly
+  - name: nightly_marts
+    definition: "selector:nightly,path:models/marts"
+"#,
+        )?;
+        assert_eq!(
+            resolved["nightly_marts"].include,
+            Some(parse_model_specifiers(&[
+                "tag:nightly,path:models/marts".to_string()
+            ])?)
+        );
+
+        let missing = resolve(
+            r#"
+selectors:
+  - name: broken
+    definition: "tag:nightly,selector:missing"
+"#,
+        );
+        assert!(missing.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_only_definition() -> FsResult<()> {
+        let resolved = resolve(
+            r#"
+selectors:
+  - name: skip_slow
+    default: true
+    definition:
+      exclude:
+        - "tag:slow"
+"#,
+        )?;
+        let entry = &resolved["skip_slow"];
+        let slow = parse_model_specifiers(&["tag:slow".to_string()])?;
+        assert_eq!(entry.include, None);
+        assert_eq!(entry.exclude, Some(slow.clone()));
+
+        // the default selector's exclude applies on its own ...
+        let final_selectors = resolve_final_selectors(resolved.clone(), &ResolveArgs::default())?;
+        assert_eq!(final_selectors.include, None);
+        assert_eq!(final_selectors.exclude, Some(slow.clone()));
+
+        // ... and is merged with the CLI exclude when selected explicitly
+        let legacy = parse_model_specifiers(&["fqn:legacy".to_string()])?;
+        let arg = ResolveArgs {
+            selector: Some("skip_slow".to_string()),
+            exclude: Some(legacy.clone()),
+            ..ResolveArgs::default()
+        };
+        let final_selectors = resolve_final_selectors(resolved.clone(), &arg)?;
+        assert_eq!(final_selectors.include, None);
+        assert_eq!(
+            final_selectors.exclude,
+            Some(SelectExpression::Or(vec![slow, legacy]))
+        );
+
+        // the manifest keeps the `exclude:` block, which parses back to the
+        // same selection
+        let manifest = resolve_manifest_selectors(resolved)?;
+        assert!(
+            manifest["skip_slow"]
+                .__definition__
+                .get("exclude")
+                .is_some_and(YmlValue::is_sequence)
+        );
+        let reparsed = reparse_manifest_selector(&manifest, "skip_slow")?;
+        assert_eq!(
+            reparsed.stable_hash(),
+            SelectExpression::Exclude(Box::new(slow)).stable_hash()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_only_definition_with_several_excludes() -> FsResult<()> {
+        let resolved = resolve(
+            r#"
+selectors:
+  - name: skip_slow_and_legacy
+    definition:
+      exclude:
+        - "tag:slow"
+        - method: fqn
+          value: legacy
+"#,
+        )?;
+        let excluded = resolved["skip_slow_and_legacy"].exclude.clone().unwrap();
+
+        let manifest = resolve_manifest_selectors(resolved)?;
+        let reparsed = reparse_manifest_selector(&manifest, "skip_slow_and_legacy")?;
+        assert_eq!(
+            reparsed.stable_hash(),
+            SelectExpression::Exclude(Box::new(excluded)).stable_hash()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_definition_round_trip() -> FsResult<()> {
+        let resolved = resolve(
+            r#"
+selectors:
+  - name: marts
+    definition:
+      union:
+        - method: tag
+          value: nightly
+          parents: true
+        - intersection:
+            - "path:models/marts"
+            - method: fqn
+              value: finance
+              children: true
+              children_depth: 2
+"#,
+        )?;
+        let include = resolved["marts"].include.clone().unwrap();
+
+        let manifest = resolve_manifest_selectors(resolved)?;
+        let reparsed = reparse_manifest_selector(&manifest, "marts")?;
+        assert_eq!(reparsed.stable_hash(), include.stable_hash());
+
+        let empty = HashMap::from([(
+            "empty".to_string(),
+            SelectorEntry {
+                include: Some(SelectExpression::Empty),
+                exclude: None,
+                is_default: false,
+                description: None,
+            },
+        )]);
+        let err = resolve_manifest_selectors(empty).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Cannot write selector `empty` to the manifest")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_keeps_not_prefix() -> FsResult<()> {
+        let resolved = resolve(
+            r#"
+selectors:
+  - name: finance_not_nightly
+    definition:
+      intersection:
+        - method: fqn
+          value: finance
+        - method: not:tag
+          value: nightly
+"#,
+        )?;
+        let include = resolved["finance_not_nightly"].include.clone().unwrap();
+
+        let manifest = resolve_manifest_selectors(resolved)?;
+        let negated = &manifest["finance_not_nightly"].__definition__["intersection"][1];
+        assert_eq!(negated["method"].as_str(), Some("not:tag"));
+        let reparsed = reparse_manifest_selector(&manifest, "finance_not_nightly")?;
+        assert_eq!(reparsed.stable_hash(), include.stable_hash());
+        Ok(())
+    }
+
+    /// Parse the selector `name` back from the `selectors` of a manifest.
+    fn reparse_manifest_selector(
+        manifest: &BTreeMap<String, DbtSelector>,
+        name: &str,
+    ) -> FsResult<SelectExpression> {
+        let selector = &manifest[name];
+        let manifest_json = serde_json::json!({
+            "selectors": {
+                name: {
+                    "name": selector.name,
+                    "definition": serde_json::to_value(&selector.__definition__).unwrap(),
+                }
+            }
+        });
+        let io_args = dbt_common::io_args::IoArgs::default();
+        SelectorParser::from_manifest_json(&manifest_json.to_string(), &io_args)?.parse_named(name)
+    }
+}
@@ -276,7 +276,7 @@ pub struct CommonArgs {
     #[arg(global = true, long, group = "selector_or_select")]
     pub selector: Option<String>,
 
-    /// Choose which tests to select adjacent to resources: eager (most inclusive), cautious (most exclusive), buildable (inbetween) or empty.
+    /// Choose which tests to select adjacent to resources: eager (most inclusive), cautious (most exclusive), buildable (inbetween), buildany or empty.
     #[arg(global = true, long, env = "DBT_INDIRECT_SELECTION")]
     pub indirect_selection: Option<IndirectSelection>,
 
@@ -771,3 +771,24 @@ pub fn from_lib(cli: &Cli) -> SystemArgs {
         num_threads: cli.common_args().threads,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_and_exclude_reject_selector_method() {
+        for flag in ["--select", "--exclude"] {
+            let err =
+                Cli::try_parse_from(["dbt-sa-cli", "parse", flag, "selector:nightly"]).unwrap_err();
+            assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+            assert!(err.to_string().contains("--selector <name>"), "{err}");
+        }
+
+        let cli = Cli::try_parse_from(["dbt-sa-cli", "parse", "--selector", "nightly"]).unwrap();
+        let Commands::Parse(args) = cli.command else {
+            panic!("expected the parse command");
+        };
+        assert_eq!(args.common_args.selector.as_deref(), Some("nightly"));
+    }
+}
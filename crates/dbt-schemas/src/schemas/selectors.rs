@@ -2,8 +2,11 @@ use std::collections::BTreeMap;
 use std::ops::Deref;
 
 use dbt_common::node_selector::{IndirectSelection, SelectExpression};
+use dbt_common::{ErrorCode, FsResult, fs_err};
 use dbt_yaml::{JsonSchema, UntaggedEnumDeserialize};
+use indexmap::IndexMap;
 use serde::de::{self, IgnoredAny, MapAccess, Visitor};
+use serde::ser::SerializeMap;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
@@ -172,6 +175,21 @@ pub enum SelectorDefinitionValue {
     Full(SelectorExpr),
 }
 
+impl SelectorDefinitionValue {
+    /// The YAML text of this definition, as it would be written under
+    /// `definition:` in `selectors.yml`; parsing it back yields an
+    /// equivalent definition.
+    pub fn to_yaml_string(&self) -> FsResult<String> {
+        dbt_yaml::to_string(self).map_err(|e| {
+            fs_err!(
+                ErrorCode::SelectorError,
+                "Failed to serialize selector definition to YAML: {}",
+                e
+            )
+        })
+    }
+}
+
 /// Top‐level expression: either a boolean node or a single atom
 #[derive(Serialize, UntaggedEnumDeserialize, Debug, Clone, JsonSchema)]
 #[serde(untagged)]
@@ -181,10 +199,25 @@ pub enum SelectorExpr {
 }
 
 /// A boolean composition of other selectors
-#[derive(Serialize, Debug, Clone, JsonSchema)]
-#[serde(rename_all = "lowercase")]
+#[derive(Debug, Clone, JsonSchema)]
 pub struct CompositeExpr {
-    pub kind: BTreeMap<String, CompositeKind>,
+    /// Keyed by `union`/`intersection`, in the order the keys appear in YAML.
+    pub kind: IndexMap<String, CompositeKind>,
+}
+
+/// Serializes as written in YAML, e.g. `union: [...]`, so that the output
+/// deserializes back into the same expression.
+impl Serialize for CompositeExpr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.kind.len()))?;
+        for kind in self.kind.values() {
+            map.serialize_entry(&kind.to_string(), kind.values())?;
+        }
+        map.end()
+    }
 }
 
 impl<'de> Deserialize<'de> for CompositeExpr {
@@ -200,7 +233,7 @@ impl<'de> Deserialize<'de> for CompositeExpr {
             fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
                 write!(
                     f,
-                    "a map with exactly one of the keys 'union' or 'intersection'"
+                    "a map with exactly one of the keys 'union', 'intersection' or 'difference'"
                 )
             }
 
@@ -217,7 +250,7 @@ impl<'de> Deserialize<'de> for CompositeExpr {
                             if composite_kind.is_some() {
                                 let _: IgnoredAny = map.next_value()?;
                                 return Err(de::Error::custom(
-                                    "multiple keys provided; expected only one of 'union' or 'intersection'",
+                                    "multiple keys provided; expected only one of 'union', 'intersection' or 'difference'",
                                 ));
                             }
                             let values: Vec<SelectorDefinitionValue> = map.next_value()?;
@@ -228,18 +261,29 @@ impl<'de> Deserialize<'de> for CompositeExpr {
                             if composite_kind.is_some() {
                                 let _: IgnoredAny = map.next_value()?;
                                 return Err(de::Error::custom(
-                                    "multiple keys provided; expected only one of 'union' or 'intersection'",
+                                    "multiple keys provided; expected only one of 'union', 'intersection' or 'difference'",
                                 ));
                             }
                             let values: Vec<SelectorDefinitionValue> = map.next_value()?;
                             composite_kind = Some(CompositeKind::Intersection(values));
                             found_key = Some("intersection".to_string());
                         }
+                        "difference" => {
+                            if composite_kind.is_some() {
+                                let _: IgnoredAny = map.next_value()?;
+                                return Err(de::Error::custom(
+                                    "multiple keys provided; expected only one of 'union', 'intersection' or 'difference'",
+                                ));
+                            }
+                            let values: Vec<SelectorDefinitionValue> = map.next_value()?;
+                            composite_kind = Some(CompositeKind::Difference(values));
+                            found_key = Some("difference".to_string());
+                        }
                         other => {
                             let _: IgnoredAny = map.next_value()?;
                             return Err(de::Error::unknown_field(
                                 other,
-                                &["union", "intersection"],
+                                &["union", "intersection", "difference"],
                             ));
                         }
                     }
@@ -247,12 +291,12 @@ impl<'de> Deserialize<'de> for CompositeExpr {
 
                 match (found_key, composite_kind) {
                     (Some(key), Some(kind)) => {
-                        let mut m = BTreeMap::new();
+                        let mut m = IndexMap::new();
                         m.insert(key, kind);
                         Ok(CompositeExpr { kind: m })
                     }
                     _ => Err(de::Error::custom(
-                        "expected a map with a 'union' or 'intersection' key",
+                        "expected a map with a 'union', 'intersection' or 'difference' key",
                     )),
                 }
             }
@@ -262,12 +306,35 @@ impl<'de> Deserialize<'de> for CompositeExpr {
     }
 }
 
-/// Is this an `OR` or an `AND`?
+/// Is this an `OR`, an `AND`, or an `A AND NOT B`?
 #[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum CompositeKind {
     Union(Vec<SelectorDefinitionValue>),
     Intersection(Vec<SelectorDefinitionValue>),
+    /// Exactly two values: what the first selects minus what the second selects.
+    Difference(Vec<SelectorDefinitionValue>),
+}
+
+impl CompositeKind {
+    pub fn values(&self) -> &[SelectorDefinitionValue] {
+        match self {
+            CompositeKind::Union(values)
+            | CompositeKind::Intersection(values)
+            | CompositeKind::Difference(values) => values,
+        }
+    }
+}
+
+/// The YAML key of the composite: `union`, `intersection` or `difference`.
+impl std::fmt::Display for CompositeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CompositeKind::Union(_) => "union",
+            CompositeKind::Intersection(_) => "intersection",
+            CompositeKind::Difference(_) => "difference",
+        })
+    }
 }
 
 //
@@ -296,8 +363,12 @@ pub struct ResolvedSelector {
 /// What we really need at runtime for each selector.
 #[derive(Debug, Clone)]
 pub struct SelectorEntry {
-    pub include: SelectExpression, // the include expression (which may contain nested excludes)
-    pub is_default: bool,          // original `default: true`
+    // the include expression (which may contain nested excludes); unset
+    // when the whole definition is an `exclude:` block
+    pub include: Option<SelectExpression>,
+    // set when the whole definition is an `exclude:` block
+    pub exclude: Option<SelectExpression>,
+    pub is_default: bool,            // original `default: true`
     pub description: Option<String>, // docs string from YAML
 }
 
@@ -333,3 +404,66 @@ pub struct MethodAtomExpr {
 pub struct ExcludeAtomExpr {
     pub exclude: Vec<SelectorDefinitionValue>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(yaml: &str) -> (serde_json::Value, serde_json::Value) {
+        let def: SelectorDefinitionValue = dbt_yaml::from_str(yaml).unwrap();
+        let exported = def.to_yaml_string().unwrap();
+        let reparsed: SelectorDefinitionValue = dbt_yaml::from_str(&exported)
+            .unwrap_or_else(|e| panic!("{e} in exported YAML:\n{exported}"));
+        (
+            serde_json::to_value(&reparsed).unwrap(),
+            serde_json::to_value(&def).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_to_yaml_string_round_trip() {
+        for yaml in [
+            "tag:nightly",
+            "\"+path:models/marts+ tag:hourly\"",
+            "tag: nightly",
+            "method: config.materialized\nvalue: true\nparents_depth: 2\nindirect_selection: cautious",
+            "exclude:\n  - tag: slow\n  - \"fqn:legacy\"",
+            r#"
+union:
+  - method: selector
+    value: base
+  - intersection:
+      - "tag:finance"
+      - method: path
+        value: models/marts
+        children: true
+        exclude:
+          - tag: deprecated
+  - exclude:
+      - "tag:slow"
+"#,
+            "intersection:\n  - tag: a\n  - difference:\n      - tag: b\n      - \"tag:c\"",
+        ] {
+            let (reparsed, original) = round_trip(yaml);
+            assert_eq!(reparsed, original, "{yaml}");
+        }
+    }
+
+    #[test]
+    fn test_to_yaml_string_writes_composite_keys() {
+        let def: SelectorDefinitionValue =
+            dbt_yaml::from_str("intersection:\n  - tag: a\n  - \"tag:b\"").unwrap();
+        let exported = def.to_yaml_string().unwrap();
+        assert!(exported.starts_with("intersection:"), "{exported}");
+        assert!(!exported.contains("kind"), "{exported}");
+
+        let SelectorDefinitionValue::Full(SelectorExpr::Composite(comp)) = def else {
+            panic!("expected a composite");
+        };
+        let kind = &comp.kind["intersection"];
+        assert_eq!(kind.to_string(), "intersection");
+        assert_eq!(kind.values().len(), 2);
+        assert_eq!(CompositeKind::Union(vec![]).to_string(), "union");
+        assert_eq!(CompositeKind::Difference(vec![]).to_string(), "difference");
+    }
+}
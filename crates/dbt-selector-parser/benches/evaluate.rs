@@ -0,0 +1,110 @@
+//! Benchmarks for the reference selector evaluator.
+//!
+//! Run with `cargo bench -p dbt-selector-parser` and compare against
+//! `cargo bench -p dbt-selector-parser --features rayon`.
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use dbt_common::node_selector::{SelectExpression, parse_model_specifiers};
+use dbt_selector_parser::evaluator::{
+    EvalStrategy, GraphAwareEvaluator, Node, evaluate_select_expression,
+};
+
+const NODE_COUNT: usize = 10_000;
+const GRAPH_NODE_COUNT: usize = 1_000;
+const UNION_ARMS: usize = 20;
+
+fn synthetic_nodes(count: usize) -> Vec<Node> {
+    (0..count)
+        .map(|i| Node {
+            unique_id: format!("model.bench.model_{i}"),
+            resource_type: "model".to_string(),
+            package_name: "bench".to_string(),
+            fqn: vec![
+                "bench".to_string(),
+                format!("group_{}", i % 50),
+                format!("model_{i}"),
+            ],
+            path: format!("models/group_{}/model_{i}.sql", i % 50),
+            tags: vec![
+                format!("tag_{}", i % 100),
+                format!("team_{}_nightly", i % 7),
+            ],
+            group: None,
+            access: None,
+            semantic_model: None,
+            attached_node: None,
+            version: None,
+            latest_version: None,
+            checksum: format!("{i}"),
+            source_status: None,
+        })
+        .collect()
+}
+
+fn bench_union(c: &mut Criterion) {
+    let nodes = synthetic_nodes(NODE_COUNT);
+    let arms = (0..UNION_ARMS)
+        .map(|i| format!("tag:tag_{}", i * 5))
+        .collect::<Vec<_>>();
+    let expr = parse_model_specifiers(&arms).unwrap();
+
+    c.bench_function("evaluate_or_20_arms_10k_nodes", |b| {
+        b.iter(|| evaluate_select_expression(black_box(&expr), black_box(&nodes)))
+    });
+}
+
+/// An intersection whose first arm selects nothing; the expensive glob arms
+/// after it are skipped once the running result is empty.
+fn bench_restrictive_intersection(c: &mut Criterion) {
+    let nodes = synthetic_nodes(NODE_COUNT);
+    let mut arms = vec![parse_model_specifiers(&["tag:does_not_exist".to_string()]).unwrap()];
+    arms.extend(
+        (0..UNION_ARMS)
+            .map(|i| parse_model_specifiers(&[format!("path:models/group_{i}*/*.sql")]).unwrap()),
+    );
+    let expr = SelectExpression::And(arms);
+
+    c.bench_function("evaluate_and_empty_first_arm_10k_nodes", |b| {
+        b.iter(|| evaluate_select_expression(black_box(&expr), black_box(&nodes)))
+    });
+}
+
+/// An intersection of a union of graph walks and a restrictive atom, on a
+/// 1000-node graph where every model depends on the one before it.
+/// `CheapFirst` evaluates the atom first and skips the walks.
+fn bench_and_strategies(c: &mut Criterion) {
+    let nodes = synthetic_nodes(GRAPH_NODE_COUNT);
+    let edges = nodes
+        .windows(2)
+        .map(|pair| (pair[0].unique_id.clone(), pair[1].unique_id.clone()))
+        .collect::<Vec<_>>();
+    let walks = (0..UNION_ARMS)
+        .map(|i| format!("+tag:tag_{}+", i * 5))
+        .collect::<Vec<_>>();
+    let expr = SelectExpression::And(vec![
+        parse_model_specifiers(&walks).unwrap(),
+        parse_model_specifiers(&["tag:does_not_exist".to_string()]).unwrap(),
+    ]);
+
+    for (name, strategy) in [
+        (
+            "evaluate_and_declared_order_1k_graph",
+            EvalStrategy::Declared,
+        ),
+        (
+            "evaluate_and_cheap_first_1k_graph",
+            EvalStrategy::CheapFirst,
+        ),
+    ] {
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges.clone()).with_strategy(strategy);
+        c.bench_function(name, |b| b.iter(|| evaluator.evaluate(black_box(&expr))));
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_union,
+    bench_restrictive_intersection,
+    bench_and_strategies
+);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+//! Fuzzes parsing of selector definition strings, e.g. the `definition:` of
+//! a selector in `selectors.yml`. Parsing may reject the input, but must
+//! never panic.
+//!
+//! Run from `crates/dbt-selector-parser` with a nightly toolchain and
+//! `cargo-fuzz` installed:
+//!
+//! ```sh
+//! cargo +nightly fuzz run parse_selector
+//! ```
+//!
+//! Crashing inputs are written to `fuzz/artifacts/parse_selector/` and can
+//! be replayed with `cargo +nightly fuzz run parse_selector <path>`.
+
+#![no_main]
+
+use std::collections::BTreeMap;
+
+use dbt_schemas::schemas::selectors::SelectorDefinitionValue;
+use dbt_selector_parser::SelectorParser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(selector) = std::str::from_utf8(data) else {
+        return;
+    };
+    let parser = SelectorParser::new_standalone(BTreeMap::new());
+    let _ = parser.parse_definition(&SelectorDefinitionValue::String(selector.to_string()));
+});
@@ -0,0 +1,137 @@
+//! On-disk cache of parsed selectors.
+//!
+//! The cache is a single JSON file under the target directory holding the
+//! parsed `SelectExpression` for every named selector together with a hash
+//! of the `selectors.yml` contents it was built from. A cache whose hash does
+//! not match the current input is ignored and overwritten.
+//
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use dbt_common::{
+    ErrorCode, FsResult, fs_err, hashing::code_hash, node_selector::SelectExpression, stdfs,
+};
+use serde::{Deserialize, Serialize};
+
+pub const SELECTOR_CACHE_FILE_NAME: &str = "selector_cache.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    input_hash: String,
+    selectors: BTreeMap<String, SelectExpression>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectorCache {
+    path: PathBuf,
+}
+
+impl SelectorCache {
+    pub fn new(target_dir: &Path) -> Self {
+        Self {
+            path: target_dir.join(SELECTOR_CACHE_FILE_NAME),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the cached selectors if the cache was built from `input`.
+    ///
+    /// A missing, unreadable or stale cache file is treated as a miss.
+    pub fn load(&self, input: &str) -> Option<BTreeMap<String, SelectExpression>> {
+        let contents = stdfs::read_to_string(&self.path).ok()?;
+        let cache: CacheFile = serde_json::from_str(&contents).ok()?;
+        (cache.input_hash == code_hash(input)).then_some(cache.selectors)
+    }
+
+    /// Write `selectors` to the cache, keyed on the hash of `input`.
+    pub fn store(
+        &self,
+        input: &str,
+        selectors: &BTreeMap<String, SelectExpression>,
+    ) -> FsResult<()> {
+        let cache = CacheFile {
+            input_hash: code_hash(input),
+            selectors: selectors.clone(),
+        };
+        let json = serde_json::to_string(&cache).map_err(|e| {
+            fs_err!(
+                ErrorCode::JsonError,
+                "Failed to serialize selector cache: {}",
+                e
+            )
+        })?;
+        if let Some(parent) = self.path.parent() {
+            stdfs::create_dir_all(parent)?;
+        }
+        stdfs::write(&self.path, json)
+    }
+
+    /// Load the selectors for `input` from the cache, or run `parse` and
+    /// cache its result on a miss.
+    pub fn load_or_parse<F>(
+        &self,
+        input: &str,
+        parse: F,
+    ) -> FsResult<BTreeMap<String, SelectExpression>>
+    where
+        F: FnOnce() -> FsResult<BTreeMap<String, SelectExpression>>,
+    {
+        if let Some(selectors) = self.load(input) {
+            return Ok(selectors);
+        }
+        let selectors = parse()?;
+        self.store(input, &selectors)?;
+        Ok(selectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbt_common::node_selector::parse_model_specifiers;
+
+    fn parsed(selector: &str) -> BTreeMap<String, SelectExpression> {
+        BTreeMap::from([(
+            "nightly".to_string(),
+            parse_model_specifiers(&[selector.to_string()]).unwrap(),
+        )])
+    }
+
+    #[test]
+    fn test_cache_hit_skips_parsing() -> FsResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = SelectorCache::new(dir.path());
+        let input = "selectors: []";
+
+        let first = cache.load_or_parse(input, || Ok(parsed("tag:nightly")))?;
+        assert!(cache.path().exists());
+
+        let second = cache.load_or_parse(input, || panic!("cache should have been used"))?;
+        assert_eq!(first, second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_miss_on_changed_input() -> FsResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = SelectorCache::new(dir.path());
+
+        cache.store("selectors: []", &parsed("tag:nightly"))?;
+        assert!(cache.load("selectors: [] # edited").is_none());
+
+        let reparsed =
+            cache.load_or_parse("selectors: [] # edited", || Ok(parsed("tag:hourly")))?;
+        assert_eq!(reparsed, parsed("tag:hourly"));
+        assert_eq!(
+            cache.load("selectors: [] # edited"),
+            Some(parsed("tag:hourly"))
+        );
+        Ok(())
+    }
+}
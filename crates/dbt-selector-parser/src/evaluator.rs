@@ -0,0 +1,1902 @@
+//! Reference evaluator for `SelectExpression` trees.
+//!
+//! The scheduler owns the production evaluator; this one works over a flat,
+//! in-memory list of nodes and is used to exercise selector semantics in
+//! tests and tooling without loading a full manifest.
+//
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use dbt_common::{
+    ErrorCode, FsResult,
+    dashmap::{self, DashMap},
+    err,
+    node_selector::{
+        MethodName, NodeMeta, SelectExpression, SelectionCriteria, SourceStatusKind, StateMethod,
+        VersionSelector, custom_method_matches,
+    },
+};
+use glob::Pattern;
+use petgraph::{
+    Direction,
+    algo::toposort,
+    graph::{DiGraph, NodeIndex},
+};
+
+pub type NodeId = String;
+
+/// The attributes of a node that selector methods can match against.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Node {
+    pub unique_id: NodeId,
+    pub resource_type: String,
+    pub package_name: String,
+    pub fqn: Vec<String>,
+    /// Path of the defining file, relative to the project root.
+    pub path: String,
+    pub tags: Vec<String>,
+    /// The dbt group the node belongs to, if any.
+    pub group: Option<String>,
+    /// The model's access level: `public`, `protected` or `private`.
+    pub access: Option<String>,
+    /// The semantic model defined on top of the node, if any.
+    pub semantic_model: Option<String>,
+    /// For unit tests, the name of the model under test.
+    pub attached_node: Option<String>,
+    /// For versioned models, the version of this node and the latest
+    /// version of the model.
+    pub version: Option<u32>,
+    pub latest_version: Option<u32>,
+    /// Content checksum, compared by `state:modified`.
+    pub checksum: String,
+    /// For sources, the outcome of the last freshness check, matched by
+    /// `source_status:`.
+    pub source_status: Option<SourceStatusKind>,
+}
+
+impl Node {
+    /// The view of this node passed to custom selector methods.
+    pub fn meta(&self) -> NodeMeta<'_> {
+        NodeMeta {
+            unique_id: &self.unique_id,
+            resource_type: &self.resource_type,
+            package_name: &self.package_name,
+            fqn: &self.fqn,
+            path: &self.path,
+            tags: &self.tags,
+        }
+    }
+}
+
+/// The handful of node attributes most selector tests need; see
+/// [`SelectorParser::test_selector`](crate::SelectorParser::test_selector).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestNode {
+    pub unique_id: NodeId,
+    pub resource_type: String,
+    pub fqn: Vec<String>,
+    pub path: String,
+    pub tags: Vec<String>,
+}
+
+impl From<&TestNode> for Node {
+    fn from(node: &TestNode) -> Self {
+        Node {
+            unique_id: node.unique_id.clone(),
+            resource_type: node.resource_type.clone(),
+            package_name: node.fqn.first().cloned().unwrap_or_default(),
+            fqn: node.fqn.clone(),
+            path: node.path.clone(),
+            tags: node.tags.clone(),
+            ..Node::default()
+        }
+    }
+}
+
+/// Compiled glob patterns keyed on the raw selector value; `None` marks
+/// values that are not valid globs and must be compared literally.
+static PATTERN_CACHE: LazyLock<DashMap<String, Option<Pattern>>> = LazyLock::new(dashmap::new);
+
+/// Evaluate `expr` against `all_nodes` and return the ids of the selected nodes.
+pub fn evaluate_select_expression(expr: &SelectExpression, all_nodes: &[Node]) -> BTreeSet<NodeId> {
+    evaluate_with_state(expr, all_nodes, None)
+}
+
+/// Like [`evaluate_select_expression`], but `state:` selectors compare
+/// `all_nodes` against the nodes of a previous run. Without a previous
+/// state, `state:` selectors match nothing.
+pub fn evaluate_with_state(
+    expr: &SelectExpression,
+    all_nodes: &[Node],
+    previous_state: Option<&[Node]>,
+) -> BTreeSet<NodeId> {
+    Evaluator {
+        all_nodes,
+        previous_state,
+        graph: None,
+        expansion_filter: None,
+        strategy: EvalStrategy::Declared,
+    }
+    .evaluate(expr)
+}
+
+/// Return the ids of every node in `universe` that `expr` does not select.
+pub fn negate(expr: &SelectExpression, universe: &[Node]) -> BTreeSet<NodeId> {
+    Evaluator {
+        all_nodes: universe,
+        previous_state: None,
+        graph: None,
+        expansion_filter: None,
+        strategy: EvalStrategy::Declared,
+    }
+    .complement(expr)
+}
+
+/// Evaluate the union of `exprs` against `nodes`, mapping each selected
+/// node to the index of the first expression in `exprs` that selects it,
+/// e.g. to assign every node to the earliest processing stage that claims
+/// it.
+pub fn evaluate_or_with_provenance(
+    exprs: &[SelectExpression],
+    nodes: &[Node],
+) -> BTreeMap<NodeId, usize> {
+    let evaluator = Evaluator {
+        all_nodes: nodes,
+        previous_state: None,
+        graph: None,
+        expansion_filter: None,
+        strategy: EvalStrategy::Declared,
+    };
+    let mut claimed = BTreeMap::new();
+    for (index, expr) in exprs.iter().enumerate() {
+        for id in evaluator.evaluate(expr) {
+            claimed.entry(id).or_insert(index);
+        }
+    }
+    claimed
+}
+
+/// Receives the size and evaluation time of each sub-expression evaluated by
+/// [`evaluate_with_metrics`], children before their parent.
+pub trait MetricsCollector {
+    /// `criteria` selected `matched_count` nodes, after its graph operators
+    /// and exclude were applied.
+    fn record_atom_match(
+        &mut self,
+        criteria: &SelectionCriteria,
+        matched_count: usize,
+        duration: Duration,
+    );
+
+    /// A non-atom expression, named by its `variant` (`And`, `Or`,
+    /// `Exclude`, `Not` or `Empty`), selected `result_count` nodes. The
+    /// `duration` includes evaluating its operands.
+    fn record_composite(&mut self, variant: &str, result_count: usize, duration: Duration);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtomMetrics {
+    pub criteria: SelectionCriteria,
+    pub matched_count: usize,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompositeMetrics {
+    pub variant: String,
+    pub result_count: usize,
+    pub duration: Duration,
+}
+
+/// [`MetricsCollector`] that keeps every record, in the order received.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryCollector {
+    pub atoms: Vec<AtomMetrics>,
+    pub composites: Vec<CompositeMetrics>,
+}
+
+impl InMemoryCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MetricsCollector for InMemoryCollector {
+    fn record_atom_match(
+        &mut self,
+        criteria: &SelectionCriteria,
+        matched_count: usize,
+        duration: Duration,
+    ) {
+        self.atoms.push(AtomMetrics {
+            criteria: criteria.clone(),
+            matched_count,
+            duration,
+        });
+    }
+
+    fn record_composite(&mut self, variant: &str, result_count: usize, duration: Duration) {
+        self.composites.push(CompositeMetrics {
+            variant: variant.to_string(),
+            result_count,
+            duration,
+        });
+    }
+}
+
+/// Like [`evaluate_select_expression`], but reports how many nodes each
+/// sub-expression selected and how long it took to `collector`, e.g. to
+/// find the slow part of a selector. Operands an `And` skips once its
+/// intersection is empty are not reported.
+pub fn evaluate_with_metrics(
+    expr: &SelectExpression,
+    nodes: &[Node],
+    collector: &mut dyn MetricsCollector,
+) -> BTreeSet<NodeId> {
+    Evaluator {
+        all_nodes: nodes,
+        previous_state: None,
+        graph: None,
+        expansion_filter: None,
+        strategy: EvalStrategy::Declared,
+    }
+    .evaluate_measured(expr, collector)
+}
+
+/// Results of previously evaluated sub-expressions, reused by
+/// [`evaluate_incremental`] while the node list stays the same.
+///
+/// Entries are keyed on the whole sub-expression, so changing any child of
+/// an `And`/`Or` also misses the cached result of the parent.
+/// Any change to the node list discards every entry.
+#[derive(Debug, Default)]
+pub struct EvalCache {
+    nodes_hash: Option<u64>,
+    entries: HashMap<SelectExpression, BTreeSet<NodeId>>,
+    hits: usize,
+    misses: usize,
+}
+
+impl EvalCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of cached sub-expression results.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Sub-expressions answered from the cache since it was created.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Sub-expressions evaluated from scratch since the cache was created.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+/// Like [`evaluate_select_expression`], but reuses results for
+/// sub-expressions already evaluated against the same `all_nodes`, e.g.
+/// between rebuilds in watch mode.
+pub fn evaluate_incremental(
+    expr: &SelectExpression,
+    all_nodes: &[Node],
+    cache: &mut EvalCache,
+) -> BTreeSet<NodeId> {
+    let nodes_hash = hash_of(all_nodes);
+    if cache.nodes_hash != Some(nodes_hash) {
+        cache.entries.clear();
+        cache.nodes_hash = Some(nodes_hash);
+    }
+    Evaluator {
+        all_nodes,
+        previous_state: None,
+        graph: None,
+        expansion_filter: None,
+        strategy: EvalStrategy::Declared,
+    }
+    .evaluate_cached(expr, cache)
+}
+
+/// The order in which the operands of an `And` are evaluated. The selected
+/// nodes are the same either way; evaluation stops at the first operand
+/// that leaves the intersection empty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EvalStrategy {
+    /// Operands in the order they are written.
+    #[default]
+    Declared,
+    /// `Empty` and atoms before nested expressions, which are usually more
+    /// expensive to evaluate and may not need evaluating at all.
+    CheapFirst,
+}
+
+/// Evaluator that also applies the graph operators of each atom (`n+`,
+/// `+n` and `@`), which [`evaluate_select_expression`] ignores, by walking
+/// the dependency edges between the nodes.
+pub struct GraphAwareEvaluator<'n> {
+    all_nodes: &'n [Node],
+    graph: DiGraph<NodeId, ()>,
+    indices: HashMap<NodeId, NodeIndex>,
+    strategy: EvalStrategy,
+}
+
+impl<'n> GraphAwareEvaluator<'n> {
+    /// `edges` are `(parent, child)` pairs, i.e. they point from a node to
+    /// the nodes that depend on it. Edges whose endpoints are not in
+    /// `all_nodes` are ignored.
+    pub fn new(all_nodes: &'n [Node], edges: impl IntoIterator<Item = (NodeId, NodeId)>) -> Self {
+        let mut graph = DiGraph::with_capacity(all_nodes.len(), 0);
+        let indices = all_nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.unique_id.clone(),
+                    graph.add_node(node.unique_id.clone()),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        for (parent, child) in edges {
+            if let (Some(&parent), Some(&child)) = (indices.get(&parent), indices.get(&child)) {
+                graph.update_edge(parent, child, ());
+            }
+        }
+        Self {
+            all_nodes,
+            graph,
+            indices,
+            strategy: EvalStrategy::default(),
+        }
+    }
+
+    /// Set the order in which the operands of `And` expressions are
+    /// evaluated.
+    pub fn with_strategy(mut self, strategy: EvalStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn graph(&self) -> &DiGraph<NodeId, ()> {
+        &self.graph
+    }
+
+    /// Evaluate `expr` and return the ids of the selected nodes, including
+    /// the ancestors and descendants requested by each atom.
+    pub fn evaluate(&self, expr: &SelectExpression) -> BTreeSet<NodeId> {
+        self.evaluate_with_expansion_filter(expr, None)
+    }
+
+    /// Like [`evaluate`](Self::evaluate), but graph operators only walk into
+    /// nodes whose resource type is in `resource_types`, e.g. `["model"]` to
+    /// expand `orders+` through models without pulling in their tests. Nodes
+    /// matched by an atom itself are kept regardless of their type.
+    pub fn evaluate_with_expansion_filter(
+        &self,
+        expr: &SelectExpression,
+        resource_types: Option<&[String]>,
+    ) -> BTreeSet<NodeId> {
+        Evaluator {
+            all_nodes: self.all_nodes,
+            previous_state: None,
+            graph: Some(self),
+            expansion_filter: resource_types,
+            strategy: self.strategy,
+        }
+        .evaluate(expr)
+    }
+
+    /// The nodes `node` depends on, at most `max_depth` edges upstream; the
+    /// same nodes `max_depth+node` adds. Excludes `node` itself, and is empty
+    /// for an unknown node.
+    pub fn ancestors(&self, node: &NodeId, max_depth: u32) -> BTreeSet<NodeId> {
+        self.neighborhood(node, Direction::Incoming, max_depth)
+    }
+
+    /// The nodes that depend on `node`, at most `max_depth` edges
+    /// downstream; the same nodes `node+max_depth` adds. Excludes `node`
+    /// itself, and is empty for an unknown node.
+    pub fn descendants(&self, node: &NodeId, max_depth: u32) -> BTreeSet<NodeId> {
+        self.neighborhood(node, Direction::Outgoing, max_depth)
+    }
+
+    fn neighborhood(
+        &self,
+        node: &NodeId,
+        direction: Direction,
+        max_depth: u32,
+    ) -> BTreeSet<NodeId> {
+        let Some(&start) = self.indices.get(node) else {
+            return BTreeSet::new();
+        };
+        self.reachable(BTreeSet::from([start]), direction, max_depth, None)
+            .into_iter()
+            .filter(|&index| index != start)
+            .map(|index| self.graph[index].clone())
+            .collect()
+    }
+
+    /// The nodes of `selected` ordered so that every node comes after the
+    /// nodes it depends on, considering only the edges between selected
+    /// nodes, e.g. the order to execute them in. Ids not in the graph are
+    /// ignored.
+    ///
+    /// Fails if the selected nodes depend on each other in a cycle.
+    pub fn topological_sort(&self, selected: &BTreeSet<NodeId>) -> FsResult<Vec<NodeId>> {
+        let induced = self.graph.filter_map(
+            |_, id| selected.contains(id).then(|| id.clone()),
+            |_, _| Some(()),
+        );
+        match toposort(&induced, None) {
+            Ok(order) => Ok(order
+                .into_iter()
+                .map(|index| induced[index].clone())
+                .collect()),
+            Err(cycle) => err!(
+                ErrorCode::SelectorError,
+                "Selected nodes depend on each other in a cycle through `{}`",
+                induced[cycle.node_id()]
+            ),
+        }
+    }
+
+    /// The longest dependency chain, counted in nodes, in the subgraph
+    /// induced by `selected`, ordered from source to sink. Among chains of
+    /// equal length the one with the smallest node ids wins. Like
+    /// [`Self::topological_sort`], only the edges between selected nodes are
+    /// considered, so cycles among unselected nodes do not matter. Returns an
+    /// empty path if the selected nodes depend on each other in a cycle.
+    pub fn critical_path(&self, selected: &BTreeSet<NodeId>) -> Vec<NodeId> {
+        let induced = self.graph.filter_map(
+            |_, id| selected.contains(id).then(|| id.clone()),
+            |_, _| Some(()),
+        );
+        let Ok(order) = toposort(&induced, None) else {
+            return vec![];
+        };
+        // Longest chain ending at each node, and its predecessor.
+        let mut longest: HashMap<NodeIndex, (usize, Option<NodeIndex>)> = HashMap::new();
+        let mut sink: Option<(usize, NodeIndex)> = None;
+        for index in order {
+            let (length, predecessor) = induced
+                .neighbors_directed(index, Direction::Incoming)
+                .filter_map(|parent| longest.get(&parent).map(|&(length, _)| (length, parent)))
+                .max_by(|(a_len, a), (b_len, b)| {
+                    a_len.cmp(b_len).then_with(|| induced[*b].cmp(&induced[*a]))
+                })
+                .map_or((1, None), |(length, parent)| (length + 1, Some(parent)));
+            longest.insert(index, (length, predecessor));
+            if sink.is_none_or(|(best, best_index)| {
+                length > best || (length == best && induced[index] < induced[best_index])
+            }) {
+                sink = Some((length, index));
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut current = sink.map(|(_, index)| index);
+        while let Some(index) = current {
+            path.push(induced[index].clone());
+            current = longest[&index].1;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Add the ancestors and descendants of `selected` that `criteria` asks
+    /// for, walking only into nodes whose resource type is in `filter`.
+    fn expand(
+        &self,
+        criteria: &SelectionCriteria,
+        selected: BTreeSet<NodeId>,
+        filter: Option<&[String]>,
+    ) -> BTreeSet<NodeId> {
+        let start = selected
+            .iter()
+            .filter_map(|id| self.indices.get(id).copied())
+            .collect::<BTreeSet<_>>();
+        let reached = if criteria.childrens_parents {
+            // `@`: every descendant, and every ancestor of those.
+            let descendants = self.reachable(start, Direction::Outgoing, u32::MAX, filter);
+            self.reachable(descendants, Direction::Incoming, u32::MAX, filter)
+        } else {
+            let mut reached = start.clone();
+            if let Some(depth) = criteria.parents_depth {
+                reached.extend(self.reachable(start.clone(), Direction::Incoming, depth, filter));
+            }
+            if let Some(depth) = criteria.children_depth {
+                reached.extend(self.reachable(start, Direction::Outgoing, depth, filter));
+            }
+            reached
+        };
+        let mut expanded = selected;
+        expanded.extend(reached.into_iter().map(|index| self.graph[index].clone()));
+        expanded
+    }
+
+    /// `start` plus every node at most `depth` edges away in `direction`.
+    /// With a `filter`, nodes of other resource types are neither reached
+    /// nor walked through.
+    fn reachable(
+        &self,
+        start: BTreeSet<NodeIndex>,
+        direction: Direction,
+        depth: u32,
+        filter: Option<&[String]>,
+    ) -> BTreeSet<NodeIndex> {
+        let mut frontier = start.iter().copied().collect::<Vec<_>>();
+        let mut reached = start;
+        for _ in 0..depth {
+            frontier = frontier
+                .into_iter()
+                .flat_map(|index| self.graph.neighbors_directed(index, direction))
+                .filter(|&neighbor| {
+                    // Nodes are added to the graph in `all_nodes` order.
+                    let resource_type = &self.all_nodes[neighbor.index()].resource_type;
+                    filter.is_none_or(|types| types.contains(resource_type))
+                })
+                .filter(|&neighbor| reached.insert(neighbor))
+                .collect();
+            if frontier.is_empty() {
+                break;
+            }
+        }
+        reached
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Evaluator<'n> {
+    all_nodes: &'n [Node],
+    previous_state: Option<&'n [Node]>,
+    /// Set when graph operators should be applied to atoms.
+    graph: Option<&'n GraphAwareEvaluator<'n>>,
+    /// Resource types graph operators may expand into; `None` allows all.
+    expansion_filter: Option<&'n [String]>,
+    strategy: EvalStrategy,
+}
+
+impl Evaluator<'_> {
+    fn evaluate(&self, expr: &SelectExpression) -> BTreeSet<NodeId> {
+        match expr {
+            SelectExpression::Atom(criteria) => self.evaluate_atom(criteria),
+            SelectExpression::And(exprs) => self.evaluate_and(exprs),
+            SelectExpression::Or(exprs) => self.evaluate_or(exprs),
+            SelectExpression::Exclude(inner) | SelectExpression::Not(inner) => {
+                self.complement(inner)
+            }
+            SelectExpression::Empty => BTreeSet::new(),
+        }
+    }
+
+    fn evaluate_cached(&self, expr: &SelectExpression, cache: &mut EvalCache) -> BTreeSet<NodeId> {
+        if let Some(cached) = cache.entries.get(expr) {
+            cache.hits += 1;
+            return cached.clone();
+        }
+        cache.misses += 1;
+        let result = match expr {
+            SelectExpression::And(exprs) => {
+                intersect(exprs.iter().map(|expr| self.evaluate_cached(expr, cache)))
+            }
+            SelectExpression::Or(exprs) => exprs
+                .iter()
+                .flat_map(|expr| self.evaluate_cached(expr, cache))
+                .collect(),
+            SelectExpression::Exclude(inner) | SelectExpression::Not(inner) => {
+                let excluded = self.evaluate_cached(inner, cache);
+                self.nodes_except(&excluded)
+            }
+            SelectExpression::Atom(_) | SelectExpression::Empty => self.evaluate(expr),
+        };
+        cache.entries.insert(expr.clone(), result.clone());
+        result
+    }
+
+    fn evaluate_measured(
+        &self,
+        expr: &SelectExpression,
+        collector: &mut dyn MetricsCollector,
+    ) -> BTreeSet<NodeId> {
+        let start = Instant::now();
+        let (variant, result) = match expr {
+            SelectExpression::Atom(criteria) => {
+                let result = self.evaluate_atom(criteria);
+                collector.record_atom_match(criteria, result.len(), start.elapsed());
+                return result;
+            }
+            SelectExpression::And(exprs) => (
+                "And",
+                intersect(
+                    exprs
+                        .iter()
+                        .map(|expr| self.evaluate_measured(expr, collector)),
+                ),
+            ),
+            SelectExpression::Or(exprs) => (
+                "Or",
+                exprs
+                    .iter()
+                    .flat_map(|expr| self.evaluate_measured(expr, collector))
+                    .collect(),
+            ),
+            SelectExpression::Exclude(inner) => (
+                "Exclude",
+                self.nodes_except(&self.evaluate_measured(inner, collector)),
+            ),
+            SelectExpression::Not(inner) => (
+                "Not",
+                self.nodes_except(&self.evaluate_measured(inner, collector)),
+            ),
+            SelectExpression::Empty => ("Empty", BTreeSet::new()),
+        };
+        collector.record_composite(variant, result.len(), start.elapsed());
+        result
+    }
+
+    fn complement(&self, expr: &SelectExpression) -> BTreeSet<NodeId> {
+        self.nodes_except(&self.evaluate(expr))
+    }
+
+    fn nodes_except(&self, excluded: &BTreeSet<NodeId>) -> BTreeSet<NodeId> {
+        self.all_nodes
+            .iter()
+            .filter(|node| !excluded.contains(&node.unique_id))
+            .map(|node| node.unique_id.clone())
+            .collect()
+    }
+
+    fn evaluate_atom(&self, criteria: &SelectionCriteria) -> BTreeSet<NodeId> {
+        let mut selected = self
+            .all_nodes
+            .iter()
+            .filter(|node| self.atom_matches(criteria, node))
+            .map(|node| node.unique_id.clone())
+            .collect::<BTreeSet<_>>();
+        if let Some(graph) = self.graph {
+            selected = graph.expand(criteria, selected, self.expansion_filter);
+        }
+        if let Some(exclude) = &criteria.exclude {
+            let excluded = self.evaluate(exclude);
+            selected.retain(|id| !excluded.contains(id));
+        }
+        selected
+    }
+
+    fn evaluate_and(&self, exprs: &[SelectExpression]) -> BTreeSet<NodeId> {
+        // A plain `all:*` operand leaves the intersection unchanged, so
+        // `And([all:*, Exclude(..)])` only evaluates the exclusion.
+        let mut operands = exprs
+            .iter()
+            .filter(|expr| !is_plain_wildcard(expr))
+            .collect::<Vec<_>>();
+        // `And([])` still selects nothing; only an intersection made up solely
+        // of wildcards selects every node.
+        if operands.is_empty() && !exprs.is_empty() {
+            return self.nodes_except(&BTreeSet::new());
+        }
+        match self.strategy {
+            EvalStrategy::Declared => {
+                intersect(operands.into_iter().map(|expr| self.evaluate(expr)))
+            }
+            EvalStrategy::CheapFirst => {
+                operands.sort_by_key(|expr| match expr {
+                    SelectExpression::Empty => 0,
+                    SelectExpression::Atom(_) => 1,
+                    _ => 2,
+                });
+                intersect(operands.into_iter().map(|expr| self.evaluate(expr)))
+            }
+        }
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    fn evaluate_or(&self, exprs: &[SelectExpression]) -> BTreeSet<NodeId> {
+        let mut result = BTreeSet::new();
+        for expr in exprs {
+            result.extend(self.evaluate(expr));
+        }
+        result
+    }
+
+    #[cfg(feature = "rayon")]
+    fn evaluate_or(&self, exprs: &[SelectExpression]) -> BTreeSet<NodeId> {
+        use rayon::prelude::*;
+
+        exprs.par_iter().map(|expr| self.evaluate(expr)).reduce(
+            BTreeSet::new,
+            |mut acc, selected| {
+                acc.extend(selected);
+                acc
+            },
+        )
+    }
+
+    fn atom_matches(&self, criteria: &SelectionCriteria, node: &Node) -> bool {
+        let value = criteria.value.as_str();
+        match criteria.method {
+            MethodName::Fqn => fqn_matches(value, &node.fqn),
+            MethodName::Tag => node.tags.iter().any(|tag| matches_pattern(value, tag)),
+            MethodName::Path => {
+                let dir = value.trim_end_matches('/');
+                node.path == dir
+                    || node.path.starts_with(&format!("{dir}/"))
+                    || matches_pattern(value, &node.path)
+            }
+            MethodName::File => {
+                let file_name = node.path.rsplit('/').next().unwrap_or_default();
+                matches_pattern(value, file_name) || matches_pattern(value, &node.path)
+            }
+            MethodName::Package => matches_pattern(value, &node.package_name),
+            MethodName::ResourceType => node.resource_type == value,
+            MethodName::Group => node
+                .group
+                .as_deref()
+                .is_some_and(|group| matches_pattern(value, group)),
+            MethodName::Access => node.access.as_deref() == Some(value),
+            MethodName::NodeId => node.unique_id == value,
+            MethodName::SemanticModel => {
+                resource_named(node, "semantic_model", value)
+                    || node
+                        .semantic_model
+                        .as_deref()
+                        .is_some_and(|name| matches_pattern(value, name))
+            }
+            MethodName::SavedQuery => resource_named(node, "saved_query", value),
+            MethodName::UnitTest => {
+                resource_named(node, "unit_test", value)
+                    || (node.resource_type == "unit_test"
+                        && node
+                            .attached_node
+                            .as_deref()
+                            .is_some_and(|model| matches_pattern(value, model)))
+            }
+            MethodName::Source => source_matches(criteria, node),
+            MethodName::Exposure => resource_named(node, "exposure", value),
+            MethodName::Metric => resource_named(node, "metric", value),
+            MethodName::State => self.state_matches(criteria, node),
+            MethodName::Version => version_matches(criteria, node),
+            MethodName::SourceStatus => criteria
+                .source_status_kind()
+                .is_some_and(|kind| node.source_status == Some(kind)),
+            MethodName::Custom => custom_method_matches(criteria, &node.meta()),
+            // every node, whatever the value
+            MethodName::Wildcard => true,
+            _ => false,
+        }
+    }
+
+    fn state_matches(&self, criteria: &SelectionCriteria, node: &Node) -> bool {
+        let (Some(previous_state), Some(state_method)) =
+            (self.previous_state, criteria.state_method())
+        else {
+            return false;
+        };
+        let previous = previous_state
+            .iter()
+            .find(|prev| prev.unique_id == node.unique_id);
+        match state_method {
+            StateMethod::New => previous.is_none(),
+            StateMethod::Modified => previous.is_none_or(|prev| prev.checksum != node.checksum),
+            StateMethod::OldRelation => previous.is_some(),
+        }
+    }
+}
+
+fn version_matches(criteria: &SelectionCriteria, node: &Node) -> bool {
+    let Some(selector) = criteria.version_selector() else {
+        return false;
+    };
+    match (selector, node.version, node.latest_version) {
+        (VersionSelector::Number(wanted), version, _) => version == Some(wanted),
+        (VersionSelector::Latest, Some(version), Some(latest)) => version == latest,
+        (VersionSelector::Prerelease, Some(version), Some(latest)) => version > latest,
+        (VersionSelector::Old, Some(version), Some(latest)) => version < latest,
+        (VersionSelector::Unversioned, version, _) => version.is_none(),
+        _ => false,
+    }
+}
+
+/// Intersect lazily produced sets. An intersection can only shrink, so once
+/// it is empty the remaining sets (including those of `Exclude` arms) are
+/// never produced.
+fn intersect(mut sets: impl Iterator<Item = BTreeSet<NodeId>>) -> BTreeSet<NodeId> {
+    let Some(mut result) = sets.next() else {
+        return BTreeSet::new();
+    };
+    while !result.is_empty() {
+        let Some(next) = sets.next() else {
+            break;
+        };
+        result.retain(|id| next.contains(id));
+    }
+    result
+}
+
+/// An `all:*` atom without graph operators or excludes, which matches every
+/// node.
+fn is_plain_wildcard(expr: &SelectExpression) -> bool {
+    matches!(
+        expr,
+        SelectExpression::Atom(criteria)
+            if criteria.method == MethodName::Wildcard
+                && !criteria.childrens_parents
+                && criteria.parents_depth.is_none()
+                && criteria.children_depth.is_none()
+                && criteria.exclude.is_none()
+    )
+}
+
+fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Match nodes of a single resource type by name, e.g. `exposure:my_dashboard`.
+fn resource_named(node: &Node, resource_type: &str, value: &str) -> bool {
+    node.resource_type == resource_type
+        && node
+            .fqn
+            .last()
+            .is_some_and(|name| matches_pattern(value, name))
+}
+
+/// dbt reads the dotted parts of a `source:` selector, method arguments
+/// first, as `<source>`, `<source>.<table>` or `<package>.<source>.<table>`,
+/// so `source.jaffle_shop:orders` and `source:jaffle_shop.orders` agree.
+fn source_matches(criteria: &SelectionCriteria, node: &Node) -> bool {
+    let [.., source_name, table_name] = node.fqn.as_slice() else {
+        return false;
+    };
+    if node.resource_type != "source" {
+        return false;
+    }
+    let parts = criteria
+        .method_args
+        .iter()
+        .map(String::as_str)
+        .chain(criteria.value.split('.'))
+        .collect::<Vec<_>>();
+    match parts.as_slice() {
+        [source] => matches_pattern(source, source_name),
+        [source, table] => {
+            matches_pattern(source, source_name) && matches_pattern(table, table_name)
+        }
+        [package, source, table] => {
+            matches_pattern(package, &node.package_name)
+                && matches_pattern(source, source_name)
+                && matches_pattern(table, table_name)
+        }
+        _ => false,
+    }
+}
+
+/// dbt matches `fqn:` values either against the node name or against a
+/// dot-separated prefix of the fully-qualified name.
+fn fqn_matches(value: &str, fqn: &[String]) -> bool {
+    if value == "*" {
+        return true;
+    }
+    if fqn.last().is_some_and(|name| matches_pattern(value, name)) {
+        return true;
+    }
+    let parts = value.split('.').collect::<Vec<_>>();
+    parts.len() <= fqn.len()
+        && parts
+            .iter()
+            .zip(fqn)
+            .all(|(part, segment)| matches_pattern(part, segment))
+}
+
+/// Match `candidate` against a selector value that may contain glob syntax
+/// (`*`, `?`, `[abc]`). Values without glob metacharacters compare literally.
+fn matches_pattern(pattern: &str, candidate: &str) -> bool {
+    if !pattern.contains(['*', '?', '[']) {
+        return pattern == candidate;
+    }
+    if let Some(compiled) = PATTERN_CACHE.get(pattern) {
+        return match compiled.value() {
+            Some(glob) => glob.matches(candidate),
+            None => pattern == candidate,
+        };
+    }
+    let compiled = Pattern::new(pattern).ok();
+    let matched = match &compiled {
+        Some(glob) => glob.matches(candidate),
+        None => pattern == candidate,
+    };
+    PATTERN_CACHE.insert(pattern.to_string(), compiled);
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbt_common::node_selector::{
+        SelectorMethod, difference, parse_model_specifiers, register_method,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn model(name: &str, tags: &[&str]) -> Node {
+        Node {
+            unique_id: format!("model.pkg.{name}"),
+            resource_type: "model".to_string(),
+            package_name: "pkg".to_string(),
+            fqn: vec!["pkg".to_string(), "staging".to_string(), name.to_string()],
+            path: format!("models/staging/{name}.sql"),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            group: None,
+            access: None,
+            semantic_model: None,
+            attached_node: None,
+            version: None,
+            latest_version: None,
+            checksum: format!("{name}-v1"),
+            source_status: None,
+        }
+    }
+
+    fn select(selector: &str, nodes: &[Node]) -> Vec<String> {
+        let expr = parse_model_specifiers(&[selector.to_string()]).unwrap();
+        evaluate_select_expression(&expr, nodes)
+            .into_iter()
+            .collect()
+    }
+
+    fn nodes() -> Vec<Node> {
+        vec![
+            model("a", &["nightly", "orders_bronze_v1"]),
+            model("b", &["nightly_full"]),
+            model("c", &["hourly", "users_bronze_v2"]),
+            model("d", &["tag1", "tag2", "tagx"]),
+        ]
+    }
+
+    fn resource(resource_type: &str, name: &str) -> Node {
+        Node {
+            unique_id: format!("{resource_type}.pkg.{name}"),
+            resource_type: resource_type.to_string(),
+            package_name: "pkg".to_string(),
+            fqn: vec!["pkg".to_string(), name.to_string()],
+            path: format!("models/{name}.yml"),
+            tags: vec![],
+            group: None,
+            access: None,
+            semantic_model: None,
+            attached_node: None,
+            version: None,
+            latest_version: None,
+            checksum: format!("{name}-v1"),
+            source_status: None,
+        }
+    }
+
+    #[test]
+    fn test_tag_literal_match() {
+        assert_eq!(select("tag:nightly", &nodes()), vec!["model.pkg.a"]);
+    }
+
+    #[test]
+    fn test_tag_multiple_star_segments() {
+        assert_eq!(
+            select("tag:*_bronze_*", &nodes()),
+            vec!["model.pkg.a", "model.pkg.c"]
+        );
+        assert_eq!(
+            select("tag:nightly*", &nodes()),
+            vec!["model.pkg.a", "model.pkg.b"]
+        );
+    }
+
+    #[test]
+    fn test_tag_question_mark_wildcard() {
+        assert_eq!(select("tag:tag?", &nodes()), vec!["model.pkg.d"]);
+        assert_eq!(select("tag:nightl?", &nodes()), vec!["model.pkg.a"]);
+        assert!(select("tag:hour?", &nodes()).is_empty());
+    }
+
+    #[test]
+    fn test_tag_character_class() {
+        assert_eq!(
+            select("tag:users_bronze_v[12]", &nodes()),
+            vec!["model.pkg.c"]
+        );
+        assert!(select("tag:tag[345]", &nodes()).is_empty());
+    }
+
+    #[test]
+    fn test_fqn_and_path_matching() {
+        assert_eq!(select("b", &nodes()), vec!["model.pkg.b"]);
+        assert_eq!(select("pkg.staging.*", &nodes()).len(), 4);
+        assert_eq!(select("path:models/staging", &nodes()).len(), 4);
+        assert_eq!(select("file:c.sql", &nodes()), vec!["model.pkg.c"]);
+    }
+
+    #[test]
+    fn test_exposure_and_metric_match_by_resource_type() {
+        let nodes = vec![
+            model("my_dashboard", &[]),
+            resource("exposure", "my_dashboard"),
+            resource("metric", "revenue"),
+            resource("metric", "revenue_weekly"),
+        ];
+        assert_eq!(
+            select("exposure:my_dashboard", &nodes),
+            vec!["exposure.pkg.my_dashboard"]
+        );
+        assert_eq!(select("metric:revenue", &nodes), vec!["metric.pkg.revenue"]);
+        assert_eq!(select("metric:revenue*", &nodes).len(), 2);
+        assert!(select("exposure:revenue", &nodes).is_empty());
+    }
+
+    #[test]
+    fn test_difference() {
+        let a = parse_model_specifiers(&["tag:nightly*".to_string()]).unwrap();
+        let all = nodes();
+
+        let a_minus_a = difference(a.clone(), a.clone());
+        assert!(evaluate_select_expression(&a_minus_a, &all).is_empty());
+
+        let a_minus_empty = difference(a.clone(), SelectExpression::Empty);
+        assert_eq!(
+            evaluate_select_expression(&a_minus_empty, &all),
+            evaluate_select_expression(&a, &all)
+        );
+
+        let b = parse_model_specifiers(&["tag:nightly_full".to_string()]).unwrap();
+        assert_eq!(
+            evaluate_select_expression(&difference(a, b), &all)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["model.pkg.a"]
+        );
+    }
+
+    #[test]
+    fn test_state_selectors() {
+        let previous = nodes();
+        let mut current = nodes();
+        current[1].checksum = "b-v2".to_string();
+        current.pop();
+        current.push(model("e", &[]));
+
+        let state = |selector: &str| {
+            let expr = parse_model_specifiers(&[selector.to_string()]).unwrap();
+            evaluate_with_state(&expr, &current, Some(&previous))
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(state("state:modified"), vec!["model.pkg.b", "model.pkg.e"]);
+        assert_eq!(
+            state("state:modified.body"),
+            vec!["model.pkg.b", "model.pkg.e"]
+        );
+        assert_eq!(state("state:new"), vec!["model.pkg.e"]);
+        assert_eq!(
+            state("state:old"),
+            vec!["model.pkg.a", "model.pkg.b", "model.pkg.c"]
+        );
+
+        // without a previous state nothing is considered modified
+        assert!(select("state:modified", &current).is_empty());
+    }
+
+    #[test]
+    fn test_and_short_circuits_on_empty_intersection() {
+        let all = nodes();
+        let missing = parse_model_specifiers(&["tag:missing".to_string()]).unwrap();
+        let nightly = parse_model_specifiers(&["tag:nightly".to_string()]).unwrap();
+
+        let expr = SelectExpression::And(vec![
+            missing.clone(),
+            nightly.clone(),
+            SelectExpression::Exclude(Box::new(nightly.clone())),
+        ]);
+        assert!(evaluate_select_expression(&expr, &all).is_empty());
+
+        // an Exclude arm narrows but never widens the intersection
+        let expr =
+            SelectExpression::And(vec![nightly, SelectExpression::Exclude(Box::new(missing))]);
+        assert_eq!(
+            evaluate_select_expression(&expr, &all)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["model.pkg.a"]
+        );
+    }
+
+    #[test]
+    fn test_group_selector() {
+        let mut nodes = nodes();
+        nodes[0].group = Some("finance".to_string());
+        nodes[1].group = Some("finance.payments".to_string());
+        nodes[2].group = Some("marketing".to_string());
+
+        assert_eq!(select("group:finance", &nodes), vec!["model.pkg.a"]);
+        assert_eq!(select("group:finance.*", &nodes), vec!["model.pkg.b"]);
+        assert_eq!(
+            select("group:finance*", &nodes),
+            vec!["model.pkg.a", "model.pkg.b"]
+        );
+        // nodes without a group never match
+        assert!(
+            select("group:*", &nodes)
+                .iter()
+                .all(|id| id != "model.pkg.d")
+        );
+    }
+
+    #[test]
+    fn test_source_selector() {
+        let mut orders = resource("source", "orders");
+        orders.fqn = vec!["pkg", "jaffle_shop", "orders"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let mut payments = orders.clone();
+        payments.unique_id = "source.pkg.jaffle_shop.payments".to_string();
+        payments.fqn[2] = "payments".to_string();
+        let nodes = vec![model("orders", &[]), orders, payments];
+
+        let orders_id = vec!["source.pkg.orders"];
+        assert_eq!(select("source:jaffle_shop.orders", &nodes), orders_id);
+        assert_eq!(select("source.jaffle_shop:orders", &nodes), orders_id);
+        assert_eq!(select("source:pkg.jaffle_shop.orders", &nodes), orders_id);
+        assert_eq!(select("source:jaffle_shop", &nodes).len(), 2);
+        assert_eq!(select("source:jaffle_shop.*", &nodes).len(), 2);
+        assert!(select("source:other_pkg.jaffle_shop.orders", &nodes).is_empty());
+        assert!(select("source:a.b.c.d", &nodes).is_empty());
+    }
+
+    #[test]
+    fn test_source_status_selector() {
+        let mut nodes = vec![
+            resource("source", "orders"),
+            resource("source", "payments"),
+            resource("source", "customers"),
+            model("orders", &[]),
+        ];
+        nodes[0].source_status = Some(SourceStatusKind::Fresher);
+        nodes[1].source_status = Some(SourceStatusKind::Warn);
+
+        assert_eq!(
+            select("source_status:fresher", &nodes),
+            vec!["source.pkg.orders"]
+        );
+        assert_eq!(
+            select("source_status:warn", &nodes),
+            vec!["source.pkg.payments"]
+        );
+        assert!(select("source_status:error", &nodes).is_empty());
+        // unknown statuses match nothing, not even unchecked sources
+        assert!(select("source_status:stale", &nodes).is_empty());
+    }
+
+    #[test]
+    fn test_node_id_selector() {
+        let nodes = nodes();
+        assert_eq!(select("node_id:model.pkg.b", &nodes), vec!["model.pkg.b"]);
+        // unique ids are compared exactly, without globs or name matching
+        assert!(select("node_id:model.pkg.*", &nodes).is_empty());
+        assert!(select("node_id:b", &nodes).is_empty());
+    }
+
+    #[test]
+    fn test_wildcard_selects_every_node() {
+        let mut nodes = nodes();
+        nodes.push(resource("metric", "revenue"));
+
+        assert_eq!(select("all:*", &nodes).len(), nodes.len());
+        assert_eq!(select("*:anything", &nodes).len(), nodes.len());
+        // the value is ignored
+        let anything = SelectExpression::Atom(SelectionCriteria::with_method(
+            MethodName::Wildcard,
+            "anything",
+        ));
+        assert_eq!(
+            evaluate_select_expression(&anything, &nodes).len(),
+            nodes.len()
+        );
+        assert_eq!(
+            select("all:*,tag:nightly", &nodes),
+            select("tag:nightly", &nodes)
+        );
+        assert!(select("all:*", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_wildcard_and_exclude() {
+        let nodes = nodes();
+        let all = SelectExpression::Atom(SelectionCriteria::with_method(MethodName::Wildcard, "*"));
+        let nightly = parse_model_specifiers(&["tag:nightly".to_string()]).unwrap();
+        let exclude_only = SelectExpression::And(vec![
+            all.clone(),
+            SelectExpression::Exclude(Box::new(nightly.clone())),
+        ]);
+        assert_eq!(
+            evaluate_select_expression(&exclude_only, &nodes),
+            negate(&nightly, &nodes)
+        );
+        assert_eq!(
+            evaluate_select_expression(&SelectExpression::And(vec![all.clone(), all]), &nodes)
+                .len(),
+            nodes.len()
+        );
+
+        // a wildcard with an exclude of its own still filters
+        let mut excluding = SelectionCriteria::with_method(MethodName::Wildcard, "*");
+        excluding.exclude = Some(Box::new(nightly.clone()));
+        let expr = SelectExpression::And(vec![SelectExpression::Atom(excluding)]);
+        assert_eq!(
+            evaluate_select_expression(&expr, &nodes),
+            negate(&nightly, &nodes)
+        );
+    }
+
+    #[test]
+    fn test_empty_and_selects_nothing() {
+        let nodes = nodes();
+        assert!(evaluate_select_expression(&SelectExpression::And(vec![]), &nodes).is_empty());
+        for strategy in [EvalStrategy::Declared, EvalStrategy::CheapFirst] {
+            let evaluator = GraphAwareEvaluator::new(&nodes, []).with_strategy(strategy);
+            assert!(
+                evaluator
+                    .evaluate(&SelectExpression::And(vec![]))
+                    .is_empty()
+            );
+        }
+    }
+
+    #[test]
+    fn test_evaluate_with_metrics() {
+        let nodes = nodes();
+        let expr =
+            parse_model_specifiers(&["tag:nightly,fqn:*".to_string(), "tag:missing".to_string()])
+                .unwrap();
+        let expr = SelectExpression::Or(vec![
+            expr,
+            SelectExpression::Exclude(Box::new(SelectExpression::Empty)),
+        ]);
+
+        let mut collector = InMemoryCollector::new();
+        let selected = evaluate_with_metrics(&expr, &nodes, &mut collector);
+        assert_eq!(selected, evaluate_select_expression(&expr, &nodes));
+
+        let atoms = collector
+            .atoms
+            .iter()
+            .map(|metrics| (metrics.criteria.to_string(), metrics.matched_count))
+            .collect::<Vec<_>>();
+        let nightly = select("tag:nightly", &nodes).len();
+        assert_eq!(
+            atoms,
+            vec![
+                ("tag:nightly".to_string(), nightly),
+                ("fqn:*".to_string(), nodes.len()),
+                ("tag:missing".to_string(), 0),
+            ]
+        );
+        let composites = collector
+            .composites
+            .iter()
+            .map(|metrics| (metrics.variant.as_str(), metrics.result_count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            composites,
+            vec![
+                ("And", nightly),
+                ("Or", nightly),
+                ("Empty", 0),
+                ("Exclude", nodes.len()),
+                ("Or", nodes.len()),
+            ]
+        );
+        // a parent's time includes its operands'
+        let root = collector.composites.last().unwrap().duration;
+        assert!(
+            collector
+                .atoms
+                .iter()
+                .all(|metrics| metrics.duration <= root)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_with_metrics_skips_after_empty_intersection() {
+        let nodes = nodes();
+        let expr = parse_model_specifiers(&["tag:missing,tag:nightly".to_string()]).unwrap();
+        let mut collector = InMemoryCollector::new();
+        assert!(evaluate_with_metrics(&expr, &nodes, &mut collector).is_empty());
+        assert_eq!(collector.atoms.len(), 1);
+        assert_eq!(collector.atoms[0].criteria.value, "missing");
+        assert_eq!(collector.composites.len(), 1);
+    }
+
+    #[test]
+    fn test_evaluate_or_with_provenance() {
+        let nodes = nodes();
+        let parse = |selector: &str| parse_model_specifiers(&[selector.to_string()]).unwrap();
+        // `a` matches both `tag:nightly*` and `fqn:a`, `b` only the first
+        let exprs = [
+            parse("fqn:a"),
+            parse("tag:nightly*"),
+            parse("tag:missing"),
+            parse("fqn:*"),
+        ];
+
+        let claimed = evaluate_or_with_provenance(&exprs, &nodes);
+        assert_eq!(
+            claimed
+                .iter()
+                .map(|(id, index)| (id.as_str(), *index))
+                .collect::<Vec<_>>(),
+            vec![
+                ("model.pkg.a", 0),
+                ("model.pkg.b", 1),
+                ("model.pkg.c", 3),
+                ("model.pkg.d", 3),
+            ]
+        );
+        assert_eq!(
+            claimed.keys().cloned().collect::<BTreeSet<_>>(),
+            evaluate_select_expression(&SelectExpression::Or(exprs.to_vec()), &nodes)
+        );
+
+        // swapping the two expressions that match `a` swaps its claimant
+        let claimed = evaluate_or_with_provenance(&[exprs[1].clone(), exprs[0].clone()], &nodes);
+        assert_eq!(claimed["model.pkg.a"], 0);
+        assert_eq!(claimed["model.pkg.b"], 0);
+        assert!(evaluate_or_with_provenance(&[], &nodes).is_empty());
+    }
+
+    #[test]
+    fn test_negate() {
+        let all = nodes();
+        let tagged = parse_model_specifiers(&["tag:nightly".to_string()]).unwrap();
+        let expr = SelectExpression::And(vec![tagged]);
+
+        assert_eq!(
+            negate(&expr, &all).into_iter().collect::<Vec<_>>(),
+            vec!["model.pkg.b", "model.pkg.c", "model.pkg.d"]
+        );
+        assert_eq!(
+            evaluate_select_expression(&SelectExpression::Not(Box::new(expr.clone())), &all),
+            negate(&expr, &all)
+        );
+        assert_eq!(negate(&SelectExpression::Empty, &all).len(), all.len());
+    }
+
+    #[test]
+    fn test_evaluate_incremental_partial_hits() {
+        let all = nodes();
+        let expr = |selectors: &[&str]| {
+            parse_model_specifiers(&selectors.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+                .unwrap()
+        };
+        let mut cache = EvalCache::new();
+
+        let first = expr(&["tag:nightly", "tag:hourly"]);
+        assert_eq!(
+            evaluate_incremental(&first, &all, &mut cache),
+            evaluate_select_expression(&first, &all)
+        );
+        assert_eq!((cache.hits(), cache.misses()), (0, 3));
+
+        // unchanged expression: answered by the root entry alone
+        evaluate_incremental(&first, &all, &mut cache);
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+
+        // one child changed: the parent misses, the unchanged child hits
+        let second = expr(&["tag:nightly", "tag:tag1"]);
+        assert_eq!(
+            evaluate_incremental(&second, &all, &mut cache),
+            evaluate_select_expression(&second, &all)
+        );
+        assert_eq!((cache.hits(), cache.misses()), (2, 5));
+        assert_eq!(cache.len(), 5);
+    }
+
+    #[test]
+    fn test_evaluate_incremental_invalidates_on_node_change() {
+        let mut all = nodes();
+        let expr = parse_model_specifiers(&["tag:nightly".to_string()]).unwrap();
+        let mut cache = EvalCache::new();
+
+        evaluate_incremental(&expr, &all, &mut cache);
+        all[1].tags.push("nightly".to_string());
+        let selected = evaluate_incremental(&expr, &all, &mut cache);
+
+        assert_eq!(
+            selected.into_iter().collect::<Vec<_>>(),
+            vec!["model.pkg.a", "model.pkg.b"]
+        );
+        assert_eq!((cache.hits(), cache.misses()), (0, 2));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_semantic_model_and_saved_query_selectors() {
+        let mut orders = model("orders", &[]);
+        orders.semantic_model = Some("orders_by_status".to_string());
+        let nodes = vec![
+            orders,
+            model("customers", &[]),
+            resource("semantic_model", "orders_by_status"),
+            resource("saved_query", "weekly_orders"),
+        ];
+        assert_eq!(
+            select("semantic_model:orders_by_status", &nodes),
+            vec!["model.pkg.orders", "semantic_model.pkg.orders_by_status"]
+        );
+        assert_eq!(
+            select("saved_query:weekly_*", &nodes),
+            vec!["saved_query.pkg.weekly_orders"]
+        );
+        assert!(select("semantic_model:weekly_orders", &nodes).is_empty());
+    }
+
+    #[test]
+    fn test_unit_test_selector() {
+        let mut test_orders = resource("unit_test", "test_orders_totals");
+        test_orders.attached_node = Some("orders".to_string());
+        let mut test_customers = resource("unit_test", "test_customer_names");
+        test_customers.attached_node = Some("customers".to_string());
+        let nodes = vec![model("orders", &[]), test_orders, test_customers];
+
+        assert_eq!(
+            select("unit_test:orders", &nodes),
+            vec!["unit_test.pkg.test_orders_totals"]
+        );
+        assert_eq!(
+            select("unit_test:test_customer_names", &nodes),
+            vec!["unit_test.pkg.test_customer_names"]
+        );
+        assert_eq!(
+            select("unit_test:*", &nodes),
+            vec![
+                "unit_test.pkg.test_customer_names",
+                "unit_test.pkg.test_orders_totals"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_selector() {
+        let versioned = |version| Node {
+            unique_id: format!("model.pkg.orders.v{version}"),
+            version: Some(version),
+            latest_version: Some(2),
+            ..model("orders", &[])
+        };
+        let nodes = vec![
+            versioned(1),
+            versioned(2),
+            versioned(3),
+            model("customers", &[]),
+        ];
+
+        assert_eq!(select("version:1", &nodes), vec!["model.pkg.orders.v1"]);
+        assert_eq!(
+            select("version:latest", &nodes),
+            vec!["model.pkg.orders.v2"]
+        );
+        assert_eq!(
+            select("version:prerelease", &nodes),
+            vec!["model.pkg.orders.v3"]
+        );
+        assert_eq!(select("version:old", &nodes), vec!["model.pkg.orders.v1"]);
+        assert_eq!(select("version:none", &nodes), vec!["model.pkg.customers"]);
+        assert!(select("version:4", &nodes).is_empty());
+    }
+
+    /// a -> b -> c -> d, plus x -> b.
+    fn graph_nodes() -> (Vec<Node>, Vec<(NodeId, NodeId)>) {
+        models_with_edges(
+            &["a", "b", "c", "d", "x"],
+            &[("a", "b"), ("x", "b"), ("b", "c"), ("c", "d")],
+        )
+    }
+
+    fn models_with_edges(
+        names: &[&str],
+        edges: &[(&str, &str)],
+    ) -> (Vec<Node>, Vec<(NodeId, NodeId)>) {
+        let nodes = names.iter().map(|name| model(name, &[])).collect();
+        let edges = edges
+            .iter()
+            .map(|(parent, child)| (format!("model.pkg.{parent}"), format!("model.pkg.{child}")))
+            .collect();
+        (nodes, edges)
+    }
+
+    fn names(ids: BTreeSet<NodeId>) -> Vec<String> {
+        ids.into_iter()
+            .map(|id| id.trim_start_matches("model.pkg.").to_string())
+            .collect()
+    }
+
+    fn critical_path_of(evaluator: &GraphAwareEvaluator<'_>, selected: &[&str]) -> Vec<String> {
+        let selected = selected
+            .iter()
+            .map(|name| format!("model.pkg.{name}"))
+            .collect();
+        evaluator
+            .critical_path(&selected)
+            .into_iter()
+            .map(|id| id.trim_start_matches("model.pkg.").to_string())
+            .collect()
+    }
+
+    fn topological_sort_of(
+        evaluator: &GraphAwareEvaluator<'_>,
+        selected: &[&str],
+    ) -> FsResult<Vec<String>> {
+        let selected = selected
+            .iter()
+            .map(|name| format!("model.pkg.{name}"))
+            .collect();
+        Ok(evaluator
+            .topological_sort(&selected)?
+            .into_iter()
+            .map(|id| id.trim_start_matches("model.pkg.").to_string())
+            .collect())
+    }
+
+    /// Asserts every edge between nodes of `order` points forward in it.
+    fn assert_dependency_order(order: &[String], edges: &[(&str, &str)]) {
+        let position = |name: &str| order.iter().position(|n| n == name);
+        for (parent, child) in edges {
+            if let (Some(parent_at), Some(child_at)) = (position(parent), position(child)) {
+                assert!(parent_at < child_at, "{parent} after {child} in {order:?}");
+            }
+        }
+    }
+
+    fn select_in_graph(selector: &str) -> Vec<String> {
+        let (nodes, edges) = graph_nodes();
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges);
+        let expr = parse_model_specifiers(&[selector.to_string()]).unwrap();
+        evaluator
+            .evaluate(&expr)
+            .into_iter()
+            .map(|id| id.trim_start_matches("model.pkg.").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_graph_depth_zero() {
+        assert_eq!(select_in_graph("0+c"), vec!["c"]);
+        assert_eq!(select_in_graph("c+0"), vec!["c"]);
+    }
+
+    #[test]
+    fn test_graph_depth_one() {
+        assert_eq!(select_in_graph("1+c"), vec!["b", "c"]);
+        assert_eq!(select_in_graph("b+1"), vec!["b", "c"]);
+        assert_eq!(select_in_graph("1+b+1"), vec!["a", "b", "c", "x"]);
+    }
+
+    #[test]
+    fn test_graph_unbounded_depth() {
+        assert_eq!(select_in_graph("+c"), vec!["a", "b", "c", "x"]);
+        assert_eq!(select_in_graph("a+"), vec!["a", "b", "c", "d"]);
+        // without graph operators only the atom itself is selected
+        assert_eq!(select_in_graph("c"), vec!["c"]);
+    }
+
+    #[test]
+    fn test_graph_childrens_parents() {
+        assert_eq!(select_in_graph("@a"), vec!["a", "b", "c", "d", "x"]);
+        assert_eq!(select_in_graph("@c"), vec!["a", "b", "c", "d", "x"]);
+        assert_eq!(select_in_graph("@d"), vec!["a", "b", "c", "d", "x"]);
+    }
+
+    #[test]
+    fn test_ancestors_and_descendants() {
+        //   a   b
+        //    \ / \
+        //     c   d
+        //     |\  |
+        //     e f g
+        //      \|/
+        //       h
+        //       |
+        //       i     j (isolated)
+        let (nodes, edges) = models_with_edges(
+            &["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"],
+            &[
+                ("a", "c"),
+                ("b", "c"),
+                ("b", "d"),
+                ("c", "e"),
+                ("c", "f"),
+                ("d", "g"),
+                ("f", "h"),
+                ("g", "h"),
+                ("h", "i"),
+            ],
+        );
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges);
+        let id = |name: &str| format!("model.pkg.{name}");
+
+        assert!(names(evaluator.ancestors(&id("h"), 0)).is_empty());
+        assert_eq!(names(evaluator.ancestors(&id("h"), 1)), vec!["f", "g"]);
+        assert_eq!(
+            names(evaluator.ancestors(&id("h"), 2)),
+            vec!["c", "d", "f", "g"]
+        );
+        assert_eq!(
+            names(evaluator.ancestors(&id("i"), u32::MAX)),
+            vec!["a", "b", "c", "d", "f", "g", "h"]
+        );
+        assert!(names(evaluator.ancestors(&id("a"), u32::MAX)).is_empty());
+
+        assert_eq!(names(evaluator.descendants(&id("b"), 1)), vec!["c", "d"]);
+        assert_eq!(
+            names(evaluator.descendants(&id("b"), 2)),
+            vec!["c", "d", "e", "f", "g"]
+        );
+        assert_eq!(
+            names(evaluator.descendants(&id("b"), u32::MAX)),
+            vec!["c", "d", "e", "f", "g", "h", "i"]
+        );
+        assert!(names(evaluator.descendants(&id("i"), u32::MAX)).is_empty());
+
+        assert!(evaluator.ancestors(&id("j"), u32::MAX).is_empty());
+        assert!(evaluator.descendants(&id("j"), u32::MAX).is_empty());
+        assert!(evaluator.descendants(&id("missing"), u32::MAX).is_empty());
+
+        // consistent with the graph operators
+        let expr = parse_model_specifiers(&["c+2".to_string()]).unwrap();
+        let mut expected = evaluator.descendants(&id("c"), 2);
+        expected.insert(id("c"));
+        assert_eq!(evaluator.evaluate(&expr), expected);
+    }
+
+    #[test]
+    fn test_topological_sort_chain() -> FsResult<()> {
+        let (nodes, edges) = graph_nodes();
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges);
+        // `d` depends on `c`, which depends on `b`, which depends on `a`
+        assert_eq!(
+            topological_sort_of(&evaluator, &["d", "b", "c", "a"])?,
+            vec!["a", "b", "c", "d"]
+        );
+        // unselected nodes, and unknown ids, are left out
+        assert_eq!(
+            topological_sort_of(&evaluator, &["d", "c", "missing"])?,
+            vec!["c", "d"]
+        );
+        assert!(topological_sort_of(&evaluator, &[])?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_topological_sort_diamond() -> FsResult<()> {
+        let edges = [("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("d", "e")];
+        let (nodes, graph_edges) = models_with_edges(&["a", "b", "c", "d", "e"], &edges);
+        let evaluator = GraphAwareEvaluator::new(&nodes, graph_edges);
+
+        let order = topological_sort_of(&evaluator, &["e", "d", "c", "b", "a"])?;
+        assert_eq!(order.len(), 5);
+        assert_eq!(order.first().map(String::as_str), Some("a"));
+        assert_eq!(order.last().map(String::as_str), Some("e"));
+        assert_dependency_order(&order, &edges);
+        Ok(())
+    }
+
+    #[test]
+    fn test_topological_sort_cycle() -> FsResult<()> {
+        // `a -> b -> c` plus the reverse edge `c -> a` closes a cycle
+        let edges = [("a", "b"), ("b", "c"), ("c", "a"), ("c", "d")];
+        let (nodes, graph_edges) = models_with_edges(&["a", "b", "c", "d"], &edges);
+        let evaluator = GraphAwareEvaluator::new(&nodes, graph_edges);
+
+        let err = topological_sort_of(&evaluator, &["a", "b", "c", "d"]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert!(err.to_string().contains("cycle"), "{err}");
+
+        // leaving a node of the cycle unselected breaks it
+        let order = topological_sort_of(&evaluator, &["a", "c", "d"])?;
+        assert_eq!(order.len(), 3);
+        assert_eq!(order[0], "c");
+        assert_dependency_order(&order, &edges);
+        Ok(())
+    }
+
+    #[test]
+    fn test_critical_path_linear_chain() {
+        let (nodes, edges) = graph_nodes();
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges);
+        assert_eq!(
+            critical_path_of(&evaluator, &["a", "b", "c", "d", "x"]),
+            vec!["a", "b", "c", "d"]
+        );
+        // the chain is cut where a node is not selected
+        assert_eq!(
+            critical_path_of(&evaluator, &["a", "b", "d", "x"]),
+            vec!["a", "b"]
+        );
+        assert!(critical_path_of(&evaluator, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_critical_path_diamond() {
+        // a -> b -> d, a -> c -> d, d -> e
+        let (nodes, edges) = models_with_edges(
+            &["a", "b", "c", "d", "e"],
+            &[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("d", "e")],
+        );
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges);
+        assert_eq!(
+            critical_path_of(&evaluator, &["a", "b", "c", "d", "e"]),
+            vec!["a", "b", "d", "e"]
+        );
+        assert_eq!(
+            critical_path_of(&evaluator, &["a", "c", "d"]),
+            vec!["a", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn test_critical_path_ignores_unselected_cycle() {
+        // a -> b -> c, with x <-> y in a cycle hanging off b
+        let (nodes, edges) = models_with_edges(
+            &["a", "b", "c", "x", "y"],
+            &[("a", "b"), ("b", "c"), ("b", "x"), ("x", "y"), ("y", "x")],
+        );
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges);
+        assert_eq!(
+            critical_path_of(&evaluator, &["a", "b", "c"]),
+            vec!["a", "b", "c"]
+        );
+        // a cycle among the selected nodes has no longest chain
+        assert!(critical_path_of(&evaluator, &["a", "b", "x", "y"]).is_empty());
+    }
+
+    #[test]
+    fn test_graph_expansion_filter_skips_tests() {
+        // stg -> orders -> report, with a test on stg and on orders, and a
+        // model only reachable through a test
+        let (mut nodes, mut edges) = models_with_edges(
+            &["stg", "orders", "report", "behind_test"],
+            &[("stg", "orders"), ("orders", "report")],
+        );
+        nodes.push(resource("test", "not_null_stg"));
+        nodes.push(resource("test", "unique_orders"));
+        for (parent, child) in [
+            ("model.pkg.stg", "test.pkg.not_null_stg"),
+            ("model.pkg.orders", "test.pkg.unique_orders"),
+            ("test.pkg.unique_orders", "model.pkg.behind_test"),
+        ] {
+            edges.push((parent.to_string(), child.to_string()));
+        }
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges);
+        let expr = parse_model_specifiers(&["stg+".to_string()]).unwrap();
+
+        assert_eq!(
+            evaluator.evaluate(&expr).into_iter().collect::<Vec<_>>(),
+            vec![
+                "model.pkg.behind_test",
+                "model.pkg.orders",
+                "model.pkg.report",
+                "model.pkg.stg",
+                "test.pkg.not_null_stg",
+                "test.pkg.unique_orders",
+            ]
+        );
+        let models_only = ["model".to_string()];
+        assert_eq!(
+            evaluator
+                .evaluate_with_expansion_filter(&expr, Some(&models_only))
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["model.pkg.orders", "model.pkg.report", "model.pkg.stg"]
+        );
+
+        // a node matched by the atom itself is kept whatever its type
+        let expr = parse_model_specifiers(&["+resource_type:test".to_string()]).unwrap();
+        assert_eq!(
+            evaluator
+                .evaluate_with_expansion_filter(&expr, Some(&models_only))
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![
+                "model.pkg.orders",
+                "model.pkg.stg",
+                "test.pkg.not_null_stg",
+                "test.pkg.unique_orders",
+            ]
+        );
+    }
+
+    /// Matches every node, counting how often it is asked.
+    struct CountingMethod;
+
+    static COUNTING_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    impl SelectorMethod for CountingMethod {
+        fn matches(&self, _node: &NodeMeta<'_>, _value: &str, _args: &[String]) -> bool {
+            COUNTING_CALLS.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+    }
+
+    #[test]
+    fn test_cheap_first_strategy() {
+        register_method("counting", CountingMethod).unwrap();
+        let (nodes, edges) = graph_nodes();
+        let declared = GraphAwareEvaluator::new(&nodes, edges.clone());
+        let cheap_first =
+            GraphAwareEvaluator::new(&nodes, edges).with_strategy(EvalStrategy::CheapFirst);
+        let expr = |selectors: &[&str]| {
+            parse_model_specifiers(&selectors.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+                .unwrap()
+        };
+
+        // the nested union is evaluated first as written, last otherwise
+        let pruned = SelectExpression::And(vec![
+            expr(&["counting:any", "a+"]),
+            expr(&["tag:does_not_exist"]),
+        ]);
+        COUNTING_CALLS.store(0, Ordering::SeqCst);
+        assert!(declared.evaluate(&pruned).is_empty());
+        assert_eq!(COUNTING_CALLS.load(Ordering::SeqCst), nodes.len());
+        COUNTING_CALLS.store(0, Ordering::SeqCst);
+        assert!(cheap_first.evaluate(&pruned).is_empty());
+        assert_eq!(COUNTING_CALLS.load(Ordering::SeqCst), 0);
+
+        // the order never changes the result
+        for selected in [
+            SelectExpression::And(vec![expr(&["a+", "x"]), expr(&["+c"])]),
+            SelectExpression::And(vec![
+                SelectExpression::Exclude(Box::new(expr(&["b"]))),
+                expr(&["@d"]),
+                SelectExpression::Or(vec![expr(&["c+"]), expr(&["x"])]),
+            ]),
+            SelectExpression::And(vec![expr(&["a+"]), SelectExpression::Empty]),
+        ] {
+            assert_eq!(
+                declared.evaluate(&selected),
+                cheap_first.evaluate(&selected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_graph_childrens_parents_excludes_unrelated_nodes() {
+        // a -> b -> c, p -> c, a -> sibling, u -> v
+        let (nodes, edges) = models_with_edges(
+            &["a", "b", "c", "p", "sibling", "u", "v"],
+            &[
+                ("a", "b"),
+                ("b", "c"),
+                ("p", "c"),
+                ("a", "sibling"),
+                ("u", "v"),
+            ],
+        );
+        let evaluator = GraphAwareEvaluator::new(&nodes, edges);
+        let expr = parse_model_specifiers(&["@b".to_string()]).unwrap();
+        let selected = evaluator
+            .evaluate(&expr)
+            .into_iter()
+            .map(|id| id.trim_start_matches("model.pkg.").to_string())
+            .collect::<Vec<_>>();
+        // the children of b, and every parent of those; not the other
+        // children of b's parents, nor the disconnected component
+        assert_eq!(selected, vec!["a", "b", "c", "p"]);
+    }
+}
@@ -0,0 +1,70 @@
+//! Structured explanation of how a named selector resolves, produced by
+//! [`SelectorParser::explain`](crate::SelectorParser::explain).
+//
+
+use dbt_common::node_selector::SelectExpression;
+use serde::Serialize;
+
+/// Position of a piece of a selector definition in its YAML source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<&dbt_yaml::Span> for SourceLocation {
+    fn from(span: &dbt_yaml::Span) -> Self {
+        Self {
+            line: span.start.line,
+            column: span.start.column,
+        }
+    }
+}
+
+/// The kind of definition node an explanation step corresponds to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "name", rename_all = "snake_case")]
+pub enum ExplanationStep {
+    /// A named selector, either the one being explained or one reached
+    /// through `selector:` inheritance.
+    Selector(String),
+    Union,
+    Intersection,
+    Difference,
+    Exclude,
+    /// A method atom or CLI-style selector string.
+    Atom,
+}
+
+/// One node of a selector definition together with the expression it
+/// resolved to. The tree mirrors the YAML definition rather than the
+/// resolved `SelectExpression`, so every step can be traced back to source.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SelectorExplanation {
+    pub step: ExplanationStep,
+    pub expression: SelectExpression,
+    /// `None` when the parser was not built from YAML text.
+    pub location: Option<SourceLocation>,
+    pub children: Vec<SelectorExplanation>,
+}
+
+impl SelectorExplanation {
+    /// Names of the selectors reached through `selector:` inheritance,
+    /// in depth-first order. The explained selector itself is not included.
+    pub fn inherited_selectors(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for child in &self.children {
+            child.collect_selectors(&mut names);
+        }
+        names
+    }
+
+    fn collect_selectors<'a>(&'a self, names: &mut Vec<&'a str>) {
+        if let ExplanationStep::Selector(name) = &self.step {
+            names.push(name);
+        }
+        for child in &self.children {
+            child.collect_selectors(names);
+        }
+    }
+}
@@ -1,3 +1,9 @@
+pub mod cache;
+pub mod evaluator;
+pub mod explain;
 mod parser;
 pub use dbt_schemas::schemas::selectors::ResolvedSelector;
-pub use parser::SelectorParser;
+pub use parser::{
+    CLI_OVERRIDE_SELECTOR, DEFAULT_MAX_SELECTOR_DEPTH, ParseContext, ParsedSelector,
+    SELECTOR_ENV_PREFIX, SelectorDiff, SelectorParser, SelectorParserBuilder, SelectorParserSeed,
+};
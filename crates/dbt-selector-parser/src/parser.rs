@@ -3,72 +3,1031 @@
 //! scheduler understands.
 //
 
-use std::{collections::BTreeMap, slice, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    path::Path,
+    slice,
+    sync::LazyLock,
+};
 
 use dbt_common::{
-    ErrorCode, FsResult, err, fs_err,
+    ErrorCode, FsError, FsResult, SelectorErrorKind,
+    constants::DBT_SELECTORS_YML,
+    err, fs_err,
     io_args::IoArgs,
     node_selector::{
-        IndirectSelection, MethodName, SelectExpression, SelectionCriteria, parse_model_specifiers,
+        IndirectSelection, MethodName, SelectExpression, SelectionCriteria, difference,
+        is_custom_method, parse_method_qualifier, parse_model_specifiers, parse_single_selector,
     },
+    stdfs,
     tracing::emit::emit_warn_log_message,
 };
 
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer, de::DeserializeSeed, ser::SerializeStruct,
+};
+
+use crate::evaluator::{Node, TestNode, evaluate_select_expression};
+use crate::explain::{ExplanationStep, SelectorExplanation, SourceLocation};
+
 use dbt_schemas::schemas::selectors::{
-    AtomExpr, CompositeExpr, CompositeKind, MethodAtomExpr, SelectorDefinition,
-    SelectorDefinitionValue, SelectorExpr,
+    AtomExpr, CompositeExpr, CompositeKind, ExcludeAtomExpr, MethodAtomExpr, SelectorDefinition,
+    SelectorDefinitionValue, SelectorExpr, SelectorFile, SelectorValue,
 };
 
+/// Default limit on how deeply `selector:` references may nest before
+/// resolution is aborted.
+pub const DEFAULT_MAX_SELECTOR_DEPTH: usize = 32;
+
+/// Prefix of the environment variables read by
+/// [`SelectorParser::augment_from_env`]: `DBT_SELECTOR_NIGHTLY` defines the
+/// selector `nightly`.
+pub const SELECTOR_ENV_PREFIX: &str = "DBT_SELECTOR_";
+
+/// Name of the selector added by [`SelectorParser::with_cli_override`].
+pub const CLI_OVERRIDE_SELECTOR: &str = "__cli_override__";
+
+/// `IoArgs` used by [`SelectorParser::new_standalone`]: no status reporter,
+/// no log or telemetry files, so warnings only go to the default stdout
+/// logger.
+static STANDALONE_IO_ARGS: LazyLock<IoArgs> = LazyLock::new(IoArgs::default);
+
+/// Prefix on an atom's method that negates it, e.g. `method: "not:tag"`.
+const NOT_PREFIX: &str = "not:";
+
+/// A resolved named selector together with the metadata of its definition,
+/// e.g. for tooling that shows selector docs on hover.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedSelector {
+    /// The name the selector is defined under.
+    pub name: String,
+    pub description: Option<String>,
+    pub expression: SelectExpression,
+}
+
+/// The nodes a selector gains and loses between two versions of the
+/// selector definitions; see [`SelectorParser::diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorDiff<'n> {
+    /// Nodes selected after the change but not before, in input order.
+    pub added: Vec<&'n TestNode>,
+    /// Nodes selected before the change but not after, in input order.
+    pub removed: Vec<&'n TestNode>,
+}
+
+impl SelectorDiff<'_> {
+    /// True if the change does not alter which nodes are selected.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Resolution state threaded through a single parse.
+///
+/// `chain` holds the named selectors currently being resolved, outermost
+/// first; its length is the current inheritance depth.
+#[derive(Debug, Clone, Default)]
+pub struct ParseContext {
+    pub chain: Vec<String>,
+    /// Set by [`SelectorParser::parse_definition_traced`] to open a span for
+    /// every parse step.
+    traced: bool,
+}
+
+impl ParseContext {
+    pub fn depth(&self) -> usize {
+        self.chain.len()
+    }
+
+    /// Enter a `TRACE` span for one parse step, if this parse is traced.
+    fn trace_step(
+        &self,
+        expression_kind: &str,
+        method: &str,
+    ) -> Option<tracing::span::EnteredSpan> {
+        self.traced.then(|| {
+            tracing::trace_span!(
+                "parse_selector",
+                method = %method,
+                depth = self.depth(),
+                expression_kind = %expression_kind
+            )
+            .entered()
+        })
+    }
+
+    /// Where in the selector definitions parsing currently is, for error
+    /// messages: ` in selector `outer -> inner``, or nothing outside any
+    /// named selector.
+    fn describe_position(&self) -> String {
+        if self.chain.is_empty() {
+            String::new()
+        } else {
+            format!(" in selector `{}`", self.chain.join(" -> "))
+        }
+    }
+
+    fn describe_chain(&self, next: &str) -> String {
+        self.chain
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(next))
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SelectorParser<'a> {
     defs: BTreeMap<String, SelectorDefinition>,
     io_args: &'a IoArgs,
+    max_depth: usize,
+    /// Raw YAML of each definition, kept to report source locations.
+    sources: BTreeMap<String, dbt_yaml::Value>,
+    /// Warn when a selector evaluated through the parser selects no nodes.
+    warn_on_empty_result: bool,
+    /// The `version` of the `selectors.yml` the definitions were read from,
+    /// if it is a whole number.
+    schema_version: Option<u32>,
+}
+
+impl SelectorParser<'static> {
+    /// Build a parser without an `IoArgs`, for tools that embed the selector
+    /// parser outside of a dbt invocation.
+    pub fn new_standalone(defs: BTreeMap<String, SelectorDefinition>) -> Self {
+        Self::new(defs, &STANDALONE_IO_ARGS)
+    }
+
+    /// Start building a parser one definition at a time; see
+    /// [`SelectorParserBuilder`].
+    pub fn builder() -> SelectorParserBuilder<'static> {
+        SelectorParserBuilder::default()
+    }
+}
+
+/// Builds a [`SelectorParser`] from definitions added one at a time,
+/// validating them as a whole in [`build`](Self::build). Without
+/// [`with_io_args`](Self::with_io_args), the parser is standalone as from
+/// [`SelectorParser::new_standalone`].
+#[derive(Debug, Clone)]
+pub struct SelectorParserBuilder<'a> {
+    defs: Vec<SelectorDefinition>,
+    io_args: &'a IoArgs,
+    max_depth: usize,
+    warn_on_empty_result: bool,
+}
+
+impl Default for SelectorParserBuilder<'_> {
+    fn default() -> Self {
+        Self {
+            defs: Vec::new(),
+            io_args: &STANDALONE_IO_ARGS,
+            max_depth: DEFAULT_MAX_SELECTOR_DEPTH,
+            warn_on_empty_result: false,
+        }
+    }
+}
+
+impl<'a> SelectorParserBuilder<'a> {
+    pub fn add_definition(mut self, def: SelectorDefinition) -> Self {
+        self.defs.push(def);
+        self
+    }
+
+    /// Borrow `io_args` for the warnings the parser emits.
+    pub fn with_io_args<'b>(self, io_args: &'b IoArgs) -> SelectorParserBuilder<'b> {
+        SelectorParserBuilder {
+            defs: self.defs,
+            io_args,
+            max_depth: self.max_depth,
+            warn_on_empty_result: self.warn_on_empty_result,
+        }
+    }
+
+    /// See [`SelectorParser::with_warn_on_empty_result`].
+    pub fn warn_on_empty(mut self, warn_on_empty_result: bool) -> Self {
+        self.warn_on_empty_result = warn_on_empty_result;
+        self
+    }
+
+    /// See [`SelectorParser::with_max_depth`].
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Fails if two definitions share a name, or more than one is marked
+    /// `default: true`.
+    pub fn build(self) -> FsResult<SelectorParser<'a>> {
+        let defaults = self
+            .defs
+            .iter()
+            .filter(|def| def.default == Some(true))
+            .map(|def| def.name.as_str())
+            .collect::<Vec<_>>();
+        if defaults.len() > 1 {
+            return err!(
+                ErrorCode::SelectorError,
+                "Multiple selectors have `default: true`: {}",
+                defaults.join(", ")
+            );
+        }
+
+        let mut defs = BTreeMap::new();
+        for def in self.defs {
+            if defs.contains_key(&def.name) {
+                return err!(
+                    ErrorCode::SelectorError,
+                    "Selector `{}` is defined more than once",
+                    def.name
+                );
+            }
+            defs.insert(def.name.clone(), def);
+        }
+        Ok(SelectorParser::new(defs, self.io_args)
+            .with_max_depth(self.max_depth)
+            .with_warn_on_empty_result(self.warn_on_empty_result))
+    }
 }
 
 impl<'a> SelectorParser<'a> {
     pub fn new(defs: BTreeMap<String, SelectorDefinition>, io_args: &'a IoArgs) -> Self {
-        Self { defs, io_args }
+        Self {
+            defs,
+            io_args,
+            max_depth: DEFAULT_MAX_SELECTOR_DEPTH,
+            sources: BTreeMap::new(),
+            warn_on_empty_result: false,
+            schema_version: None,
+        }
     }
 
-    pub fn parse_named(&self, name: &str) -> FsResult<SelectExpression> {
-        let def = self
+    /// Build a parser straight from the text of a `selectors.yml` file.
+    pub fn from_yaml_str(yaml: &str, io_args: &'a IoArgs) -> FsResult<Self> {
+        let file: SelectorFile = dbt_yaml::from_str(yaml).map_err(|e| {
+            fs_err!(
+                ErrorCode::SelectorError,
+                "Failed to parse selectors YAML: {}",
+                e
+            )
+        })?;
+        let defs = file
+            .selectors
+            .into_iter()
+            .map(|def| (def.name.clone(), def))
+            .collect();
+        let mut parser = Self::new(defs, io_args);
+        parser.schema_version = file
+            .version
+            .and_then(|version| version.to_string().parse().ok());
+
+        // Re-read the text untyped to keep the span of every definition.
+        if let Ok(raw) = dbt_yaml::from_str::<dbt_yaml::Value>(yaml)
+            && let Some(selectors) = raw.get("selectors").and_then(|v| v.as_sequence())
+        {
+            for selector in selectors {
+                if let (Some(name), Some(definition)) = (
+                    selector.get("name").and_then(|v| v.as_str()),
+                    selector.get("definition"),
+                ) {
+                    parser.sources.insert(name.to_string(), definition.clone());
+                }
+            }
+        }
+        Ok(parser)
+    }
+
+    /// Build a parser from the selector definitions a `manifest.json`
+    /// embeds under its `selectors` key, keyed by selector name. A manifest
+    /// without selectors yields a parser without definitions.
+    pub fn from_manifest_json(manifest_json: &str, io_args: &'a IoArgs) -> FsResult<Self> {
+        let manifest: serde_json::Value = serde_json::from_str(manifest_json).map_err(|e| {
+            fs_err!(
+                ErrorCode::SelectorError,
+                "Failed to parse manifest JSON: {}",
+                e
+            )
+        })?;
+        let defs = match manifest.get("selectors") {
+            None | Some(serde_json::Value::Null) => BTreeMap::new(),
+            Some(selectors) => BTreeMap::<String, SelectorDefinition>::deserialize(selectors)
+                .map_err(|e| {
+                    fs_err!(
+                        ErrorCode::SelectorError,
+                        "Failed to parse the selectors of the manifest: {}",
+                        e
+                    )
+                })?,
+        };
+        Ok(Self::new(defs, io_args))
+    }
+
+    /// Like [`from_yaml_str`](Self::from_yaml_str), but fails unless the
+    /// file declares a `version` of at least `min_version`; see
+    /// [`check_schema_version`](Self::check_schema_version).
+    pub fn from_yaml_str_versioned(
+        yaml: &str,
+        min_version: u32,
+        io_args: &'a IoArgs,
+    ) -> FsResult<Self> {
+        let parser = Self::from_yaml_str(yaml, io_args)?;
+        parser.check_schema_version(min_version)?;
+        Ok(parser)
+    }
+
+    /// The `version` of the `selectors.yml` the definitions were read from.
+    /// `None` if the file has no version, its version is not a whole number,
+    /// or the parser was not built from YAML.
+    pub fn schema_version(&self) -> Option<u32> {
+        self.schema_version
+    }
+
+    /// Fail unless the definitions were read from a `selectors.yml` whose
+    /// `version` is at least `required`. A file without a version fails too,
+    /// since it may predate the required schema.
+    pub fn check_schema_version(&self, required: u32) -> FsResult<()> {
+        match self.schema_version {
+            Some(version) if version >= required => Ok(()),
+            Some(version) => err!(
+                ErrorCode::SelectorError,
+                "{} version {} is older than the required version {}",
+                DBT_SELECTORS_YML,
+                version,
+                required
+            ),
+            None => err!(
+                ErrorCode::SelectorError,
+                "{} does not declare a `version`; version {} or later is required",
+                DBT_SELECTORS_YML,
+                required
+            ),
+        }
+    }
+
+    /// Override the maximum `selector:` inheritance depth.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Emit a warning whenever a selector evaluated through the parser, e.g.
+    /// by [`test_selector`](Self::test_selector), selects no nodes. Such a
+    /// result is usually a typo in a selector value.
+    pub fn with_warn_on_empty_result(mut self, warn_on_empty_result: bool) -> Self {
+        self.warn_on_empty_result = warn_on_empty_result;
+        self
+    }
+
+    /// Combine the definitions of two parsers, e.g. from the `selectors.yml`
+    /// of different packages, so that `selector:` references may cross them.
+    ///
+    /// Fails if both parsers define a selector with the same name.
+    pub fn merge(mut self, other: SelectorParser<'a>) -> FsResult<SelectorParser<'a>> {
+        for (name, def) in other.defs {
+            if self.defs.contains_key(&name) {
+                return err!(
+                    ErrorCode::SelectorError,
+                    "Selector `{}` is defined more than once",
+                    name
+                );
+            }
+            self.defs.insert(name, def);
+        }
+        self.sources.extend(other.sources);
+        Ok(self)
+    }
+
+    /// Add the selectors defined by `DBT_SELECTOR_<NAME>` environment
+    /// variables, e.g. overrides injected by a CI pipeline. Each value is a
+    /// selector string, and `<NAME>` is lowercased to give the selector's
+    /// name. A variable replaces a selector of the same name from YAML, with
+    /// a warning. Returns the number of selectors read from the environment.
+    ///
+    /// Fails without changing the parser if any value is not a valid
+    /// selector. Variables whose name or value is not valid Unicode are
+    /// ignored.
+    pub fn augment_from_env(&mut self) -> FsResult<usize> {
+        let vars = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)));
+        self.augment_from_vars(vars)
+    }
+
+    fn augment_from_vars(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> FsResult<usize> {
+        let mut overrides = vars
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let name = key.strip_prefix(SELECTOR_ENV_PREFIX)?.to_lowercase();
+                (!name.is_empty()).then_some((name, key, value))
+            })
+            .collect::<Vec<_>>();
+        overrides.sort();
+        for (_, key, value) in &overrides {
+            parse_model_specifiers(slice::from_ref(value)).map_err(|e| {
+                fs_err!(
+                    ErrorCode::SelectorError,
+                    "Environment variable `{}` is not a valid selector: {}",
+                    key,
+                    e
+                )
+            })?;
+        }
+
+        let count = overrides.len();
+        for (name, key, value) in overrides {
+            if self.defs.contains_key(&name) {
+                emit_warn_log_message(
+                    ErrorCode::SelectorError,
+                    format!("Selector `{name}` is overridden by environment variable `{key}`"),
+                    self.io_args.status_reporter.as_ref(),
+                );
+            }
+            self.sources.remove(&name);
+            self.defs.insert(
+                name.clone(),
+                SelectorDefinition {
+                    name,
+                    description: None,
+                    default: None,
+                    definition: SelectorDefinitionValue::String(value),
+                },
+            );
+        }
+        Ok(count)
+    }
+
+    /// Build a parser from the `selectors.yml` at the root of a dbt project.
+    /// A project without a `selectors.yml` yields a parser with no selectors.
+    ///
+    /// The file is parsed as plain YAML; Jinja in it is not rendered.
+    pub fn from_file(project_root: &Path, io_args: &'a IoArgs) -> FsResult<Self> {
+        let path = project_root.join(DBT_SELECTORS_YML);
+        if !stdfs::exists(&path)? {
+            return Ok(Self::new(BTreeMap::new(), io_args));
+        }
+        Self::from_yaml_str(&stdfs::read_to_string(&path)?, io_args)
+    }
+
+    /// [`from_file`](Self::from_file) followed by
+    /// [`with_cli_override`](Self::with_cli_override), for the common
+    /// "project selectors, then the command line" sequence.
+    pub fn load_project_selectors(
+        project_root: &Path,
+        io_args: &'a IoArgs,
+        cli_override: Option<&str>,
+    ) -> FsResult<Self> {
+        Self::from_file(project_root, io_args)?.with_cli_override(cli_override)
+    }
+
+    /// Add the selector passed on the command line as the selector named
+    /// [`CLI_OVERRIDE_SELECTOR`], so that `parse_named` resolves it like any
+    /// other. `override_yaml` is the YAML of a selector `definition:`: a
+    /// selector string or a full expression, which may reference the
+    /// selectors from YAML. `None` leaves the parser unchanged.
+    pub fn with_cli_override(
+        mut self,
+        override_yaml: Option<&str>,
+    ) -> FsResult<SelectorParser<'a>> {
+        let Some(yaml) = override_yaml else {
+            return Ok(self);
+        };
+        let definition: SelectorDefinitionValue = dbt_yaml::from_str(yaml).map_err(|e| {
+            fs_err!(
+                ErrorCode::SelectorError,
+                "Failed to parse the command line selector: {}",
+                e
+            )
+        })?;
+        self.insert_definition(SelectorDefinition {
+            name: CLI_OVERRIDE_SELECTOR.to_string(),
+            description: None,
+            default: None,
+            definition,
+        });
+        Ok(self)
+    }
+
+    /// Name of the first selector (in name order) marked `default: true`.
+    pub fn default_selector(&self) -> Option<&str> {
+        self.defs
+            .iter()
+            .find(|(_, def)| def.default == Some(true))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Parse the selector marked `default: true`.
+    ///
+    /// Fails if no selector, or more than one selector, is marked as default.
+    pub fn parse_default(&self) -> FsResult<SelectExpression> {
+        let defaults = self
             .defs
-            .get(name)
-            .ok_or_else(|| fs_err!(ErrorCode::SelectorError, "Unknown selector `{}`", name))?;
-        self.parse_definition(&def.definition)
+            .iter()
+            .filter(|(_, def)| def.default == Some(true))
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        match defaults.as_slice() {
+            [] => err!(ErrorCode::SelectorError, "No selector has `default: true`"),
+            [name] => self.parse_named(name),
+            names => err!(
+                ErrorCode::SelectorError,
+                "Multiple selectors have `default: true`: {}",
+                names.join(", ")
+            ),
+        }
+    }
+
+    pub fn parse_named(&self, name: &str) -> FsResult<SelectExpression> {
+        self.parse_named_with_context(name, &mut ParseContext::default())
+    }
+
+    /// Resolve the selector `name` and return the `nodes` it selects, in
+    /// their original order. Meant for tests that check which nodes a
+    /// selector picks rather than the shape of its expression.
+    pub fn test_selector<'n>(
+        &self,
+        name: &str,
+        nodes: &'n [TestNode],
+    ) -> FsResult<Vec<&'n TestNode>> {
+        let expr = self.parse_named(name)?;
+        let all_nodes = nodes.iter().map(Node::from).collect::<Vec<_>>();
+        let selected = evaluate_select_expression(&expr, &all_nodes);
+        if selected.is_empty() && self.warn_on_empty_result {
+            emit_warn_log_message(
+                ErrorCode::SelectorError,
+                format!("Selector `{name}` ({expr}) does not select any nodes"),
+                self.io_args.status_reporter.as_ref(),
+            );
+        }
+        Ok(nodes
+            .iter()
+            .filter(|node| selected.contains(&node.unique_id))
+            .collect())
+    }
+
+    /// Compare the nodes that the selector `name` picks from `nodes` under
+    /// the `before` and `after` definitions, e.g. to review the effect of a
+    /// `selectors.yml` change.
+    pub fn diff<'n>(
+        before: &SelectorParser,
+        after: &SelectorParser,
+        name: &str,
+        nodes: &'n [TestNode],
+    ) -> FsResult<SelectorDiff<'n>> {
+        let all_nodes = nodes.iter().map(Node::from).collect::<Vec<_>>();
+        let before = evaluate_select_expression(&before.parse_named(name)?, &all_nodes);
+        let after = evaluate_select_expression(&after.parse_named(name)?, &all_nodes);
+        Ok(SelectorDiff {
+            added: nodes
+                .iter()
+                .filter(|node| after.contains(&node.unique_id) && !before.contains(&node.unique_id))
+                .collect(),
+            removed: nodes
+                .iter()
+                .filter(|node| before.contains(&node.unique_id) && !after.contains(&node.unique_id))
+                .collect(),
+        })
+    }
+
+    /// Parse every defined selector, ordered by name. Each selector is
+    /// resolved independently, so one broken definition does not hide the
+    /// results or errors of the others.
+    pub fn parse_all(&self) -> Vec<(String, FsResult<SelectExpression>)> {
+        self.defs
+            .keys()
+            .map(|name| (name.clone(), self.parse_named(name)))
+            .collect()
+    }
+
+    /// Parse every defined selector with its inheritance fully expanded,
+    /// keyed by name. Like [`parse_named`](Self::parse_named), every
+    /// `selector:` atom, including those of selector strings (e.g.
+    /// `"selector:nightly,tag:finance"`), is replaced by the expression of
+    /// the selector it names, so no value refers to another selector.
+    ///
+    /// Fails on the first selector that does not parse, or that refers to
+    /// an unknown selector or to itself.
+    pub fn resolve_all(&self) -> FsResult<BTreeMap<String, SelectExpression>> {
+        self.defs
+            .keys()
+            .map(|name| Ok((name.clone(), self.parse_named(name)?)))
+            .collect()
+    }
+
+    /// Parse several named selectors and select the union of their nodes,
+    /// e.g. for `--selector nightly,weekly`. No names yield `Empty`; the
+    /// first selector that fails to parse aborts with its error.
+    pub fn parse_named_group(&self, names: &[&str]) -> FsResult<SelectExpression> {
+        if names.is_empty() {
+            return Ok(SelectExpression::Empty);
+        }
+        let exprs = names
+            .iter()
+            .map(|name| self.parse_named(name))
+            .collect::<FsResult<Vec<_>>>()?;
+        Ok(SelectExpression::Or(exprs))
+    }
+
+    /// Like [`parse_named_group`](Self::parse_named_group), but also adds
+    /// the CLI-style selector string `extra` to the union, e.g. a user's
+    /// `--select` on top of the selectors a CI pipeline runs. Each operand
+    /// keeps its position: the named selectors in order, then `extra`. No
+    /// names and no `extra` yield `Empty`.
+    ///
+    /// Fails if a named selector is unknown or does not parse, or if
+    /// `extra` is not a valid selector.
+    pub fn parse_union_override(
+        &self,
+        names: &[&str],
+        extra: Option<&str>,
+    ) -> FsResult<SelectExpression> {
+        let mut exprs = names
+            .iter()
+            .map(|name| self.parse_named(name))
+            .collect::<FsResult<Vec<_>>>()?;
+        if let Some(extra) = extra {
+            exprs.push(self.parse_raw(extra)?);
+        }
+        if exprs.is_empty() {
+            return Ok(SelectExpression::Empty);
+        }
+        Ok(SelectExpression::Or(exprs))
+    }
+
+    /// Like [`parse_all`](Self::parse_all), but resolves the selectors on
+    /// the rayon thread pool. Worth it for workspaces with many selectors.
+    ///
+    /// The parser is shared by reference across threads, so it must be
+    /// `Sync`; this holds because the borrowed `IoArgs` only carries a
+    /// `Send + Sync` status reporter.
+    #[cfg(feature = "rayon")]
+    pub fn parse_definitions_parallel(&self) -> BTreeMap<String, FsResult<SelectExpression>> {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        (&self.defs)
+            .into_par_iter()
+            .map(|(name, _)| (name.clone(), self.parse_named(name)))
+            .collect()
+    }
+
+    /// Like [`parse_named`](Self::parse_named), but also returns the
+    /// definition's canonical name and description.
+    pub fn parse_named_with_meta(&self, name: &str) -> FsResult<ParsedSelector> {
+        let expression = self.parse_named(name)?;
+        let def = &self.defs[name];
+        Ok(ParsedSelector {
+            name: def.name.clone(),
+            description: def.description.clone(),
+            expression,
+        })
+    }
+
+    fn parse_named_with_context(
+        &self,
+        name: &str,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
+        if ctx.chain.iter().any(|visited| visited == name) {
+            let mut chain = ctx.chain.clone();
+            chain.push(name.to_string());
+            return Err(FsError::selector(SelectorErrorKind::CircularReference(
+                chain,
+            )));
+        }
+        if ctx.depth() >= self.max_depth {
+            return err!(
+                ErrorCode::SelectorError,
+                "Selector inheritance exceeds maximum depth of {}: {}",
+                self.max_depth,
+                ctx.describe_chain(name)
+            );
+        }
+
+        let def = self.defs.get(name).ok_or_else(|| {
+            FsError::selector(SelectorErrorKind::UnknownSelector(name.to_string()))
+        })?;
+
+        ctx.chain.push(name.to_string());
+        let result = self.parse_top_level_definition(&def.definition, ctx);
+        ctx.chain.pop();
+        result
     }
 
+    /// Parse the `definition:` of a selector. Unlike nested values, it may
+    /// be a bare `exclude:` block, which excludes from the current run's
+    /// selection.
     pub fn parse_definition(&self, def: &SelectorDefinitionValue) -> FsResult<SelectExpression> {
+        self.parse_top_level_definition(def, &mut ParseContext::default())
+    }
+
+    /// Like [`parse_definition`](Self::parse_definition), but opens a
+    /// `TRACE`-level `parse_selector` span for every step of the parse, with
+    /// the fields `method` (empty for composites), `depth` (the `selector:`
+    /// inheritance depth) and `expression_kind` (`string`, `atom`, `exclude`
+    /// or the composite's key, e.g. `union`). Spans nest like the steps, to
+    /// find which part of a large selector is slow to parse.
+    pub fn parse_definition_traced(
+        &self,
+        def: &SelectorDefinitionValue,
+    ) -> FsResult<SelectExpression> {
+        let mut ctx = ParseContext {
+            traced: true,
+            ..ParseContext::default()
+        };
+        self.parse_top_level_definition(def, &mut ctx)
+    }
+
+    /// Parse a CLI-style selector string, as if it were a selector's
+    /// `definition:`.
+    pub fn parse_raw(&self, s: &str) -> FsResult<SelectExpression> {
+        self.parse_definition(&SelectorDefinitionValue::String(s.to_owned()))
+    }
+
+    /// Parse a selector for the single node with the given unique id, e.g.
+    /// `model.my_project.my_model` or `node_id:model.my_project.my_model`.
+    pub fn parse_node_specifier(&self, spec: &str) -> FsResult<SelectExpression> {
+        let unique_id = spec.strip_prefix("node_id:").unwrap_or(spec);
+        if unique_id.is_empty() {
+            return err!(
+                ErrorCode::SelectorError,
+                "Invalid selector spec: `{}`",
+                spec
+            );
+        }
+        Ok(SelectExpression::Atom(SelectionCriteria::with_method(
+            MethodName::NodeId,
+            unique_id,
+        )))
+    }
+
+    /// Select every node except those matched by any of `defs`, like
+    /// `--exclude` without `--select`: `And([all:*, Exclude(Or(defs))])`.
+    ///
+    /// Fails with [`SelectorErrorKind::EmptyExclude`] if `defs` is empty.
+    pub fn parse_exclude_only(
+        &self,
+        defs: &[SelectorDefinitionValue],
+    ) -> FsResult<SelectExpression> {
+        let excluded = self.parse_exclude_list(defs, &mut ParseContext::default())?;
+        Ok(SelectExpression::And(vec![
+            SelectExpression::Atom(SelectionCriteria::with_method(MethodName::Wildcard, "*")),
+            SelectExpression::Exclude(Box::new(excluded)),
+        ]))
+    }
+
+    /// Parse the CLI-style selector `base` and add the tests of the models
+    /// it selects, like `--select my_model+` with downstream tests included.
+    /// `nodes_with_tests` pairs a model's unique id with the unique id of a
+    /// test on it; each test of a selected model is added as a `node_id:`
+    /// atom, in input order, giving `Or([base, node_id:<test>, ..])`. With
+    /// no such test, `base` is returned as parsed.
+    ///
+    /// Only the unique ids of the models are known here, so `base` selects
+    /// among them by resource type, package and name (e.g. `fqn:`,
+    /// `node_id:`, `package:`); methods that need other attributes, such as
+    /// `tag:`, select none of them, and graph operators do not reach other
+    /// models.
+    pub fn parse_with_downstream_tests(
+        &self,
+        base: &str,
+        nodes_with_tests: &[(&str, &str)],
+    ) -> FsResult<SelectExpression> {
+        let expr = self.parse_raw(base)?;
+        let models = nodes_with_tests
+            .iter()
+            .map(|(model_id, _)| *model_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(node_from_unique_id)
+            .collect::<Vec<_>>();
+        let selected = evaluate_select_expression(&expr, &models);
+
+        let mut tests = Vec::new();
+        let mut seen = BTreeSet::new();
+        for (model_id, test_id) in nodes_with_tests {
+            if selected.contains(*model_id) && seen.insert(*test_id) {
+                tests.push(SelectExpression::Atom(SelectionCriteria::with_method(
+                    MethodName::NodeId,
+                    *test_id,
+                )));
+            }
+        }
+        if tests.is_empty() {
+            return Ok(expr);
+        }
+        Ok(SelectExpression::Or(
+            std::iter::once(expr).chain(tests).collect(),
+        ))
+    }
+
+    /// Parse several CLI-style selector strings and select the union of
+    /// their nodes. No strings yield `Empty`.
+    pub fn parse_raw_many(&self, ss: &[&str]) -> FsResult<SelectExpression> {
+        if ss.is_empty() {
+            return Ok(SelectExpression::Empty);
+        }
+        let exprs = ss
+            .iter()
+            .map(|s| self.parse_raw(s))
+            .collect::<FsResult<Vec<_>>>()?;
+        Ok(SelectExpression::Or(exprs))
+    }
+
+    fn parse_top_level_definition(
+        &self,
+        def: &SelectorDefinitionValue,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
+        match def {
+            SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Exclude(exclude))) => {
+                Ok(SelectExpression::Exclude(Box::new(
+                    self.parse_exclude_list(&exclude.exclude, ctx)?,
+                )))
+            }
+            _ => self.parse_definition_with_context(def, ctx),
+        }
+    }
+
+    /// Like [`Self::parse_definition`], but resolves `selector:` references
+    /// relative to an existing [`ParseContext`] so that circular or overly
+    /// deep inheritance is reported instead of overflowing the stack.
+    pub fn parse_definition_with_context(
+        &self,
+        def: &SelectorDefinitionValue,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
         match def {
-            SelectorDefinitionValue::String(s) => Ok(parse_model_specifiers(slice::from_ref(s))?),
-            SelectorDefinitionValue::Full(expr) => self.parse_expr(expr),
+            SelectorDefinitionValue::String(s) => {
+                let _span = ctx.trace_step("string", "");
+                let expr = parse_model_specifiers(slice::from_ref(s))?;
+                self.resolve_selector_atoms(expr, ctx)
+            }
+            SelectorDefinitionValue::Full(expr) => self.parse_expr_with_context(expr, ctx),
+        }
+    }
+
+    /// Replace the `selector:` atoms of a parsed selector string with the
+    /// expressions of the selectors they name, like a full atom with
+    /// `method: selector`.
+    fn resolve_selector_atoms(
+        &self,
+        mut expr: SelectExpression,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
+        let references = expr
+            .atoms()
+            .filter(|criteria| criteria.method == MethodName::Selector)
+            .map(|criteria| {
+                let has_graph_operators = criteria.childrens_parents
+                    || criteria.parents_depth.is_some()
+                    || criteria.children_depth.is_some();
+                (criteria.value.clone(), has_graph_operators)
+            })
+            .collect::<Vec<_>>();
+        if references
+            .iter()
+            .any(|(_, has_graph_operators)| *has_graph_operators)
+        {
+            emit_warn_log_message(
+                ErrorCode::SelectorError,
+                "Graph operators (parents, children, etc.) are not supported with selector inheritance and will be ignored",
+                self.io_args.status_reporter.as_ref(),
+            );
         }
+        let names = references
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<BTreeSet<_>>();
+        for name in names {
+            let replacement = self.parse_named_with_context(&name, ctx)?;
+            expr = expr.substitute(&name, replacement);
+        }
+        Ok(expr)
     }
 
     pub fn parse_expr(&self, expr: &SelectorExpr) -> FsResult<SelectExpression> {
+        self.parse_expr_with_context(expr, &mut ParseContext::default())
+    }
+
+    /// Like [`parse_expr`](Self::parse_expr), but first rewrites every
+    /// method atom of `expr` through `f`, e.g. to prefix `fqn` values with a
+    /// tenant's project name. Shorthand `method: value` atoms are passed to
+    /// `f` in their long form. Selector strings, and the definitions reached
+    /// through `selector:` references, are parsed as written.
+    pub fn parse_expr_transformed<F>(&self, expr: &SelectorExpr, f: F) -> FsResult<SelectExpression>
+    where
+        F: Fn(&MethodAtomExpr) -> MethodAtomExpr,
+    {
+        self.parse_expr(&transform_method_atoms(expr, &f))
+    }
+
+    /// Rewrite `def` into a canonical form, so that equivalent selectors
+    /// serialize identically, e.g. to find duplicates:
+    ///
+    /// * selector strings and `method: value` shorthands become full
+    ///   `method`/`value` atoms, with the method written as the parser
+    ///   resolves it (`TAG` ⇒ `tag`);
+    /// * `indirect_selection` is always explicit;
+    /// * `parents`/`children` are set whenever a depth is, and an unlimited
+    ///   depth is left unset rather than written out;
+    /// * the values of an `intersection` are sorted by method name.
+    ///
+    /// The canonical form selects the same nodes as `def`. Selector strings
+    /// with `selector:` atoms are kept as written, since their references
+    /// can only be resolved against the other definitions. Fails on
+    /// unsupported methods.
+    pub fn canonicalize(def: &SelectorDefinitionValue) -> FsResult<SelectorDefinitionValue> {
+        match def {
+            SelectorDefinitionValue::String(s) => {
+                let expr = parse_model_specifiers(slice::from_ref(s))?;
+                if expr
+                    .atoms()
+                    .any(|criteria| criteria.method == MethodName::Selector)
+                {
+                    return Ok(def.clone());
+                }
+                match expression_to_definition(&expr) {
+                    Some(full) => Self::canonicalize(&full),
+                    None => Ok(def.clone()),
+                }
+            }
+            SelectorDefinitionValue::Full(expr) => {
+                Ok(SelectorDefinitionValue::Full(canonicalize_expr(expr)?))
+            }
+        }
+    }
+
+    fn parse_expr_with_context(
+        &self,
+        expr: &SelectorExpr,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
+        let _span = match expr {
+            SelectorExpr::Composite(comp) => {
+                let op = comp.kind.keys().next().map_or("", String::as_str);
+                ctx.trace_step(op, "")
+            }
+            SelectorExpr::Atom(AtomExpr::Method(method)) => ctx.trace_step("atom", &method.method),
+            SelectorExpr::Atom(AtomExpr::MethodKey(method_value)) => {
+                let method = method_value.keys().next().map_or("", String::as_str);
+                ctx.trace_step("atom", method)
+            }
+            // traced by `parse_exclude_list`, which composites call directly
+            SelectorExpr::Atom(AtomExpr::Exclude(_)) => None,
+        };
         match expr {
-            SelectorExpr::Composite(comp) => self.parse_composite(comp),
-            SelectorExpr::Atom(atom) => self.parse_atom(atom),
+            SelectorExpr::Composite(comp) => self.parse_composite_with_context(comp, ctx),
+            SelectorExpr::Atom(atom) => self.parse_atom_with_context(atom, ctx),
         }
     }
 
     pub fn parse_composite(&self, comp: &CompositeExpr) -> FsResult<SelectExpression> {
+        self.parse_composite_with_context(comp, &mut ParseContext::default())
+    }
+
+    fn parse_composite_with_context(
+        &self,
+        comp: &CompositeExpr,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
+        if let Some(CompositeKind::Difference(values)) = comp.kind.values().next() {
+            let [include, exclude] = values.as_slice() else {
+                return err!(
+                    ErrorCode::SelectorError,
+                    "`difference` takes exactly two selectors, found {}",
+                    values.len()
+                );
+            };
+            return Ok(difference(
+                self.parse_definition_with_context(include, ctx)?,
+                self.parse_definition_with_context(exclude, ctx)?,
+            ));
+        }
+
         let mut includes = Vec::new();
         let mut exclude_exprs = Vec::new();
 
         // Get the operator and values from the single entry map
-        let (op_kind, values) = comp
+        let (op, op_kind, values) = comp
             .kind
             .iter()
             .next()
-            .map(|(_k, kind)| {
+            .map(|(op, kind)| {
                 let vals = match kind {
                     CompositeKind::Union(vals) => vals,
                     CompositeKind::Intersection(vals) => vals,
+                    CompositeKind::Difference(vals) => vals,
                 };
-                (kind, vals)
+                (op, kind, vals)
             })
-            .ok_or_else(|| fs_err!(ErrorCode::SelectorError, "Empty composite expression"))?;
+            .ok_or_else(|| FsError::selector(SelectorErrorKind::EmptyComposite))?;
+        if values.is_empty() {
+            return Err(Box::new(
+                FsError::selector(SelectorErrorKind::EmptyComposite)
+                    .with_context(format!("Empty `{op}` list{}", ctx.describe_position())),
+            ));
+        }
 
         for value in values {
             // Check if this value is an exclude expression
@@ -76,24 +1035,29 @@ impl<'a> SelectorParser<'a> {
                 value
             {
                 // Handle exclude as a special case within composite expressions
-                let exprs = self.collect_definition_includes(&exclude.exclude)?;
-                let exclude_expression = match exprs.len() {
-                    0 => return Err(fs_err!(ErrorCode::SelectorError, "Empty exclude list")),
-                    1 => exprs.into_iter().next().unwrap(),
-                    _ => SelectExpression::Or(exprs),
-                };
-                exclude_exprs.push(exclude_expression);
+                exclude_exprs.push(self.parse_exclude_list(&exclude.exclude, ctx)?);
             } else {
                 // Handle regular include expressions
-                let resolved = self.parse_definition(value)?;
+                let resolved = self.parse_definition_with_context(value, ctx)?;
                 includes.push(resolved);
             }
         }
+        // `intersection` of nothing but excludes would select every node not
+        // excluded, which is almost never what the author meant.
+        if includes.is_empty() && matches!(op_kind, CompositeKind::Intersection(_)) {
+            return err!(
+                ErrorCode::SelectorError,
+                "`{}` list has only `exclude` values and nothing to exclude them from{}",
+                op,
+                ctx.describe_position()
+            );
+        }
 
         // Build the boolean operator over includes
         let include_expr = match op_kind {
             CompositeKind::Union(_) => SelectExpression::Or(includes),
             CompositeKind::Intersection(_) => SelectExpression::And(includes),
+            CompositeKind::Difference(_) => unreachable!("difference is parsed above"),
         };
 
         // If we have exclude expressions, combine them
@@ -115,13 +1079,46 @@ impl<'a> SelectorParser<'a> {
         Ok(include_expr)
     }
 
+    /// The union of the values of an `exclude:` list.
+    fn parse_exclude_list(
+        &self,
+        values: &[SelectorDefinitionValue],
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
+        let _span = ctx.trace_step("exclude", "");
+        let mut exprs = self.collect_definition_includes(values, ctx)?;
+        match exprs.len() {
+            0 => Err(FsError::selector(SelectorErrorKind::EmptyExclude)),
+            1 => Ok(exprs.pop().unwrap()),
+            _ => Ok(SelectExpression::Or(exprs)),
+        }
+    }
+
+    #[cfg(test)]
     fn parse_atom(&self, atom: &AtomExpr) -> FsResult<SelectExpression> {
+        self.parse_atom_with_context(atom, &mut ParseContext::default())
+    }
+
+    fn parse_atom_with_context(
+        &self,
+        atom: &AtomExpr,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
         match atom {
+            AtomExpr::Method(expr) if expr.method.starts_with(NOT_PREFIX) => {
+                let inner = AtomExpr::Method(MethodAtomExpr {
+                    method: strip_not(&expr.method).to_string(),
+                    ..expr.clone()
+                });
+                Ok(SelectExpression::Not(Box::new(
+                    self.parse_atom_with_context(&inner, ctx)?,
+                )))
+            }
             AtomExpr::Method(expr) => {
                 // Special handling for selector method - recursively resolve the referenced selector
                 if expr.method == "selector" {
                     // Recursively resolve the referenced selector
-                    let referenced_selector = self.parse_named(&expr.value)?;
+                    let referenced_selector = self.parse_named_with_context(&expr.value, ctx)?;
 
                     // Note: Per the docs, graph operators (parents, children, etc.) are NOT
                     // supported for selector inheritance, so we ignore them and return the
@@ -143,17 +1140,20 @@ impl<'a> SelectorParser<'a> {
                     Ok(referenced_selector)
                 } else {
                     // Use atom_to_select_expression which handles the exclude field properly
-                    self.atom_to_select_expression(AtomExpr::Method(MethodAtomExpr {
-                        method: expr.method.clone(),
-                        value: expr.value.clone(),
-                        childrens_parents: expr.childrens_parents,
-                        parents: expr.parents,
-                        children: expr.children,
-                        parents_depth: expr.parents_depth,
-                        children_depth: expr.children_depth,
-                        indirect_selection: expr.indirect_selection,
-                        exclude: expr.exclude.clone(),
-                    }))
+                    self.atom_to_select_expression(
+                        AtomExpr::Method(MethodAtomExpr {
+                            method: expr.method.clone(),
+                            value: expr.value.clone(),
+                            childrens_parents: expr.childrens_parents,
+                            parents: expr.parents,
+                            children: expr.children,
+                            parents_depth: expr.parents_depth,
+                            children_depth: expr.children_depth,
+                            indirect_selection: expr.indirect_selection,
+                            exclude: expr.exclude.clone(),
+                        }),
+                        ctx,
+                    )
                 }
             }
 
@@ -165,6 +1165,15 @@ impl<'a> SelectorParser<'a> {
                     ));
                 }
                 let (m, v) = method_value.iter().next().unwrap();
+                if m.starts_with(NOT_PREFIX) {
+                    let inner = AtomExpr::MethodKey(BTreeMap::from([(
+                        strip_not(m).to_string(),
+                        v.clone(),
+                    )]));
+                    return Ok(SelectExpression::Not(Box::new(
+                        self.parse_atom_with_context(&inner, ctx)?,
+                    )));
+                }
                 let wrapper = AtomExpr::Method(MethodAtomExpr {
                     method: m.clone(),
                     value: v.clone(),
@@ -176,45 +1185,549 @@ impl<'a> SelectorParser<'a> {
                     indirect_selection: Some(IndirectSelection::default()),
                     exclude: None,
                 });
-                self.atom_to_select_expression(wrapper)
+                self.atom_to_select_expression(wrapper, ctx)
             }
 
             AtomExpr::Exclude(_) => {
                 err!(
                     ErrorCode::SelectorError,
-                    "Top level exclude not allowed in YAML selectors"
+                    "`exclude` is only allowed as a selector's whole definition or as an operand of `union`/`intersection`, not nested inside other expressions"
                 )
             }
         }
     }
 
-    fn collect_definition_includes(
-        &self,
-        defs: &[SelectorDefinitionValue],
-    ) -> FsResult<Vec<SelectExpression>> {
-        defs.iter().map(|dv| self.parse_definition(dv)).collect()
+    /// Parse the value of a `--select` flag.
+    ///
+    /// Uses the same operators as `parse_model_specifiers` (spaces union,
+    /// commas intersect), but never splits on a comma that is escaped (`\,`)
+    /// or sits inside `[...]`, e.g. in a glob character class.
+    pub fn parse_cli_select(s: &str) -> FsResult<SelectExpression> {
+        let mut or_terms = Vec::new();
+        for token in split_unprotected(s, char::is_whitespace) {
+            let mut and_terms = split_unprotected(&token, |c| c == ',')
+                .iter()
+                .map(|piece| parse_single_selector(piece).map(SelectExpression::Atom))
+                .collect::<FsResult<Vec<_>>>()?;
+            or_terms.push(match and_terms.len() {
+                0 => continue,
+                1 => and_terms.pop().unwrap(),
+                _ => SelectExpression::And(and_terms),
+            });
+        }
+        match or_terms.len() {
+            0 => err!(
+                ErrorCode::SelectorError,
+                "empty selector passed to --select: `{}`",
+                s
+            ),
+            1 => Ok(or_terms.pop().unwrap()),
+            _ => Ok(SelectExpression::Or(or_terms)),
+        }
     }
 
-    fn atom_to_select_expression(&self, atom: AtomExpr) -> FsResult<SelectExpression> {
-        match atom {
-            AtomExpr::Method(expr) => {
-                let method = expr.method.clone();
-                let value = expr.value.clone();
-                let childrens_parents = expr.childrens_parents;
-                let parents = expr.parents;
-                let children = expr.children;
-                let parents_depth = expr.parents_depth;
+    /// Parse a selector string written in the dbt v1 format, where a parents
+    /// depth follows the `+` (`+1model`) instead of preceding it (`1+model`).
+    ///
+    /// Old-style specifiers are rewritten to the current format and a
+    /// deprecation warning is emitted; anything else is passed through, so
+    /// the result is always what `parse_model_specifiers` returns for the
+    /// translated string.
+    pub fn parse_v1_syntax(&self, s: &str) -> FsResult<SelectExpression> {
+        let mut translated_any = false;
+        let specifiers = s
+            .split_whitespace()
+            .map(|token| {
+                token
+                    .split(',')
+                    .map(|piece| match translate_v1_specifier(piece) {
+                        Some(translated) => {
+                            translated_any = true;
+                            translated
+                        }
+                        None => piece.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect::<Vec<_>>();
+        if translated_any {
+            emit_warn_log_message(
+                ErrorCode::SelectorError,
+                format!(
+                    "Selector `{}` uses the deprecated v1 graph operator syntax; use `{}` instead",
+                    s,
+                    specifiers.join(" ")
+                ),
+                self.io_args.status_reporter.as_ref(),
+            );
+        }
+        parse_model_specifiers(&specifiers)
+    }
+
+    /// Explain how the selector `name` resolves: which composite, exclude
+    /// and atom steps produce which expressions, where each step is written
+    /// in YAML, and which selectors are reached through `selector:`
+    /// inheritance.
+    pub fn explain(&self, name: &str) -> FsResult<SelectorExplanation> {
+        // Resolve first so that cycles and unknown references are reported
+        // exactly as `parse_named` reports them.
+        let expression = self.parse_named(name)?;
+        let def = &self.defs[name];
+        let mut ctx = ParseContext {
+            chain: vec![name.to_string()],
+            ..ParseContext::default()
+        };
+        let definition =
+            self.explain_definition(&def.definition, self.sources.get(name), &mut ctx)?;
+        Ok(SelectorExplanation {
+            step: ExplanationStep::Selector(name.to_string()),
+            expression,
+            location: definition.location,
+            children: vec![definition],
+        })
+    }
+
+    fn explain_definition(
+        &self,
+        def: &SelectorDefinitionValue,
+        source: Option<&dbt_yaml::Value>,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectorExplanation> {
+        let location = source.map(|value| SourceLocation::from(value.span()));
+        let explain_each = |defs: &[SelectorDefinitionValue],
+                            sources: Option<&dbt_yaml::Value>,
+                            ctx: &mut ParseContext| {
+            defs.iter()
+                .enumerate()
+                .map(|(i, def)| {
+                    let source = sources.and_then(|v| v.get(i));
+                    self.explain_definition(def, source, ctx)
+                })
+                .collect::<FsResult<Vec<_>>>()
+        };
+
+        let SelectorDefinitionValue::Full(expr) = def else {
+            return Ok(SelectorExplanation {
+                step: ExplanationStep::Atom,
+                expression: self.parse_definition_with_context(def, ctx)?,
+                location,
+                children: vec![],
+            });
+        };
+        let (step, children) = match expr {
+            SelectorExpr::Composite(comp) => {
+                let (key, kind) = comp
+                    .kind
+                    .iter()
+                    .next()
+                    .ok_or_else(|| FsError::selector(SelectorErrorKind::EmptyComposite))?;
+                let (step, values) = match kind {
+                    CompositeKind::Union(vals) => (ExplanationStep::Union, vals),
+                    CompositeKind::Intersection(vals) => (ExplanationStep::Intersection, vals),
+                    CompositeKind::Difference(vals) => (ExplanationStep::Difference, vals),
+                };
+                let sources = source.and_then(|v| v.get(key.as_str()));
+                (step, explain_each(values, sources, ctx)?)
+            }
+            SelectorExpr::Atom(AtomExpr::Exclude(exclude)) => {
+                let sources = source.and_then(|v| v.get("exclude"));
+                let children = explain_each(&exclude.exclude, sources, ctx)?;
+                let expression = match children.as_slice() {
+                    [] => return Err(FsError::selector(SelectorErrorKind::EmptyExclude)),
+                    [only] => only.expression.clone(),
+                    _ => SelectExpression::Or(
+                        children.iter().map(|c| c.expression.clone()).collect(),
+                    ),
+                };
+                return Ok(SelectorExplanation {
+                    step: ExplanationStep::Exclude,
+                    expression: SelectExpression::Exclude(Box::new(expression)),
+                    location,
+                    children,
+                });
+            }
+            SelectorExpr::Atom(atom) => {
+                let referenced = match atom {
+                    AtomExpr::Method(method) if method.method == "selector" => {
+                        Some(method.value.to_string())
+                    }
+                    AtomExpr::MethodKey(method_value) if method_value.len() == 1 => {
+                        method_value.get("selector").map(ToString::to_string)
+                    }
+                    _ => None,
+                };
+                if let Some(referenced) = referenced {
+                    ctx.chain.push(referenced.clone());
+                    let definition = self.explain_definition(
+                        &self.defs[&referenced].definition,
+                        self.sources.get(&referenced),
+                        ctx,
+                    );
+                    ctx.chain.pop();
+                    let definition = definition?;
+                    return Ok(SelectorExplanation {
+                        step: ExplanationStep::Selector(referenced),
+                        expression: definition.expression.clone(),
+                        location,
+                        children: vec![definition],
+                    });
+                }
+                let children = match atom {
+                    AtomExpr::Method(method) => {
+                        let sources = source.and_then(|v| v.get("exclude"));
+                        explain_each(method.exclude.as_deref().unwrap_or_default(), sources, ctx)?
+                    }
+                    _ => vec![],
+                };
+                (ExplanationStep::Atom, children)
+            }
+        };
+        Ok(SelectorExplanation {
+            step,
+            expression: self.parse_expr_with_context(expr, ctx)?,
+            location,
+            children,
+        })
+    }
+
+    /// Resolve two named selectors and return the nodes selected by
+    /// `include` but not by `exclude`.
+    pub fn parse_difference(&self, include: &str, exclude: &str) -> FsResult<SelectExpression> {
+        Ok(difference(
+            self.parse_named(include)?,
+            self.parse_named(exclude)?,
+        ))
+    }
+
+    /// Check `def` for errors without building a `SelectExpression`.
+    ///
+    /// Reports unknown `selector:` references, empty composite or exclude
+    /// lists, malformed method-key atoms and method names that do not map to
+    /// any `MethodName`. All problems are collected: a single problem is
+    /// returned as-is, several are chained behind an `ErrorCode::MultiError`.
+    pub fn validate(&self, def: &SelectorDefinitionValue) -> FsResult<()> {
+        let mut errors = Vec::new();
+        self.validate_into(def, &mut errors);
+
+        let mut errors = errors.into_iter();
+        match (errors.next(), errors.len()) {
+            (None, _) => Ok(()),
+            (Some(only), 0) => Err(Box::new(only)),
+            (Some(first), rest) => {
+                let chained = errors.fold(first, |acc, e| acc.with_chained_errors(Box::new(e)));
+                Err(Box::new(
+                    FsError::new(
+                        ErrorCode::MultiError,
+                        format!("{} errors found in selector definition", rest + 1),
+                    )
+                    .with_chained_errors(Box::new(chained)),
+                ))
+            }
+        }
+    }
+
+    fn validate_into(&self, def: &SelectorDefinitionValue, errors: &mut Vec<FsError>) {
+        let expr = match def {
+            SelectorDefinitionValue::String(s) => {
+                match parse_model_specifiers(slice::from_ref(s)) {
+                    Ok(expr) => {
+                        for criteria in expr.atoms() {
+                            if criteria.method == MethodName::Selector {
+                                self.validate_method("selector", &criteria.value, errors);
+                            }
+                        }
+                    }
+                    Err(e) => errors.push(*e),
+                }
+                return;
+            }
+            SelectorDefinitionValue::Full(expr) => expr,
+        };
+
+        match expr {
+            SelectorExpr::Composite(comp) => {
+                let Some(kind) = comp.kind.values().next() else {
+                    errors.push(*FsError::selector(SelectorErrorKind::EmptyComposite));
+                    return;
+                };
+                let (op, values) = match kind {
+                    CompositeKind::Union(vals) => ("union", vals),
+                    CompositeKind::Intersection(vals) => ("intersection", vals),
+                    CompositeKind::Difference(vals) => ("difference", vals),
+                };
+                if values.is_empty() {
+                    errors.push(
+                        FsError::selector(SelectorErrorKind::EmptyComposite)
+                            .with_context(format!("Empty `{op}` list")),
+                    );
+                } else if op == "difference" && values.len() != 2 {
+                    errors.push(FsError::new(
+                        ErrorCode::SelectorError,
+                        format!(
+                            "`difference` takes exactly two selectors, found {}",
+                            values.len()
+                        ),
+                    ));
+                }
+                for value in values {
+                    self.validate_into(value, errors);
+                }
+            }
+            SelectorExpr::Atom(AtomExpr::Method(method)) => {
+                self.validate_method(&method.method, &method.value, errors);
+                if let Some(exclude) = &method.exclude {
+                    self.validate_exclude(exclude, errors);
+                }
+            }
+            SelectorExpr::Atom(AtomExpr::MethodKey(method_value)) => {
+                if method_value.len() != 1 {
+                    errors.push(FsError::new(
+                        ErrorCode::SelectorError,
+                        format!(
+                            "MethodKey must have exactly one key-value pair, found keys: {}",
+                            method_value.keys().cloned().collect::<Vec<_>>().join(", ")
+                        ),
+                    ));
+                }
+                for (method, value) in method_value {
+                    self.validate_method(method, value, errors);
+                }
+            }
+            SelectorExpr::Atom(AtomExpr::Exclude(exclude)) => {
+                self.validate_exclude(&exclude.exclude, errors);
+            }
+        }
+    }
+
+    fn validate_method(&self, method: &str, value: &str, errors: &mut Vec<FsError>) {
+        let method = strip_not(method);
+        if method == "selector" {
+            if !self.defs.contains_key(value) {
+                errors.push(*FsError::selector(SelectorErrorKind::UnknownSelector(
+                    value.to_string(),
+                )));
+            }
+            return;
+        }
+        if qualify_method(method).is_none() {
+            let head = method.split('.').next().unwrap_or_default();
+            errors.push(*FsError::selector(SelectorErrorKind::UnsupportedMethod(
+                head.to_string(),
+            )));
+        }
+    }
+
+    fn validate_exclude(&self, exclude: &[SelectorDefinitionValue], errors: &mut Vec<FsError>) {
+        if exclude.is_empty() {
+            errors.push(*FsError::selector(SelectorErrorKind::EmptyExclude));
+        }
+        for value in exclude {
+            self.validate_into(value, errors);
+        }
+    }
+
+    /// Return every defined selector name ordered so that each selector
+    /// appears after all selectors it references (directly or transitively)
+    /// via `selector:` atoms. Ties are broken alphabetically.
+    pub fn list_all_names(&self) -> FsResult<Vec<String>> {
+        // name -> selectors it depends on (restricted to defined selectors)
+        let mut pending: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut dependencies: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        let mut dependents: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (name, def) in &self.defs {
+            let mut refs = BTreeSet::new();
+            collect_selector_references(&def.definition, &mut refs);
+            let deps = refs
+                .iter()
+                .filter_map(|r| self.defs.get_key_value(r.as_str()).map(|(k, _)| k.as_str()))
+                .collect::<Vec<_>>();
+            pending.insert(name.as_str(), deps.len());
+            for &dep in &deps {
+                dependents.entry(dep).or_default().push(name.as_str());
+            }
+            dependencies.insert(name.as_str(), deps);
+        }
+
+        let mut ready = pending
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(name, _)| *name)
+            .collect::<VecDeque<_>>();
+        let mut ordered = Vec::with_capacity(self.defs.len());
+        while let Some(name) = ready.pop_front() {
+            ordered.push(name.to_string());
+            for dependent in dependents.get(name).into_iter().flatten() {
+                let count = pending.get_mut(dependent).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(*dependent);
+                }
+            }
+        }
+
+        if ordered.len() != self.defs.len() {
+            // Every selector left pending depends on another pending one, so
+            // following those dependencies runs into a cycle.
+            let is_pending = |name: &str| pending[name] > 0;
+            let mut chain = Vec::new();
+            let mut current = pending
+                .keys()
+                .copied()
+                .find(|name| is_pending(name))
+                .unwrap();
+            while !chain.contains(&current) {
+                chain.push(current);
+                current = dependencies[current]
+                    .iter()
+                    .copied()
+                    .find(|name| is_pending(name))
+                    .unwrap();
+            }
+            let start = chain.iter().position(|name| *name == current).unwrap();
+            let cycle = chain[start..]
+                .iter()
+                .chain([&current])
+                .map(|name| name.to_string())
+                .collect();
+            return Err(FsError::selector(SelectorErrorKind::CircularReference(
+                cycle,
+            )));
+        }
+        Ok(ordered)
+    }
+
+    /// Every `selector:` reference to a selector that is not defined, as
+    /// `(selector_name, referenced_name)` pairs ordered by selector name.
+    /// Unlike `parse_named`, which stops at the first unknown reference,
+    /// this scans all definitions without resolving them.
+    pub fn describe_undefined_references(&self) -> Vec<(String, String)> {
+        let mut undefined = Vec::new();
+        for (name, def) in &self.defs {
+            let mut refs = BTreeSet::new();
+            collect_selector_references(&def.definition, &mut refs);
+            undefined.extend(
+                refs.into_iter()
+                    .filter(|r| !self.defs.contains_key(r))
+                    .map(|r| (name.clone(), r)),
+            );
+        }
+        undefined
+    }
+
+    /// Defined selectors that are neither in `used_names` (the selectors
+    /// invoked at runtime) nor reachable from one of them through
+    /// `selector:` inheritance, sorted by name. A `default: true` selector
+    /// only counts as used if the caller includes it in `used_names`.
+    pub fn unused_selectors(&self, used_names: &[&str]) -> Vec<String> {
+        let mut reachable = BTreeSet::new();
+        let mut queue = used_names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<VecDeque<_>>();
+        while let Some(name) = queue.pop_front() {
+            let Some(def) = self.defs.get(&name) else {
+                continue;
+            };
+            if !reachable.insert(name) {
+                continue;
+            }
+            let mut refs = BTreeSet::new();
+            collect_selector_references(&def.definition, &mut refs);
+            queue.extend(refs.into_iter().filter(|r| !reachable.contains(r)));
+        }
+        self.defs
+            .keys()
+            .filter(|name| !reachable.contains(*name))
+            .cloned()
+            .collect()
+    }
+
+    /// The definition of the selector `name`, as written.
+    pub fn get_definition(&self, name: &str) -> Option<&SelectorDefinition> {
+        self.defs.get(name)
+    }
+
+    /// Every selector definition with its name, ordered by name.
+    pub fn iter_definitions(&self) -> impl Iterator<Item = (&str, &SelectorDefinition)> + '_ {
+        self.defs.iter().map(|(name, def)| (name.as_str(), def))
+    }
+
+    /// Add the selector `def`, keyed by its name. Returns the definition it
+    /// replaces, if a selector of that name was already defined.
+    pub fn insert_definition(&mut self, def: SelectorDefinition) -> Option<SelectorDefinition> {
+        self.sources.remove(&def.name);
+        self.defs.insert(def.name.clone(), def)
+    }
+
+    /// Remove the selector `name`, returning its definition. Selectors that
+    /// reference it fail to parse until it is defined again.
+    pub fn remove_definition(&mut self, name: &str) -> Option<SelectorDefinition> {
+        self.sources.remove(name);
+        self.defs.remove(name)
+    }
+
+    /// Rename the selector `old` to `new` and rewrite every `selector: old`
+    /// reference in the definitions to point at `new`. Returns the number of
+    /// references rewritten.
+    ///
+    /// Fails if `old` is not defined or `new` already is.
+    pub fn rename_selector(&mut self, old: &str, new: &str) -> FsResult<usize> {
+        if self.defs.contains_key(new) {
+            return err!(
+                ErrorCode::SelectorError,
+                "Cannot rename selector `{}` to `{}`: `{}` is already defined",
+                old,
+                new,
+                new
+            );
+        }
+        let Some(mut def) = self.defs.remove(old) else {
+            return Err(FsError::selector(SelectorErrorKind::UnknownSelector(
+                old.to_string(),
+            )));
+        };
+        def.name = new.to_string();
+        self.defs.insert(new.to_string(), def);
+        if let Some(source) = self.sources.remove(old) {
+            self.sources.insert(new.to_string(), source);
+        }
+
+        Ok(self
+            .defs
+            .values_mut()
+            .map(|def| rename_selector_references(&mut def.definition, old, new))
+            .sum())
+    }
+
+    fn collect_definition_includes(
+        &self,
+        defs: &[SelectorDefinitionValue],
+        ctx: &mut ParseContext,
+    ) -> FsResult<Vec<SelectExpression>> {
+        defs.iter()
+            .map(|dv| self.parse_definition_with_context(dv, ctx))
+            .collect()
+    }
+
+    fn atom_to_select_expression(
+        &self,
+        atom: AtomExpr,
+        ctx: &mut ParseContext,
+    ) -> FsResult<SelectExpression> {
+        match atom {
+            AtomExpr::Method(expr) => {
+                let method = expr.method.clone();
+                let value = expr.value.clone();
+                let childrens_parents = expr.childrens_parents;
+                let parents = expr.parents;
+                let children = expr.children;
+                let parents_depth = expr.parents_depth;
                 let children_depth = expr.children_depth;
                 let indirect_selection = expr.indirect_selection;
                 let exclude = expr.exclude;
                 // ── 1️⃣  resolve method / args ────────────────────────────────
-                let (name, args) = {
-                    let mut parts = method.split('.').map(|s| s.to_string());
-                    let head = parts.next().unwrap();
-                    let nm = MethodName::from_str(&head)
-                        .unwrap_or_else(|_| MethodName::default_for(&value));
-                    (nm, parts.collect())
-                };
+                let (name, args) = resolve_method(&method, &value);
 
                 // ── 2️⃣  normalise depth flags ────────────────────────────────
                 let pd = if parents && parents_depth.is_none() {
@@ -232,7 +1745,7 @@ impl<'a> SelectorParser<'a> {
                 let exclude_expr: Option<Box<SelectExpression>> = if let Some(defs) = &exclude {
                     let exprs = defs
                         .iter()
-                        .map(|d| self.parse_definition(d))
+                        .map(|d| self.parse_definition_with_context(d, ctx))
                         .collect::<FsResult<Vec<_>>>()?;
                     match exprs.len() {
                         0 => None,
@@ -258,13 +1771,7 @@ impl<'a> SelectorParser<'a> {
             }
             AtomExpr::MethodKey(method_value) => {
                 let (m, v) = method_value.into_iter().next().unwrap();
-                let (name, args) = {
-                    let mut parts = m.split('.').map(|s| s.to_string());
-                    let head = parts.next().unwrap();
-                    let nm =
-                        MethodName::from_str(&head).unwrap_or_else(|_| MethodName::default_for(&v));
-                    (nm, parts.collect())
-                };
+                let (name, args) = resolve_method(&m, &v);
                 Ok(SelectExpression::Atom(SelectionCriteria::new(
                     name,
                     args,
@@ -278,23 +1785,506 @@ impl<'a> SelectorParser<'a> {
             }
             AtomExpr::Exclude(expr) => {
                 // A standalone exclude atom - this becomes a top-level exclude
-                let exprs = self.collect_definition_includes(&expr.exclude)?;
-                let exclude_expr = match exprs.len() {
-                    0 => return Err(fs_err!(ErrorCode::SelectorError, "Empty exclude list")),
-                    1 => exprs.into_iter().next().unwrap(),
-                    _ => SelectExpression::Or(exprs),
+                Ok(SelectExpression::Exclude(Box::new(
+                    self.parse_exclude_list(&expr.exclude, ctx)?,
+                )))
+            }
+        }
+    }
+}
+
+// The parser borrows `IoArgs`, so only the selector definitions are
+// serialized; deserialization goes through `SelectorParserSeed`, which
+// supplies the `IoArgs` to borrow.
+impl Serialize for SelectorParser<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("SelectorParser", 2)?;
+        state.serialize_field("defs", &self.defs)?;
+        state.serialize_field("max_depth", &self.max_depth)?;
+        state.end()
+    }
+}
+
+/// `DeserializeSeed` that rebuilds a `SelectorParser` borrowing the given `IoArgs`.
+pub struct SelectorParserSeed<'a>(pub &'a IoArgs);
+
+impl<'de, 'a> DeserializeSeed<'de> for SelectorParserSeed<'a> {
+    type Value = SelectorParser<'a>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        fn default_max_depth() -> usize {
+            DEFAULT_MAX_SELECTOR_DEPTH
+        }
+
+        #[derive(Deserialize)]
+        struct SerializedParser {
+            defs: BTreeMap<String, SelectorDefinition>,
+            #[serde(default = "default_max_depth")]
+            max_depth: usize,
+        }
+
+        let parsed = SerializedParser::deserialize(deserializer)?;
+        Ok(SelectorParser::new(parsed.defs, self.0).with_max_depth(parsed.max_depth))
+    }
+}
+
+/// Split `s` on characters matching `is_delimiter`, except inside `[...]`
+/// or when escaped with a backslash. Escapes are removed and empty pieces
+/// are dropped.
+fn split_unprotected(s: &str, is_delimiter: impl Fn(char) -> bool) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut bracket_depth = 0usize;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|&next| is_delimiter(next)) => {
+                current.extend(chars.next());
+            }
+            '[' => {
+                bracket_depth += 1;
+                current.push(c);
+            }
+            ']' => {
+                bracket_depth = bracket_depth.saturating_sub(1);
+                current.push(c);
+            }
+            c if bracket_depth == 0 && is_delimiter(c) => {
+                pieces.push(std::mem::take(&mut current));
+            }
+            c => current.push(c),
+        }
+    }
+    pieces.push(current);
+    pieces.retain(|piece| !piece.trim().is_empty());
+    pieces
+}
+
+/// Resolve a YAML `method:` into a method and its arguments, consulting
+/// methods registered at runtime after the built-in ones. Unknown methods
+/// fall back to the method `value` implies, as on the command line.
+fn resolve_method(method: &str, value: &str) -> (MethodName, Vec<String>) {
+    qualify_method(method).unwrap_or_else(|| {
+        let args = method.split('.').skip(1).map(str::to_string).collect();
+        (MethodName::default_for(value), args)
+    })
+}
+
+/// Split a dotted `method:` such as `source.jaffle_shop.orders` into a
+/// method and its arguments, trying progressively longer prefixes: the head
+/// as a built-in or registered method first, then longer dotted names
+/// registered at runtime. The parts after the matched prefix are the
+/// arguments.
+fn qualify_method(method: &str) -> Option<(MethodName, Vec<String>)> {
+    let parts = method.split('.').collect::<Vec<_>>();
+    (1..=parts.len()).find_map(|len| {
+        let rest = parts[len..].iter().map(|part| part.to_string());
+        if len == 1 {
+            let (name, args) = parse_method_qualifier(parts[0])?;
+            return Some((name, args.into_iter().chain(rest).collect()));
+        }
+        let prefix = parts[..len].join(".");
+        is_custom_method(&prefix).then(|| {
+            (
+                MethodName::Custom,
+                std::iter::once(prefix).chain(rest).collect(),
+            )
+        })
+    })
+}
+
+/// Rewrite a v1 `+<depth><value>` specifier as `<depth>+<value>`, keeping
+/// a leading `@`. Returns `None` if `piece` is not in the v1 format.
+fn translate_v1_specifier(piece: &str) -> Option<String> {
+    let (at, rest) = match piece.strip_prefix('@') {
+        Some(rest) => ("@", rest),
+        None => ("", piece),
+    };
+    let rest = rest.strip_prefix('+')?;
+    let value_start = rest.find(|c: char| !c.is_ascii_digit())?;
+    if value_start == 0 {
+        return None;
+    }
+    let (depth, value) = rest.split_at(value_start);
+    Some(format!("{at}{depth}+{value}"))
+}
+
+fn strip_not(method: &str) -> &str {
+    method.strip_prefix(NOT_PREFIX).unwrap_or(method)
+}
+
+/// Collect the names referenced through `selector:` atoms anywhere in `def`.
+fn collect_selector_references(def: &SelectorDefinitionValue, out: &mut BTreeSet<String>) {
+    let expr = match def {
+        SelectorDefinitionValue::String(s) => {
+            // unparsable strings reference nothing; `validate` reports them
+            if let Ok(expr) = parse_model_specifiers(slice::from_ref(s)) {
+                out.extend(
+                    expr.atoms()
+                        .filter(|criteria| criteria.method == MethodName::Selector)
+                        .map(|criteria| criteria.value.clone()),
+                );
+            }
+            return;
+        }
+        SelectorDefinitionValue::Full(expr) => expr,
+    };
+    match expr {
+        SelectorExpr::Composite(comp) => {
+            for kind in comp.kind.values() {
+                let values = match kind {
+                    CompositeKind::Union(vals)
+                    | CompositeKind::Intersection(vals)
+                    | CompositeKind::Difference(vals) => vals,
+                };
+                for value in values {
+                    collect_selector_references(value, out);
+                }
+            }
+        }
+        SelectorExpr::Atom(AtomExpr::Method(method)) => {
+            if strip_not(&method.method) == "selector" {
+                out.insert(method.value.to_string());
+            }
+            for value in method.exclude.iter().flatten() {
+                collect_selector_references(value, out);
+            }
+        }
+        SelectorExpr::Atom(AtomExpr::MethodKey(method_value)) => {
+            for (method, value) in method_value {
+                if strip_not(method) == "selector" {
+                    out.insert(value.to_string());
+                }
+            }
+        }
+        SelectorExpr::Atom(AtomExpr::Exclude(exclude)) => {
+            for value in &exclude.exclude {
+                collect_selector_references(value, out);
+            }
+        }
+    }
+}
+
+fn canonicalize_expr(expr: &SelectorExpr) -> FsResult<SelectorExpr> {
+    let canonicalize_values = |values: &[SelectorDefinitionValue]| {
+        values
+            .iter()
+            .map(SelectorParser::canonicalize)
+            .collect::<FsResult<Vec<_>>>()
+    };
+    Ok(match expr {
+        SelectorExpr::Composite(comp) => SelectorExpr::Composite(CompositeExpr {
+            kind: comp
+                .kind
+                .iter()
+                .map(|(key, kind)| {
+                    let kind = match kind {
+                        CompositeKind::Union(vals) => {
+                            CompositeKind::Union(canonicalize_values(vals)?)
+                        }
+                        CompositeKind::Intersection(vals) => {
+                            let mut vals = canonicalize_values(vals)?;
+                            // method atoms first, by method; the rest keep their order
+                            vals.sort_by_key(|value| match value {
+                                SelectorDefinitionValue::Full(SelectorExpr::Atom(
+                                    AtomExpr::Method(method),
+                                )) => (false, method.method.clone()),
+                                _ => (true, String::new()),
+                            });
+                            CompositeKind::Intersection(vals)
+                        }
+                        CompositeKind::Difference(vals) => {
+                            CompositeKind::Difference(canonicalize_values(vals)?)
+                        }
+                    };
+                    Ok((key.clone(), kind))
+                })
+                .collect::<FsResult<_>>()?,
+        }),
+        SelectorExpr::Atom(AtomExpr::Exclude(exclude)) => {
+            SelectorExpr::Atom(AtomExpr::Exclude(ExcludeAtomExpr {
+                exclude: canonicalize_values(&exclude.exclude)?,
+            }))
+        }
+        SelectorExpr::Atom(AtomExpr::MethodKey(method_value)) => {
+            if method_value.len() != 1 {
+                return err!(
+                    ErrorCode::SelectorError,
+                    "MethodKey must have exactly one key-value pair"
+                );
+            }
+            let (method, value) = method_value.iter().next().unwrap();
+            canonicalize_method_atom(&MethodAtomExpr {
+                method: method.clone(),
+                value: value.clone(),
+                childrens_parents: false,
+                parents: false,
+                children: false,
+                parents_depth: None,
+                children_depth: None,
+                indirect_selection: None,
+                exclude: None,
+            })?
+        }
+        SelectorExpr::Atom(AtomExpr::Method(method)) => canonicalize_method_atom(method)?,
+    })
+}
+
+fn canonicalize_method_atom(atom: &MethodAtomExpr) -> FsResult<SelectorExpr> {
+    let (not, method) = match atom.method.strip_prefix(NOT_PREFIX) {
+        Some(method) => (NOT_PREFIX, method),
+        None => ("", atom.method.as_str()),
+    };
+    let method = if method == "selector" {
+        method.to_string()
+    } else {
+        let (name, args) = qualify_method(method).ok_or_else(|| {
+            let head = method.split('.').next().unwrap_or_default();
+            FsError::selector(SelectorErrorKind::UnsupportedMethod(head.to_string()))
+        })?;
+        SelectionCriteria::builder(name, "")
+            .with_method_args(args)
+            .build()
+            .qualifier()
+    };
+    let exclude = match &atom.exclude {
+        Some(values) if !values.is_empty() => Some(
+            values
+                .iter()
+                .map(SelectorParser::canonicalize)
+                .collect::<FsResult<Vec<_>>>()?,
+        ),
+        _ => None,
+    };
+    Ok(SelectorExpr::Atom(AtomExpr::Method(MethodAtomExpr {
+        method: format!("{not}{method}"),
+        value: atom.value.clone(),
+        childrens_parents: atom.childrens_parents,
+        parents: atom.parents || atom.parents_depth.is_some(),
+        children: atom.children || atom.children_depth.is_some(),
+        parents_depth: atom.parents_depth.filter(|&depth| depth != u32::MAX),
+        children_depth: atom.children_depth.filter(|&depth| depth != u32::MAX),
+        indirect_selection: Some(atom.indirect_selection.unwrap_or_default()),
+        exclude,
+    })))
+}
+
+/// A node with only what its unique id tells, e.g. `model.jaffle.orders`:
+/// resource type `model`, package `jaffle` and fqn `[jaffle, orders]`.
+fn node_from_unique_id(unique_id: &str) -> Node {
+    let mut parts = unique_id.split('.');
+    let resource_type = parts.next().unwrap_or_default().to_string();
+    let fqn = parts.map(str::to_string).collect::<Vec<_>>();
+    Node {
+        unique_id: unique_id.to_string(),
+        resource_type,
+        package_name: fqn.first().cloned().unwrap_or_default(),
+        fqn,
+        ..Node::default()
+    }
+}
+
+/// The full YAML form of an expression parsed from a selector string, or
+/// `None` if it uses operators selector strings cannot express.
+fn expression_to_definition(expr: &SelectExpression) -> Option<SelectorDefinitionValue> {
+    let composite = |key: &str, exprs: &[SelectExpression]| {
+        let values = exprs
+            .iter()
+            .map(expression_to_definition)
+            .collect::<Option<Vec<_>>>()?;
+        let kind = match key {
+            "union" => CompositeKind::Union(values),
+            _ => CompositeKind::Intersection(values),
+        };
+        Some(SelectorExpr::Composite(CompositeExpr {
+            kind: [(key.to_string(), kind)].into_iter().collect(),
+        }))
+    };
+    let expr = match expr {
+        SelectExpression::Atom(criteria) => SelectorExpr::Atom(AtomExpr::Method(MethodAtomExpr {
+            method: criteria.qualifier(),
+            value: criteria.value.as_str().into(),
+            childrens_parents: criteria.childrens_parents,
+            parents: criteria.parents_depth.is_some(),
+            children: criteria.children_depth.is_some(),
+            parents_depth: criteria.parents_depth,
+            children_depth: criteria.children_depth,
+            indirect_selection: criteria.indirect,
+            exclude: None,
+        })),
+        SelectExpression::And(exprs) => composite("intersection", exprs)?,
+        SelectExpression::Or(exprs) => composite("union", exprs)?,
+        SelectExpression::Exclude(_) | SelectExpression::Not(_) | SelectExpression::Empty => {
+            return None;
+        }
+    };
+    Some(SelectorDefinitionValue::Full(expr))
+}
+
+/// A copy of `expr` with every method atom replaced by `f` applied to it;
+/// see [`SelectorParser::parse_expr_transformed`].
+fn transform_method_atoms<F>(expr: &SelectorExpr, f: &F) -> SelectorExpr
+where
+    F: Fn(&MethodAtomExpr) -> MethodAtomExpr,
+{
+    let transform_values = |values: &[SelectorDefinitionValue]| {
+        values
+            .iter()
+            .map(|value| match value {
+                SelectorDefinitionValue::Full(expr) => {
+                    SelectorDefinitionValue::Full(transform_method_atoms(expr, f))
+                }
+                SelectorDefinitionValue::String(_) => value.clone(),
+            })
+            .collect::<Vec<_>>()
+    };
+    let transform_method = |method: &MethodAtomExpr| {
+        let mut method = f(method);
+        method.exclude = method.exclude.as_deref().map(transform_values);
+        SelectorExpr::Atom(AtomExpr::Method(method))
+    };
+
+    match expr {
+        SelectorExpr::Composite(comp) => SelectorExpr::Composite(CompositeExpr {
+            kind: comp
+                .kind
+                .iter()
+                .map(|(key, kind)| {
+                    let kind = match kind {
+                        CompositeKind::Union(vals) => CompositeKind::Union(transform_values(vals)),
+                        CompositeKind::Intersection(vals) => {
+                            CompositeKind::Intersection(transform_values(vals))
+                        }
+                        CompositeKind::Difference(vals) => {
+                            CompositeKind::Difference(transform_values(vals))
+                        }
+                    };
+                    (key.clone(), kind)
+                })
+                .collect(),
+        }),
+        SelectorExpr::Atom(AtomExpr::Method(method)) => transform_method(method),
+        SelectorExpr::Atom(AtomExpr::MethodKey(method_value)) if method_value.len() == 1 => {
+            let (method, value) = method_value.iter().next().unwrap();
+            transform_method(&MethodAtomExpr {
+                method: method.clone(),
+                value: value.clone(),
+                childrens_parents: false,
+                parents: false,
+                children: false,
+                parents_depth: None,
+                children_depth: None,
+                indirect_selection: Some(IndirectSelection::default()),
+                exclude: None,
+            })
+        }
+        SelectorExpr::Atom(AtomExpr::MethodKey(_)) => expr.clone(),
+        SelectorExpr::Atom(AtomExpr::Exclude(exclude)) => {
+            SelectorExpr::Atom(AtomExpr::Exclude(ExcludeAtomExpr {
+                exclude: transform_values(&exclude.exclude),
+            }))
+        }
+    }
+}
+
+/// Point every `selector: old` atom anywhere in `def` at `new`, returning
+/// the number of atoms changed.
+fn rename_selector_references(def: &mut SelectorDefinitionValue, old: &str, new: &str) -> usize {
+    let expr = match def {
+        SelectorDefinitionValue::String(s) => {
+            let (renamed_string, renamed) = rename_selector_string_references(s, old, new);
+            *s = renamed_string;
+            return renamed;
+        }
+        SelectorDefinitionValue::Full(expr) => expr,
+    };
+    let mut renamed = 0;
+    match expr {
+        SelectorExpr::Composite(comp) => {
+            for kind in comp.kind.values_mut() {
+                let values = match kind {
+                    CompositeKind::Union(vals)
+                    | CompositeKind::Intersection(vals)
+                    | CompositeKind::Difference(vals) => vals,
                 };
-                Ok(SelectExpression::Exclude(Box::new(exclude_expr)))
+                for value in values {
+                    renamed += rename_selector_references(value, old, new);
+                }
+            }
+        }
+        SelectorExpr::Atom(AtomExpr::Method(method)) => {
+            if strip_not(&method.method) == "selector" && method.value.as_str() == old {
+                method.value = SelectorValue::from(new);
+                renamed += 1;
+            }
+            for value in method.exclude.iter_mut().flatten() {
+                renamed += rename_selector_references(value, old, new);
+            }
+        }
+        SelectorExpr::Atom(AtomExpr::MethodKey(method_value)) => {
+            for (method, value) in method_value.iter_mut() {
+                if strip_not(method) == "selector" && value.as_str() == old {
+                    *value = SelectorValue::from(new);
+                    renamed += 1;
+                }
+            }
+        }
+        SelectorExpr::Atom(AtomExpr::Exclude(exclude)) => {
+            for value in &mut exclude.exclude {
+                renamed += rename_selector_references(value, old, new);
+            }
+        }
+    }
+    renamed
+}
+
+/// Point every `selector:old` atom of the selector string `s` at `new`,
+/// keeping its graph operators and the delimiters between atoms as written.
+fn rename_selector_string_references(s: &str, old: &str, new: &str) -> (String, usize) {
+    let is_delimiter = |c: char| c == ',' || c.is_whitespace();
+    let mut result = String::with_capacity(s.len());
+    let mut renamed = 0;
+    for chunk in s.split_inclusive(is_delimiter) {
+        let piece = chunk.trim_end_matches(is_delimiter);
+        let delimiters = &chunk[piece.len()..];
+        let rewritten = parse_single_selector(piece)
+            .ok()
+            .filter(|criteria| criteria.method == MethodName::Selector && criteria.value == old)
+            .and_then(|_| {
+                let (qualifier, rest) = piece.split_once(':')?;
+                let operators = rest.strip_prefix(old)?;
+                Some(format!("{qualifier}:{new}{operators}"))
+            });
+        match rewritten {
+            Some(piece) => {
+                result.push_str(&piece);
+                renamed += 1;
             }
+            None => result.push_str(piece),
         }
+        result.push_str(delimiters);
     }
+    (result, renamed)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use dbt_schemas::schemas::selectors::{ExcludeAtomExpr, SelectorValue};
+    use crate::explain::SelectorExplanation;
+    use dbt_common::node_selector::{
+        NodeMeta, SelectorMethod, SourceStatusKind, VersionSelector, register_method,
+    };
+    use dbt_common::{io_args::StaticAnalysisOffReason, io_utils::StatusReporter, path::DbtPath};
+    use dbt_telemetry::{ExecutionPhase, NodeOutcome};
     use dbt_test_primitives::assert_contains;
+    use indexmap::IndexMap;
+    use std::sync::{Arc, Mutex};
 
     // ============================================================================
     // 1. Basic Atom Selectors
@@ -379,6 +2369,69 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    /// Test selecting a node by unique id, with and without the `node_id:` prefix.
+    /// Expects a NodeId atom for the id, the same as the string form, and an error for no id.
+    fn test_parse_node_specifier() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(BTreeMap::new(), &io_args);
+
+        let expected = parser.parse_raw("node_id:model.my_project.my_model")?;
+        let criteria = expected.atoms().next().unwrap();
+        assert_eq!(criteria.method, MethodName::NodeId);
+        assert_eq!(criteria.value, "model.my_project.my_model");
+        for spec in [
+            "model.my_project.my_model",
+            "node_id:model.my_project.my_model",
+        ] {
+            assert_eq!(parser.parse_node_specifier(spec)?, expected, "{spec}");
+        }
+        // a plain name is still an `fqn:` selector in selector strings
+        assert_eq!(
+            parser.parse_raw("my_model")?.atoms().next().unwrap().method,
+            MethodName::Fqn
+        );
+
+        for spec in ["", "node_id:"] {
+            let err = parser.parse_node_specifier(spec).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SelectorError);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test the `*` and `all` methods, in long, shorthand and string form.
+    /// Expects `Wildcard` atoms, and bare `*` or `all` values to stay `fqn:`.
+    fn test_wildcard_method() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: star
+    definition:
+      method: "*"
+      value: "*"
+  - name: all_shorthand
+    definition:
+      all: everything
+  - name: all_string
+    definition: "all:*"
+  - name: bare_all
+    definition: "all"
+"#,
+            &io_args,
+        );
+
+        for name in ["star", "all_shorthand", "all_string"] {
+            let expr = parser.parse_named(name)?;
+            let criteria = expr.atoms().next().unwrap();
+            assert_eq!(criteria.method, MethodName::Wildcard, "{name}");
+        }
+        let bare = parser.parse_named("bare_all")?;
+        assert_eq!(bare.atoms().next().unwrap().method, MethodName::Fqn);
+        Ok(())
+    }
+
     #[test]
     /// Test that MethodKey with multiple pairs fails validation.
     /// Expects an error indicating exactly one key-value pair is required.
@@ -402,22 +2455,532 @@ mod tests {
         }
     }
 
-    // ============================================================================
-    // 2. Composite Operation Basics
-    // ============================================================================
+    /// `org:<name>` selects nodes from packages named `<name>_*`.
+    struct OrgMethod;
+
+    impl SelectorMethod for OrgMethod {
+        fn matches(&self, node: &NodeMeta<'_>, value: &str, _args: &[String]) -> bool {
+            node.package_name.starts_with(&format!("{value}_"))
+        }
+    }
 
     #[test]
-    /// Test basic union and intersection operations with mixed include/exclude scenarios.
-    /// Expects Or for unions, And for intersections, with excludes nested within method criteria.
-    fn test_composite_operations() -> FsResult<()> {
-        let defs = BTreeMap::new();
+    /// Test a selector method registered at runtime, from YAML through evaluation.
+    /// Expects `org:` atoms to parse as Custom criteria and select via the registered method.
+    fn test_custom_method_end_to_end() -> FsResult<()> {
+        register_method("org", OrgMethod)?;
+        assert!(register_method("org", OrgMethod).is_err());
+        assert!(register_method("tag", OrgMethod).is_err());
+
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: acme_yaml
+    definition:
+      method: org
+      value: acme
+  - name: acme_cli
+    definition: "org:acme"
+"#,
+            &io_args,
+        );
+        parser.validate(&parser.defs["acme_yaml"].definition)?;
+
+        let node = |package: &str| Node {
+            unique_id: format!("model.{package}.m"),
+            package_name: package.to_string(),
+            ..Default::default()
+        };
+        let nodes = vec![node("acme_core"), node("acme_marts"), node("globex_core")];
+        for name in ["acme_yaml", "acme_cli"] {
+            let expr = parser.parse_named(name)?;
+            assert_eq!(expr.to_string(), "org:acme");
+            assert_eq!(
+                evaluate_select_expression(&expr, &nodes)
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+                vec!["model.acme_core.m", "model.acme_marts.m"]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test resolving dotted YAML methods such as `source.jaffle_shop.orders`.
+    /// Expects the longest-known prefix rule: built-in heads first, then longer registered names.
+    fn test_dotted_method_arguments() -> FsResult<()> {
+        assert_eq!(
+            resolve_method("source.jaffle_shop.orders", "*"),
+            (
+                MethodName::Source,
+                vec!["jaffle_shop".to_string(), "orders".to_string()]
+            )
+        );
+        assert_eq!(
+            resolve_method("config.materialized", "table"),
+            (MethodName::Config, vec!["materialized".to_string()])
+        );
+
+        register_method("acme.team", OrgMethod)?;
+        assert_eq!(
+            resolve_method("acme.team.core", "acme"),
+            (
+                MethodName::Custom,
+                vec!["acme.team".to_string(), "core".to_string()]
+            )
+        );
+        // unknown methods still fall back to the method the value implies
+        assert_eq!(
+            resolve_method("acme.other", "models/staging"),
+            (MethodName::Path, vec!["other".to_string()])
+        );
+
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: orders_source
+    definition:
+      method: source.jaffle_shop
+      value: orders
+"#,
+            &io_args,
+        );
+        parser.validate(&parser.defs["orders_source"].definition)?;
+        let expr = parser.parse_named("orders_source")?;
+        let criteria = expr.atoms().next().unwrap();
+        assert_eq!(criteria.method, MethodName::Source);
+        assert_eq!(criteria.method_args, vec!["jaffle_shop"]);
+        assert_eq!(criteria.value, "orders");
+        Ok(())
+    }
+
+    #[test]
+    /// Test YAML selectors using the `access:` method for each access level.
+    /// Expects exactly the models with that access level, and nothing for an unknown level.
+    fn test_access_selectors() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: public_models
+    definition:
+      method: access
+      value: public
+  - name: protected_models
+    definition:
+      access: protected
+  - name: private_models
+    definition: "access:private"
+  - name: invalid_access
+    definition:
+      method: access
+      value: INVALID
+"#,
+            &io_args,
+        );
+
+        let node = |name: &str, access: Option<&str>| Node {
+            unique_id: format!("model.pkg.{name}"),
+            resource_type: "model".to_string(),
+            access: access.map(str::to_string),
+            ..Default::default()
+        };
+        let nodes = vec![
+            node("orders", Some("public")),
+            node("customers", Some("protected")),
+            node("stg_orders", Some("private")),
+            node("seed_like", None),
+        ];
+        for (name, expected) in [
+            ("public_models", vec!["model.pkg.orders"]),
+            ("protected_models", vec!["model.pkg.customers"]),
+            ("private_models", vec!["model.pkg.stg_orders"]),
+            ("invalid_access", vec![]),
+        ] {
+            let expr = parser.parse_named(name)?;
+            assert!(matches!(&expr, SelectExpression::Atom(c) if c.method == MethodName::Access));
+            assert_eq!(
+                evaluate_select_expression(&expr, &nodes)
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+                expected,
+                "selector `{name}`"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test YAML selectors using the `source_status:` method in each form.
+    /// Expects SourceStatus atoms that select sources by their freshness outcome.
+    fn test_source_status_selectors() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: fresher_sources
+    definition:
+      method: source_status
+      value: fresher
+  - name: warn_sources
+    definition:
+      source_status: warn
+  - name: failing_sources
+    definition:
+      union:
+        - "source_status:error"
+        - "source_status:warn"
+"#,
+            &io_args,
+        );
+
+        let node = |name: &str, status: Option<SourceStatusKind>| Node {
+            unique_id: format!("source.pkg.raw.{name}"),
+            resource_type: "source".to_string(),
+            source_status: status,
+            ..Default::default()
+        };
+        let nodes = vec![
+            node("orders", Some(SourceStatusKind::Fresher)),
+            node("payments", Some(SourceStatusKind::Warn)),
+            node("customers", Some(SourceStatusKind::Error)),
+            node("unchecked", None),
+        ];
+        for (name, expected) in [
+            ("fresher_sources", vec!["source.pkg.raw.orders"]),
+            ("warn_sources", vec!["source.pkg.raw.payments"]),
+            (
+                "failing_sources",
+                vec!["source.pkg.raw.customers", "source.pkg.raw.payments"],
+            ),
+        ] {
+            let expr = parser.parse_named(name)?;
+            parser.validate(&parser.defs[name].definition)?;
+            assert!(
+                expr.atoms()
+                    .all(|criteria| criteria.method == MethodName::SourceStatus)
+            );
+            assert_eq!(
+                evaluate_select_expression(&expr, &nodes)
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+                expected,
+                "selector `{name}`"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test YAML selectors using the semantic layer `semantic_model:` and `saved_query:` methods.
+    /// Expects SemanticModel and SavedQuery atoms rather than an inferred `fqn:`.
+    fn test_semantic_layer_selectors() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: by_status
+    definition: "semantic_model:orders_by_status"
+  - name: weekly
+    definition:
+      method: saved_query
+      value: weekly_orders
+"#,
+            &io_args,
+        );
+
+        for (name, method, value) in [
+            ("by_status", MethodName::SemanticModel, "orders_by_status"),
+            ("weekly", MethodName::SavedQuery, "weekly_orders"),
+        ] {
+            let result = parser.parse_named(name)?;
+            if let SelectExpression::Atom(criteria) = result {
+                assert_eq!(criteria.method, method);
+                assert_eq!(criteria.value, value);
+            } else {
+                panic!("Expected Atom expression, got {result:?}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test YAML selectors using the `unit_test:` method, with and without a wildcard.
+    /// Expects UnitTest atoms whose value is passed through unchanged.
+    fn test_unit_test_selector() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: my_model_tests
+    definition: "unit_test:my_model"
+  - name: all_unit_tests
+    definition:
+      method: unit_test
+      value: "*"
+"#,
+            &io_args,
+        );
+
+        for (name, value) in [("my_model_tests", "my_model"), ("all_unit_tests", "*")] {
+            let result = parser.parse_named(name)?;
+            if let SelectExpression::Atom(criteria) = result {
+                assert_eq!(criteria.method, MethodName::UnitTest);
+                assert_eq!(criteria.value, value);
+            } else {
+                panic!("Expected Atom expression, got {result:?}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test YAML selectors using the `version:` method with a number and with `latest`.
+    /// Expects Version atoms whose value decodes to the matching `VersionSelector`.
+    fn test_version_selector() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: v2
+    definition: "version:2"
+  - name: latest
+    definition:
+      method: version
+      value: latest
+"#,
+            &io_args,
+        );
+
+        for (name, expected) in [
+            ("v2", VersionSelector::Number(2)),
+            ("latest", VersionSelector::Latest),
+        ] {
+            let result = parser.parse_named(name)?;
+            if let SelectExpression::Atom(criteria) = result {
+                assert_eq!(criteria.method, MethodName::Version);
+                assert_eq!(criteria.version_selector(), Some(expected));
+            } else {
+                panic!("Expected Atom expression, got {result:?}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test YAML selectors using the `group:` method, with and without a wildcard.
+    /// Expects Group atoms whose value is passed through unchanged.
+    fn test_group_selector() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: finance
+    definition: "group:finance"
+  - name: finance_subgroups
+    definition:
+      method: group
+      value: "finance.*"
+"#,
+            &io_args,
+        );
+
+        for (name, value) in [("finance", "finance"), ("finance_subgroups", "finance.*")] {
+            let result = parser.parse_named(name)?;
+            if let SelectExpression::Atom(criteria) = result {
+                assert_eq!(criteria.method, MethodName::Group);
+                assert!(criteria.method_args.is_empty());
+                assert_eq!(criteria.value, value);
+            } else {
+                panic!("Expected Atom expression, got {result:?}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test that config selectors carry the config key in `method_args`.
+    /// Expects `config.materialized:table` to parse identically from CLI and YAML forms.
+    fn test_config_selector() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let from_string = parser.parse_definition(&SelectorDefinitionValue::String(
+            "config.materialized:table".to_string(),
+        ))?;
+        let from_yaml = parser.parse_definition(&SelectorDefinitionValue::Full(
+            SelectorExpr::Atom(AtomExpr::Method(MethodAtomExpr {
+                method: "config.materialized".to_string(),
+                value: SelectorValue::from("table"),
+                childrens_parents: false,
+                parents: false,
+                children: false,
+                parents_depth: None,
+                children_depth: None,
+                indirect_selection: Some(IndirectSelection::default()),
+                exclude: None,
+            })),
+        ))?;
+        let mut method_value = BTreeMap::new();
+        method_value.insert(
+            "config.materialized".to_string(),
+            SelectorValue::from("table"),
+        );
+        let from_method_key = parser.parse_atom(&AtomExpr::MethodKey(method_value))?;
+
+        for result in [&from_string, &from_yaml, &from_method_key] {
+            if let SelectExpression::Atom(criteria) = result {
+                assert_eq!(criteria.method, MethodName::Config);
+                assert_eq!(criteria.method_args, vec!["materialized".to_string()]);
+                assert_eq!(criteria.value, "table");
+            } else {
+                panic!("Expected Atom expression");
+            }
+        }
+        assert_eq!(from_string, from_yaml);
+        Ok(())
+    }
+
+    #[test]
+    /// Test that `exposure:` and `metric:` string selectors map to their own methods.
+    /// Expects the method to be recognised rather than falling back to FQN.
+    fn test_exposure_and_metric_selectors() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        for (selector, method, value) in [
+            (
+                "exposure:my_dashboard",
+                MethodName::Exposure,
+                "my_dashboard",
+            ),
+            ("metric:revenue", MethodName::Metric, "revenue"),
+        ] {
+            let result =
+                parser.parse_definition(&SelectorDefinitionValue::String(selector.to_string()))?;
+            if let SelectExpression::Atom(criteria) = result {
+                assert_eq!(criteria.method, method);
+                assert_eq!(criteria.value, value);
+            } else {
+                panic!("Expected Atom expression");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test parsing a YAML expression whose method atoms are rewritten first.
+    /// Expects every `fqn` value, including shorthand and excluded atoms, to gain the prefix.
+    fn test_parse_expr_transformed() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: tenant
+    definition:
+      union:
+        - method: fqn
+          value: orders
+          children: true
+        - fqn: customers
+        - method: tag
+          value: nightly
+          exclude:
+            - method: fqn
+              value: legacy
+  - name: expected
+    definition:
+      union:
+        - method: fqn
+          value: myproject.orders
+          children: true
+        - fqn: myproject.customers
+        - method: tag
+          value: nightly
+          exclude:
+            - method: fqn
+              value: myproject.legacy
+"#,
+            &io_args,
+        );
+        let SelectorDefinitionValue::Full(expr) = &parser.defs["tenant"].definition else {
+            panic!("Expected a full YAML definition");
+        };
+
+        let transformed = parser.parse_expr_transformed(expr, |method| {
+            let mut method = method.clone();
+            if method.method == "fqn" {
+                method.value = SelectorValue::from(format!("myproject.{}", method.value));
+            }
+            method
+        })?;
+        assert_eq!(transformed, parser.parse_named("expected")?);
+        assert_eq!(
+            parser.parse_expr_transformed(expr, MethodAtomExpr::clone)?,
+            parser.parse_named("tenant")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test parsing raw selector strings without wrapping them in a definition.
+    /// Expects the same expressions as `parse_definition`, and a union for several strings.
+    fn test_parse_raw() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(BTreeMap::new(), &io_args);
+
+        for selector in [
+            "model_a",
+            "tag:nightly",
+            "+orders+2",
+            "exposure:my_dashboard",
+            "tag:a,tag:b",
+            "model_a model_b",
+        ] {
+            assert_eq!(
+                parser.parse_raw(selector)?,
+                parser.parse_definition(&SelectorDefinitionValue::String(selector.to_string()))?
+            );
+        }
+        if let SelectExpression::Atom(criteria) = parser.parse_raw("model_a")? {
+            assert_eq!(criteria.method, MethodName::Fqn);
+            assert_eq!(criteria.value, "model_a");
+        } else {
+            panic!("Expected Atom expression");
+        }
+
+        assert_eq!(parser.parse_raw_many(&[])?, SelectExpression::Empty);
+        assert_eq!(
+            parser.parse_raw_many(&["tag:nightly", "metric:revenue"])?,
+            SelectExpression::Or(vec![
+                parser.parse_raw("tag:nightly")?,
+                parser.parse_raw("metric:revenue")?,
+            ])
+        );
+        assert!(parser.parse_raw_many(&["tag:nightly", "@"]).is_err());
+        Ok(())
+    }
+
+    // ============================================================================
+    // 2. Composite Operation Basics
+    // ============================================================================
+
+    #[test]
+    /// Test basic union and intersection operations with mixed include/exclude scenarios.
+    /// Expects Or for unions, And for intersections, with excludes nested within method criteria.
+    fn test_composite_operations() -> FsResult<()> {
+        let defs = BTreeMap::new();
         let io_args = IoArgs::default();
         let parser = SelectorParser::new(defs, &io_args);
 
         // Test union
         let union_result = parser.parse_composite(&CompositeExpr {
             kind: {
-                let mut m = BTreeMap::new();
+                let mut m = IndexMap::new();
                 m.insert(
                     "union".to_string(),
                     CompositeKind::Union(vec![
@@ -438,7 +3001,7 @@ mod tests {
         // Test intersection
         let intersection_result = parser.parse_composite(&CompositeExpr {
             kind: {
-                let mut m = BTreeMap::new();
+                let mut m = IndexMap::new();
                 m.insert(
                     "intersection".to_string(),
                     CompositeKind::Intersection(vec![
@@ -459,7 +3022,7 @@ mod tests {
         // Test composite with excludes - excludes should be nested within the include
         let composite_with_exclude = parser.parse_composite(&CompositeExpr {
             kind: {
-                let mut m = BTreeMap::new();
+                let mut m = IndexMap::new();
                 m.insert(
                     "union".to_string(),
                     CompositeKind::Union(vec![
@@ -613,8 +3176,8 @@ mod tests {
     }
 
     #[test]
-    /// Test that standalone exclude atoms (not in composite) are rejected.
-    /// Expects an error indicating top-level excludes are not allowed in YAML selectors.
+    /// Test that exclude atoms outside a definition's top level or a composite are rejected.
+    /// Expects an error naming the places where excludes are allowed.
     fn test_standalone_exclude() {
         let defs = BTreeMap::new();
         let io_args = IoArgs::default();
@@ -629,433 +3192,3130 @@ mod tests {
             assert_eq!(e.code, ErrorCode::SelectorError);
             assert_contains!(
                 e.to_string(),
-                "Top level exclude not allowed in YAML selectors"
+                "`exclude` is only allowed as a selector's whole definition"
             );
         }
     }
 
-    // Helper to create a string selector
-    fn s(val: &str) -> SelectorDefinitionValue {
-        SelectorDefinitionValue::String(val.to_string())
-    }
-
-    // Helper to create an exclude block
-    fn exclude(vals: Vec<&str>) -> SelectorDefinitionValue {
-        let exclude_vals = vals.into_iter().map(s).collect();
-        SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Exclude(ExcludeAtomExpr {
-            exclude: exclude_vals,
-        })))
-    }
+    #[test]
+    /// Test atoms whose method carries a `not:` prefix.
+    /// Expects a Not expression wrapping the atom parsed without the prefix.
+    fn test_not_prefix() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly
+    definition: "tag:nightly"
+  - name: not_tag
+    definition:
+      method: "not:tag"
+      value: nightly
+  - name: not_method_key
+    definition:
+      "not:tag": nightly
+  - name: not_selector
+    definition:
+      method: "not:selector"
+      value: nightly
+"#,
+            &io_args,
+        );
 
-    // Helper to create a composite selector
-    fn composite(kind: &str, items: Vec<SelectorDefinitionValue>) -> SelectorDefinitionValue {
-        let mut m = BTreeMap::new();
-        let k = match kind {
-            "union" => CompositeKind::Union(items),
-            "intersection" => CompositeKind::Intersection(items),
-            _ => panic!("Unknown kind"),
-        };
-        m.insert(kind.to_string(), k);
-        SelectorDefinitionValue::Full(SelectorExpr::Composite(CompositeExpr { kind: m }))
+        for name in ["not_tag", "not_method_key", "not_selector"] {
+            let result = parser.parse_named(name)?;
+            assert!(
+                matches!(result, SelectExpression::Not(_)),
+                "selector `{name}`: {result:?}"
+            );
+            assert_eq!(result.to_string(), "not(tag:nightly)");
+        }
+        assert_eq!(
+            parser.list_all_names()?.first().map(String::as_str),
+            Some("nightly")
+        );
+        Ok(())
     }
 
     #[test]
-    /// Test basic union with single exclude: union: [A, exclude: [B]]
-    /// Expects And([Or([A]), Exclude(B)]) - union includes wrapped with exclude.
-    fn test_basic_union_with_exclude() -> FsResult<()> {
-        let defs = BTreeMap::new();
-        let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
+    /// Test parsing `--select` values with one and two comma-separated selectors.
+    /// Expects commas to intersect and spaces to union, as in `parse_model_specifiers`.
+    fn test_parse_cli_select() -> FsResult<()> {
+        let single = SelectorParser::parse_cli_select("tag:a")?;
+        assert_eq!(single, parse_model_specifiers(&["tag:a".to_string()])?);
+
+        let double = SelectorParser::parse_cli_select("tag:a,tag:b")?;
+        assert_eq!(
+            double,
+            parse_model_specifiers(&["tag:a,tag:b".to_string()])?
+        );
+        assert!(matches!(&double, SelectExpression::And(exprs) if exprs.len() == 2));
 
-        // union: [A, exclude: [B]]
-        // Logic: (A) AND NOT (B)
+        let union = SelectorParser::parse_cli_select("tag:a,tag:b  tag:c")?;
+        assert_eq!(
+            union,
+            parse_model_specifiers(&["tag:a,tag:b".to_string(), "tag:c".to_string()])?
+        );
 
-        let def = composite("union", vec![s("tag:A"), exclude(vec!["tag:B"])]);
-        let result = parser.parse_definition(&def)?;
+        assert!(SelectorParser::parse_cli_select(" , ").is_err());
+        Ok(())
+    }
 
-        if let SelectExpression::And(exprs) = result {
-            // [Or([A]), Exclude(B)]
+    #[test]
+    /// Test commas protected by brackets or a backslash in `--select` values.
+    /// Expects the comma to stay part of the selector value.
+    fn test_parse_cli_select_protected_commas() -> FsResult<()> {
+        let bracketed = SelectorParser::parse_cli_select("path:models/[a,b]*")?;
+        if let SelectExpression::Atom(criteria) = &bracketed {
+            assert_eq!(criteria.method, MethodName::Path);
+            assert_eq!(criteria.value, "models/[a,b]*");
+        } else {
+            panic!("Expected Atom expression, got {bracketed:?}");
+        }
+
+        let escaped = SelectorParser::parse_cli_select(r"tag:a\,b,tag:c")?;
+        if let SelectExpression::And(exprs) = &escaped {
             assert_eq!(exprs.len(), 2);
-            if let SelectExpression::Or(inc) = &exprs[0] {
-                assert_eq!(inc.len(), 1);
-                if let SelectExpression::Atom(c) = &inc[0] {
-                    assert_eq!(c.value, "A");
-                }
-            }
-            if let SelectExpression::Exclude(exc) = &exprs[1] {
-                if let SelectExpression::Atom(c) = &**exc {
-                    assert_eq!(c.value, "B");
-                }
-            }
+            assert_eq!(exprs[0].to_string(), "tag:a,b");
+            assert_eq!(exprs[1].to_string(), "tag:c");
         } else {
-            panic!("Expected And(Or([A]), Exclude(B)), got {:?}", result);
+            panic!("Expected And expression, got {escaped:?}");
         }
         Ok(())
     }
 
     #[test]
-    /// Test basic intersection with single exclude: intersection: [A, exclude: [B]]
-    /// Expects And([And([A]), Exclude(B)]) - intersection includes wrapped with exclude.
-    fn test_basic_intersection_with_exclude() -> FsResult<()> {
-        let defs = BTreeMap::new();
+    /// Test loading `selectors.yml` from a project directory.
+    /// Expects its selectors to be parsed, and a project without the file to load no selectors.
+    fn test_from_file() -> FsResult<()> {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
-
-        // intersection: [A, exclude: [B]]
-        // Logic: (A) AND NOT (B)
+        let project = tempfile::tempdir().unwrap();
+
+        let empty = SelectorParser::from_file(project.path(), &io_args)?;
+        assert!(empty.defs.is_empty());
+
+        std::fs::write(
+            project.path().join(DBT_SELECTORS_YML),
+            r#"
+selectors:
+  - name: nightly
+    definition: tag:nightly
+"#,
+        )
+        .unwrap();
+        let parser = SelectorParser::from_file(project.path(), &io_args)?;
+        assert_eq!(parser.parse_named("nightly")?.to_string(), "tag:nightly");
+
+        std::fs::write(project.path().join(DBT_SELECTORS_YML), "selectors: [").unwrap();
+        assert!(SelectorParser::from_file(project.path(), &io_args).is_err());
+        Ok(())
+    }
 
-        let def = composite("intersection", vec![s("tag:A"), exclude(vec!["tag:B"])]);
-        let result = parser.parse_definition(&def)?;
+    #[test]
+    /// Test adding a command line selector to the selectors from YAML.
+    /// Expects `__cli_override__` to resolve, including references to YAML selectors.
+    fn test_with_cli_override() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let yaml = r#"
+selectors:
+  - name: nightly
+    definition: tag:nightly
+"#;
+        let parser = parser_from_yaml(yaml, &io_args).with_cli_override(None)?;
+        assert!(parser.get_definition(CLI_OVERRIDE_SELECTOR).is_none());
+
+        let parser = parser_from_yaml(yaml, &io_args).with_cli_override(Some("tag:hourly"))?;
+        assert_eq!(
+            parser.parse_named(CLI_OVERRIDE_SELECTOR)?.to_string(),
+            "tag:hourly"
+        );
+        assert_eq!(parser.parse_named("nightly")?.to_string(), "tag:nightly");
+
+        let parser = parser_from_yaml(yaml, &io_args).with_cli_override(Some(
+            r#"
+intersection:
+  - method: selector
+    value: nightly
+  - resource_type: model
+"#,
+        ))?;
+        assert_eq!(
+            parser.parse_named(CLI_OVERRIDE_SELECTOR)?,
+            SelectExpression::And(vec![
+                parser.parse_named("nightly")?,
+                parse_single_selector("resource_type:model").map(SelectExpression::Atom)?,
+            ])
+        );
 
-        if let SelectExpression::And(exprs) = result {
-            // [And([A]), Exclude(B)]
-            assert_eq!(exprs.len(), 2);
-            if let SelectExpression::And(inc) = &exprs[0] {
-                if let SelectExpression::Atom(c) = &inc[0] {
-                    assert_eq!(c.value, "A");
-                }
-            }
-            if let SelectExpression::Exclude(exc) = &exprs[1] {
-                if let SelectExpression::Atom(c) = &**exc {
-                    assert_eq!(c.value, "B");
-                }
-            }
-        } else {
-            panic!("Expected And(And([A]), Exclude(B)), got {:?}", result);
-        }
+        let err = parser_from_yaml(yaml, &io_args)
+            .with_cli_override(Some("union: ["))
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "Failed to parse the command line selector");
+
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join(DBT_SELECTORS_YML), yaml).unwrap();
+        let parser = SelectorParser::load_project_selectors(
+            project.path(),
+            &io_args,
+            Some("{method: selector, value: nightly}"),
+        )?;
+        assert_eq!(parser.defs.len(), 2);
+        assert_eq!(
+            parser.parse_named(CLI_OVERRIDE_SELECTOR)?,
+            parser.parse_named("nightly")?
+        );
         Ok(())
     }
 
     #[test]
-    /// Test union with multiple exclude blocks: union: [A, exclude: [B], exclude: [C]]
-    /// Expects And([Or([A]), Exclude(Or([B, C]))]) - multiple excludes combined as Or.
-    fn test_multiple_excludes_union() -> FsResult<()> {
-        let defs = BTreeMap::new();
+    /// Test composites without any selector to combine.
+    /// Expects an error naming the composite and the selector it appears in, without evaluating anything.
+    fn test_empty_composite_is_rejected_at_parse_time() {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: empty_union
+    definition:
+      union: []
+  - name: empty_intersection
+    definition:
+      intersection: []
+  - name: only_excludes
+    definition:
+      intersection:
+        - exclude:
+            - tag:slow
+  - name: nested
+    definition:
+      union:
+        - tag:nightly
+        - intersection: []
+  - name: outer
+    definition:
+      method: selector
+      value: empty_union
+"#,
+            &io_args,
+        );
 
-        // union: [A, exclude: [B], exclude: [C]]
-        // Logic: (A) AND NOT (B OR C)
+        for (name, message) in [
+            (
+                "empty_union",
+                "Empty `union` list in selector `empty_union`: Empty composite expression",
+            ),
+            (
+                "empty_intersection",
+                "Empty `intersection` list in selector `empty_intersection`: Empty composite expression",
+            ),
+            (
+                "nested",
+                "Empty `intersection` list in selector `nested`: Empty composite expression",
+            ),
+            (
+                "outer",
+                "Empty `union` list in selector `outer -> empty_union`: Empty composite expression",
+            ),
+        ] {
+            let err = parser.parse_named(name).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SelectorError);
+            assert_eq!(
+                err.selector_kind(),
+                Some(&SelectorErrorKind::EmptyComposite),
+                "{name}"
+            );
+            assert_eq!(err.to_string(), message);
+        }
 
-        let def = composite(
-            "union",
-            vec![s("tag:A"), exclude(vec!["tag:B"]), exclude(vec!["tag:C"])],
+        let err = parser.parse_named("only_excludes").unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_eq!(
+            err.to_string(),
+            "`intersection` list has only `exclude` values and nothing to exclude them from in selector `only_excludes`"
         );
-        let result = parser.parse_definition(&def)?;
 
-        if let SelectExpression::And(exprs) = result {
-            // [Or([A]), Exclude(Or([B, C]))]
-            let ex = &exprs[1];
-            if let SelectExpression::Exclude(inner) = ex {
-                if let SelectExpression::Or(list) = &**inner {
-                    let vals: Vec<String> = list
-                        .iter()
-                        .map(|e| {
-                            if let SelectExpression::Atom(c) = e {
-                                c.value.clone()
-                            } else {
-                                "".to_string()
-                            }
-                        })
-                        .collect();
-                    assert!(vals.contains(&"B".to_string()));
-                    assert!(vals.contains(&"C".to_string()));
-                } else {
-                    panic!("Expected Or inside Exclude");
-                }
-            } else {
-                panic!("Expected Exclude");
-            }
-        } else {
-            panic!("Expected And");
+        let err = parser
+            .parse_composite(&CompositeExpr {
+                kind: IndexMap::from([("union".to_string(), CompositeKind::Union(vec![]))]),
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.selector_kind(),
+            Some(&SelectorErrorKind::EmptyComposite)
+        );
+        assert_eq!(
+            err.to_string(),
+            "Empty `union` list: Empty composite expression"
+        );
+        let err = parser
+            .validate(&parser.defs["empty_union"].definition)
+            .unwrap_err();
+        assert_eq!(
+            err.selector_kind(),
+            Some(&SelectorErrorKind::EmptyComposite)
+        );
+    }
+
+    #[test]
+    /// Test the `difference:` composite of exactly two selectors.
+    /// Expects the first selector minus the second, and an error for any other number of values.
+    fn test_difference_composite() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly_not_slow
+    definition:
+      difference:
+        - tag:nightly
+        - method: tag
+          value: slow
+  - name: one_value
+    definition:
+      difference:
+        - tag:nightly
+  - name: three_values
+    definition:
+      difference:
+        - tag:nightly
+        - tag:slow
+        - tag:legacy
+"#,
+            &io_args,
+        );
+
+        let expected = difference(
+            SelectExpression::Atom(parse_single_selector("tag:nightly")?),
+            SelectExpression::Atom(parse_single_selector("tag:slow")?),
+        );
+        assert_eq!(parser.parse_named("nightly_not_slow")?, expected);
+
+        for (name, found) in [("one_value", 1), ("three_values", 3)] {
+            let err = parser.parse_named(name).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SelectorError);
+            assert_contains!(
+                err.to_string(),
+                &format!("`difference` takes exactly two selectors, found {found}")
+            );
+            assert!(parser.validate(&parser.defs[name].definition).is_err());
         }
         Ok(())
     }
 
     #[test]
-    /// Test intersection with multiple exclude blocks: intersection: [A, exclude: [B], exclude: [C]]
-    /// Expects And([And([A]), Exclude(Or([B, C]))]) - multiple excludes combined as Or.
-    fn test_multiple_excludes_intersection() -> FsResult<()> {
-        let defs = BTreeMap::new();
+    /// Test a selector whose whole definition is an `exclude:` block.
+    /// Expects an Exclude expression over the union of the listed values.
+    fn test_top_level_exclude() -> FsResult<()> {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: skip_slow
+    definition:
+      exclude:
+        - tag:slow
+  - name: skip_slow_and_legacy
+    definition:
+      exclude:
+        - tag:slow
+        - method: path
+          value: models/legacy
+"#,
+            &io_args,
+        );
 
-        // intersection: [A, exclude: [B], exclude: [C]]
-        // Logic: (A) AND NOT (B OR C)
+        assert_eq!(
+            parser.parse_named("skip_slow")?.to_string(),
+            "exclude(tag:slow)"
+        );
+        assert_eq!(
+            parser.parse_named("skip_slow_and_legacy")?.to_string(),
+            "exclude(tag:slow path:models/legacy)"
+        );
+        assert_eq!(
+            parser.parse_definition(&parser.defs["skip_slow"].definition)?,
+            parser.parse_named("skip_slow")?
+        );
 
-        let def = composite(
-            "intersection",
-            vec![s("tag:A"), exclude(vec!["tag:B"]), exclude(vec!["tag:C"])],
+        // still rejected when nested inside a method's exclude list
+        let nested = parser_from_yaml(
+            r#"
+selectors:
+  - name: nested
+    definition:
+      method: tag
+      value: nightly
+      exclude:
+        - exclude:
+            - tag:slow
+"#,
+            &io_args,
         );
-        let result = parser.parse_definition(&def)?;
+        assert!(nested.parse_named("nested").is_err());
+        Ok(())
+    }
 
-        if let SelectExpression::And(exprs) = result {
-            // [And([A]), Exclude(Or([B, C]))]
-            let ex = &exprs[1];
-            if let SelectExpression::Exclude(inner) = ex {
-                if let SelectExpression::Or(list) = &**inner {
-                    let vals: Vec<String> = list
-                        .iter()
-                        .map(|e| {
-                            if let SelectExpression::Atom(c) = e {
-                                c.value.clone()
-                            } else {
-                                "".to_string()
-                            }
-                        })
-                        .collect();
-                    assert!(vals.contains(&"B".to_string()));
-                    assert!(vals.contains(&"C".to_string()));
-                } else {
-                    panic!("Expected Or inside Exclude");
-                }
-            }
+    fn test_node(name: &str, dir: &str, tags: &[&str]) -> TestNode {
+        TestNode {
+            unique_id: format!("model.pkg.{name}"),
+            resource_type: "model".to_string(),
+            fqn: vec!["pkg".to_string(), dir.to_string(), name.to_string()],
+            path: format!("models/{dir}/{name}.sql"),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
         }
-        Ok(())
     }
 
-    // ============================================================================
-    // 4. Complex Nested Logic
-    // ============================================================================
+    fn unique_ids(nodes: Vec<&TestNode>) -> Vec<&str> {
+        nodes.iter().map(|node| node.unique_id.as_str()).collect()
+    }
 
     #[test]
-    /// Test complex nested structure: union of intersections with excludes.
-    /// Expects Or([And([A, And([Or([B,C]), Exclude(D)])]), And([E,F])]) - deeply nested excludes.
-    fn test_union_of_intersections_with_exclude() -> FsResult<()> {
-        // Mimics the user case structure but generic
-        // union:
-        //   - intersection: [A, union: [B, C, exclude: [D]]]
-        //   - intersection: [E, F]
-
-        let defs = BTreeMap::new();
+    /// Test evaluating named selectors against an in-memory list of nodes.
+    /// Expects exactly the nodes matched by the resolved expression, in input order.
+    fn test_test_selector() -> FsResult<()> {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
-
-        let inner_union = composite(
-            "union",
-            vec![s("tag:B"), s("tag:C"), exclude(vec!["tag:D"])],
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly_marts
+    definition:
+      intersection:
+        - tag:nightly
+        - path:models/marts
+  - name: nightly_except_staging
+    definition:
+      union:
+        - tag:nightly
+        - exclude:
+            - fqn:pkg.staging
+  - name: inherited
+    definition:
+      method: selector
+      value: nightly_marts
+"#,
+            &io_args,
         );
+        let nodes = vec![
+            test_node("stg_orders", "staging", &["nightly"]),
+            test_node("orders", "marts", &["nightly"]),
+            test_node("customers", "marts", &["hourly"]),
+        ];
+
+        assert_eq!(
+            unique_ids(parser.test_selector("nightly_marts", &nodes)?),
+            vec!["model.pkg.orders"]
+        );
+        assert_eq!(
+            unique_ids(parser.test_selector("nightly_except_staging", &nodes)?),
+            vec!["model.pkg.orders"]
+        );
+        assert_eq!(
+            unique_ids(parser.test_selector("inherited", &nodes)?),
+            vec!["model.pkg.orders"]
+        );
+        assert!(parser.test_selector("missing", &nodes).is_err());
+        Ok(())
+    }
 
-        let intersection1 = composite("intersection", vec![s("tag:A"), inner_union]);
+    #[derive(Default)]
+    struct MockStatusReporter {
+        warnings: Mutex<Vec<(ErrorCode, String)>>,
+    }
 
-        let intersection2 = composite("intersection", vec![s("tag:E"), s("tag:F")]);
+    impl StatusReporter for MockStatusReporter {
+        fn collect_error(&self, _error: &FsError) {}
 
-        let top = composite("union", vec![intersection1, intersection2]);
+        fn collect_warning(&self, warning: &FsError) {
+            self.warnings
+                .lock()
+                .unwrap()
+                .push((warning.code, warning.to_string()));
+        }
 
-        let result = parser.parse_definition(&top)?;
+        fn collect_node_evaluation(
+            &self,
+            _unique_id: &str,
+            _execution_phase: ExecutionPhase,
+            _node_outcome: NodeOutcome,
+            _upstream_target: Option<(String, String, bool)>,
+            _static_analysis_off_reason: (Option<StaticAnalysisOffReason>, dbt_yaml::Span),
+        ) {
+        }
 
-        // Structure:
-        // Or([
-        //   And([ A, And([ Or([B, C]), Exclude(D) ]) ]),  <-- Intersection 1
-        //   And([ E, F ])                                 <-- Intersection 2
-        // ])
+        fn show_progress(&self, _action: &str, _target: &str, _description: Option<&str>) {}
 
-        if let SelectExpression::Or(top_list) = result {
-            assert_eq!(top_list.len(), 2);
+        fn bulk_publish_empty(&self, _file_paths: Vec<DbtPath>) {}
+    }
 
-            // Check Intersection 1
-            if let SelectExpression::And(i1) = &top_list[0] {
-                // A, InnerUnion
-                if let SelectExpression::Atom(a) = &i1[0] {
-                    assert_eq!(a.value, "A");
-                }
-                if let SelectExpression::And(u) = &i1[1] {
-                    // Or([B,C]), Exclude(D)
-                    if let SelectExpression::Or(bc) = &u[0] {
-                        assert_eq!(bc.len(), 2);
-                    }
-                    if let SelectExpression::Exclude(d) = &u[1] {
-                        if let SelectExpression::Atom(da) = &**d {
-                            assert_eq!(da.value, "D");
-                        }
-                    }
+    #[test]
+    /// Test diffing the nodes a selector picks before and after a YAML change.
+    /// Expects nodes only matching the new tag as added and nodes only matching the old one as removed.
+    fn test_selector_diff() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let before = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly
+    definition:
+      method: tag
+      value: old
+"#,
+            &io_args,
+        );
+        let after = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly
+    definition:
+      method: tag
+      value: new
+"#,
+            &io_args,
+        );
+        let nodes = vec![
+            test_node("legacy", "marts", &["old"]),
+            test_node("fresh", "marts", &["new"]),
+            test_node("both", "marts", &["old", "new"]),
+            test_node("neither", "marts", &[]),
+        ];
+
+        let diff = SelectorParser::diff(&before, &after, "nightly", &nodes)?;
+        assert_eq!(diff.added, vec![&nodes[1]]);
+        assert_eq!(diff.removed, vec![&nodes[0]]);
+
+        let reverse = SelectorParser::diff(&after, &before, "nightly", &nodes)?;
+        assert_eq!(reverse.added, diff.removed);
+        assert_eq!(reverse.removed, diff.added);
+
+        assert!(SelectorParser::diff(&before, &before, "nightly", &nodes)?.is_empty());
+        assert!(SelectorParser::diff(&before, &after, "missing", &nodes).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test warning about selectors that select no nodes.
+    /// Expects a SelectorError warning for an empty result only when the flag is enabled.
+    fn test_warn_on_empty_result() -> FsResult<()> {
+        let reporter = Arc::new(MockStatusReporter::default());
+        let io_args = IoArgs {
+            status_reporter: Some(reporter.clone()),
+            ..IoArgs::default()
+        };
+        let defs = BTreeMap::from([
+            with_default("typo", "tag:nighly", None),
+            with_default("nightly", "tag:nightly", None),
+        ]);
+        let nodes = vec![test_node("orders", "marts", &["nightly"])];
+
+        let quiet = SelectorParser::new(defs.clone(), &io_args);
+        assert!(quiet.test_selector("typo", &nodes)?.is_empty());
+        assert!(reporter.warnings.lock().unwrap().is_empty());
+
+        let parser = SelectorParser::new(defs, &io_args).with_warn_on_empty_result(true);
+        assert_eq!(parser.test_selector("nightly", &nodes)?.len(), 1);
+        assert!(reporter.warnings.lock().unwrap().is_empty());
+
+        assert!(parser.test_selector("typo", &nodes)?.is_empty());
+        let warnings = reporter.warnings.lock().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].0, ErrorCode::SelectorError);
+        assert_contains!(
+            warnings[0].1,
+            "Selector `typo` (tag:nighly) does not select any nodes"
+        );
+        Ok(())
+    }
+
+    /// Sets environment variables for the duration of a test and removes
+    /// them again when dropped.
+    struct EnvVars(Vec<&'static str>);
+
+    impl EnvVars {
+        fn set(vars: &[(&'static str, &str)]) -> Self {
+            for (key, value) in vars {
+                // SAFETY: only this test sets `DBT_SELECTOR_*` variables, and
+                // every key is unique to it.
+                unsafe {
+                    #[allow(clippy::disallowed_methods)]
+                    std::env::set_var(key, value);
                 }
             }
+            EnvVars(vars.iter().map(|(key, _)| *key).collect())
+        }
+    }
 
-            // Check Intersection 2
-            if let SelectExpression::And(i2) = &top_list[1] {
-                assert_eq!(i2.len(), 2);
+    impl Drop for EnvVars {
+        fn drop(&mut self) {
+            for key in &self.0 {
+                // SAFETY: see `EnvVars::set`.
+                unsafe { std::env::remove_var(key) };
             }
         }
-
-        Ok(())
     }
 
     #[test]
-    /// Test complex nested structure: intersection of unions with excludes.
-    /// Expects And([And([Or([A]), Exclude(B)]), And([Or([C]), Exclude(D)])]) - multiple union/exclude pairs.
-    fn test_intersection_of_unions_with_exclude() -> FsResult<()> {
-        // intersection:
-        //   - union: [A, exclude: [B]]
-        //   - union: [C, exclude: [D]]
-
-        let defs = BTreeMap::new();
-        let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
+    /// Test adding selectors from `DBT_SELECTOR_<NAME>` environment variables.
+    /// Expects new and overriding selectors with a warning, and no change for an invalid value.
+    fn test_augment_from_env() -> FsResult<()> {
+        let reporter = Arc::new(MockStatusReporter::default());
+        let io_args = IoArgs {
+            status_reporter: Some(reporter.clone()),
+            ..IoArgs::default()
+        };
+        let defs = BTreeMap::from([
+            with_default("env_test_nightly", "tag:nightly", None),
+            inherits_from("env_test_child", "env_test_nightly"),
+        ]);
+
+        {
+            let _vars = EnvVars::set(&[
+                ("DBT_SELECTOR_ENV_TEST_NIGHTLY", "tag:hotfix"),
+                ("DBT_SELECTOR_ENV_TEST_ADDED", "+orders"),
+            ]);
+            let mut parser = SelectorParser::new(defs.clone(), &io_args);
+            assert_eq!(parser.augment_from_env()?, 2);
+            assert_eq!(parser.parse_named("env_test_added")?.to_string(), "+orders");
+            assert_eq!(
+                parser.parse_named("env_test_child")?.to_string(),
+                "tag:hotfix"
+            );
 
-        let u1 = composite("union", vec![s("tag:A"), exclude(vec!["tag:B"])]);
-        let u2 = composite("union", vec![s("tag:C"), exclude(vec!["tag:D"])]);
+            let warnings = reporter.warnings.lock().unwrap();
+            assert_eq!(warnings.len(), 1);
+            assert_contains!(
+                warnings[0].1,
+                "Selector `env_test_nightly` is overridden by environment variable `DBT_SELECTOR_ENV_TEST_NIGHTLY`"
+            );
+        }
 
-        let top = composite("intersection", vec![u1, u2]);
+        {
+            let _vars = EnvVars::set(&[("DBT_SELECTOR_ENV_TEST_BROKEN", "@")]);
+            let mut parser = SelectorParser::new(defs.clone(), &io_args);
+            let err = parser.augment_from_env().unwrap_err();
+            assert_eq!(err.code, ErrorCode::SelectorError);
+            assert_contains!(err.to_string(), "DBT_SELECTOR_ENV_TEST_BROKEN");
+            assert_eq!(parser.defs.len(), 2);
+        }
 
-        let result = parser.parse_definition(&top)?;
+        let mut parser = SelectorParser::new(defs, &io_args);
+        assert_eq!(parser.augment_from_env()?, 0);
+        assert_eq!(
+            parser.parse_named("env_test_child")?.to_string(),
+            "tag:nightly"
+        );
+        Ok(())
+    }
 
-        // And([
-        //   And([ Or([A]), Exclude(B) ]),
-        //   And([ Or([C]), Exclude(D) ])
-        // ])
+    #[test]
+    /// Test parsing every defined selector in one pass.
+    /// Expects broken selectors to report errors without hiding independent successes.
+    fn test_parse_all() {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(
+            BTreeMap::from([
+                with_default("nightly", "tag:nightly", None),
+                inherits_from("broken", "missing"),
+                inherits_from("child", "nightly"),
+                with_default("invalid", "@", None),
+            ]),
+            &io_args,
+        );
 
-        if let SelectExpression::And(top_list) = result {
-            assert_eq!(top_list.len(), 2);
-            // Verify structure roughly
-            if let SelectExpression::And(u1_res) = &top_list[0] {
-                if let SelectExpression::Exclude(ex) = &u1_res[1] {
-                    if let SelectExpression::Atom(c) = &**ex {
-                        assert_eq!(c.value, "B");
-                    }
-                }
+        let results = parser.parse_all();
+        let names = results
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["broken", "child", "invalid", "nightly"]);
+        for (name, result) in &results {
+            match name.as_str() {
+                "broken" => assert_contains!(
+                    result.as_ref().unwrap_err().to_string(),
+                    "Unknown selector `missing`"
+                ),
+                "invalid" => assert!(result.is_err()),
+                _ => assert_eq!(result.as_ref().unwrap().to_string(), "tag:nightly"),
             }
-            if let SelectExpression::And(u2_res) = &top_list[1] {
-                if let SelectExpression::Exclude(ex) = &u2_res[1] {
-                    if let SelectExpression::Atom(c) = &**ex {
-                        assert_eq!(c.value, "D");
-                    }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    /// Test resolving every selector on the rayon thread pool.
+    /// Expects the same results, success or error, as the sequential `parse_all`.
+    fn test_parse_definitions_parallel() {
+        let io_args = IoArgs::default();
+        let mut defs = BTreeMap::from([
+            inherits_from("broken", "missing"),
+            with_default("invalid", "@", None),
+        ]);
+        for i in 0..64 {
+            defs.extend([with_default(
+                &format!("tag_{i}"),
+                &format!("tag:t{i}"),
+                None,
+            )]);
+            defs.extend([inherits_from(&format!("child_{i}"), &format!("tag_{i}"))]);
+        }
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let parallel = parser.parse_definitions_parallel();
+        let sequential = parser.parse_all();
+        assert_eq!(parallel.len(), sequential.len());
+        for (name, expected) in sequential {
+            let actual = &parallel[&name];
+            match expected {
+                Ok(expr) => assert_eq!(actual.as_ref().unwrap(), &expr),
+                Err(err) => {
+                    assert_eq!(actual.as_ref().unwrap_err().to_string(), err.to_string())
                 }
             }
         }
+        assert_eq!(parallel["child_7"].as_ref().unwrap().to_string(), "tag:t7");
+    }
+
+    #[test]
+    /// Test parsing several named selectors as one union.
+    /// Expects `Empty` for no names, an `Or` of each selector otherwise, and the unknown name's error.
+    fn test_parse_named_group() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(
+            BTreeMap::from([
+                with_default("nightly", "tag:nightly", None),
+                with_default("weekly", "tag:weekly", None),
+                inherits_from("child", "nightly"),
+            ]),
+            &io_args,
+        );
 
+        assert_eq!(parser.parse_named_group(&[])?, SelectExpression::Empty);
+        assert_eq!(
+            parser.parse_named_group(&["nightly"])?,
+            SelectExpression::Or(vec![parser.parse_named("nightly")?])
+        );
+        assert_eq!(
+            parser.parse_named_group(&["weekly", "child"])?,
+            SelectExpression::Or(vec![
+                parser.parse_named("weekly")?,
+                parser.parse_named("child")?,
+            ])
+        );
+
+        let err = parser
+            .parse_named_group(&["nightly", "missing", "weekly"])
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "Unknown selector `missing`");
         Ok(())
     }
 
     #[test]
-    /// Test deeply nested excludes: union containing exclude of union with exclude.
-    /// Expects Or([Exclude(And([Or([A]), Exclude(B)]))]) - nested exclude structures.
-    fn test_deeply_nested_excludes() -> FsResult<()> {
-        // union:
-        //   - exclude:
-        //       - union:
-        //           - A
-        //           - exclude: [B]
+    /// Test finding selectors unreachable from the selectors used at runtime.
+    /// Expects selectors reached through inheritance to count as used, and only the orphan reported.
+    fn test_unused_selectors() {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(
+            BTreeMap::from([
+                with_default("base", "tag:nightly", None),
+                inherits_from("middle", "base"),
+                inherits_from("top", "middle"),
+                with_default("orphan", "tag:legacy", None),
+            ]),
+            &io_args,
+        );
 
-        // Logic: NOT ( (A) AND NOT (B) )
-        // Wait, "exclude" takes a list of definitions.
-        // If I exclude a composite...
+        assert_eq!(parser.unused_selectors(&["top"]), vec!["orphan"]);
+        assert_eq!(parser.unused_selectors(&["middle"]), vec!["orphan", "top"]);
+        assert_eq!(
+            parser.unused_selectors(&[]),
+            vec!["base", "middle", "orphan", "top"]
+        );
+        // names that are not defined are ignored
+        assert_eq!(
+            parser.unused_selectors(&["top", "orphan", "missing"]),
+            Vec::<String>::new()
+        );
+    }
 
-        let defs = BTreeMap::new();
+    #[test]
+    /// Test finding unused selectors when the inheritance is written in a selector string.
+    /// Expects the selector referenced from the string to count as used.
+    fn test_unused_selectors_string_reference() {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: base
+    definition: tag:nightly
+  - name: child
+    definition: "+selector:base,tag:finance selector:base"
+  - name: orphan
+    definition: tag:legacy
+"#,
+            &io_args,
+        );
 
-        let inner = composite("union", vec![s("tag:A"), exclude(vec!["tag:B"])]);
-        // The exclude atom contains a list of definitions. `inner` is a definition (Full).
+        assert_eq!(parser.unused_selectors(&["child"]), vec!["orphan"]);
+    }
 
-        let exclude_inner =
-            SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Exclude(ExcludeAtomExpr {
-                exclude: vec![inner],
-            })));
+    #[test]
+    /// Test renaming a selector that is referenced zero, one or several times.
+    /// Expects the key, the definition name and every `selector:` reference to change.
+    fn test_rename_selector() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let mut parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: base
+    definition: tag:nightly
+  - name: lonely
+    definition: tag:legacy
+  - name: child
+    definition:
+      method: selector
+      value: base
+  - name: combined
+    definition:
+      union:
+        - selector: base
+        - method: tag
+          value: finance
+          exclude:
+            - method: selector
+              value: base
+"#,
+            &io_args,
+        );
 
-        let top = composite("union", vec![exclude_inner]); // Just an exclude at top level wrapped in union
+        assert_eq!(parser.rename_selector("lonely", "solitary")?, 0);
+        assert!(!parser.defs.contains_key("lonely"));
+        assert_eq!(parser.defs["solitary"].name, "solitary");
+        assert_eq!(parser.parse_named("solitary")?.to_string(), "tag:legacy");
 
-        let result = parser.parse_definition(&top)?;
+        let before = parser.parse_named("combined")?;
+        assert_eq!(parser.rename_selector("base", "nightly")?, 3);
+        assert_eq!(parser.parse_named("combined")?, before);
+        assert_eq!(parser.parse_named("child")?.to_string(), "tag:nightly");
+        assert!(parser.describe_undefined_references().is_empty());
 
-        // Union([ Exclude( ... ) ]) -> Or([ Exclude(...) ])
-        // Inside Exclude: The result of parsing `inner`.
-        // `inner` parses to: And([ Or([A]), Exclude(B) ])
-        // So: Or([ Exclude( And([ Or([A]), Exclude(B) ]) ) ])
+        assert_eq!(parser.rename_selector("child", "kid")?, 0);
+        assert_eq!(parser.rename_selector("kid", "child")?, 0);
 
-        if let SelectExpression::Or(list) = result {
-            if let SelectExpression::Exclude(inner_res) = &list[0] {
-                // And([ Or([A]), Exclude(B) ])
-                if let SelectExpression::And(parts) = &**inner_res {
-                    assert_eq!(parts.len(), 2);
-                    if let SelectExpression::Exclude(b) = &parts[1] {
-                        if let SelectExpression::Atom(c) = &**b {
-                            assert_eq!(c.value, "B");
-                        }
-                    }
-                } else {
-                    panic!("Expected And inside Exclude");
+        let mut parser = SelectorParser::new(
+            BTreeMap::from([
+                with_default("base", "tag:nightly", None),
+                inherits_from("child", "base"),
+            ]),
+            &io_args,
+        );
+        assert_eq!(parser.rename_selector("base", "root")?, 1);
+        assert_eq!(parser.parse_named("child")?.to_string(), "tag:nightly");
+        Ok(())
+    }
+
+    fn canonical_json(def: &SelectorDefinitionValue) -> serde_json::Value {
+        serde_json::to_value(SelectorParser::canonicalize(def).unwrap()).unwrap()
+    }
+
+    #[test]
+    /// Test canonicalizing equivalent spellings of the same selectors.
+    /// Expects every spelling of a selector to canonicalize identically, and unsupported methods to fail.
+    fn test_canonicalize_equivalent_forms() {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: tag_string
+    definition: "tag:nightly"
+  - name: tag_shorthand
+    definition:
+      tag: nightly
+  - name: tag_method
+    definition:
+      method: TAG
+      value: nightly
+      parents: false
+      indirect_selection: eager
+  - name: graph_string
+    definition: "2+path:models/marts+"
+  - name: graph_depths
+    definition:
+      method: path
+      value: models/marts
+      parents_depth: 2
+      children: true
+  - name: graph_explicit
+    definition:
+      method: path
+      value: models/marts
+      parents: true
+      parents_depth: 2
+      children: true
+      children_depth: 4294967295
+  - name: and_string
+    definition: "path:models,tag:finance,fqn:orders"
+  - name: and_intersection
+    definition:
+      intersection:
+        - tag: finance
+        - method: fqn
+          value: orders
+        - "path:models"
+"#,
+            &io_args,
+        );
+        let json = |name: &str| canonical_json(&parser.defs[name].definition);
+
+        for group in [
+            &["tag_string", "tag_shorthand", "tag_method"][..],
+            &["graph_string", "graph_depths", "graph_explicit"],
+            &["and_string", "and_intersection"],
+        ] {
+            for name in &group[1..] {
+                assert_eq!(json(name), json(group[0]), "{name} vs {}", group[0]);
+            }
+        }
+        assert_ne!(json("tag_string"), json("graph_string"));
+        assert_eq!(
+            json("tag_shorthand"),
+            serde_json::json!({
+                "method": "tag",
+                "value": "nightly",
+                "childrens_parents": false,
+                "parents": false,
+                "children": false,
+                "parents_depth": null,
+                "children_depth": null,
+                "indirect_selection": "eager",
+                "exclude": null,
+            })
+        );
+
+        let unsupported = SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::MethodKey(
+            BTreeMap::from([("bogus".to_string(), SelectorValue::from("x"))]),
+        )));
+        assert_eq!(
+            SelectorParser::canonicalize(&unsupported)
+                .unwrap_err()
+                .selector_kind(),
+            Some(&SelectorErrorKind::UnsupportedMethod("bogus".to_string()))
+        );
+    }
+
+    #[test]
+    /// Test that composite operands keep the order of the YAML list.
+    /// Expects `Or`/`And` children in list order, not sorted, however the values are spelled.
+    fn test_composite_order_is_preserved() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: union_order
+    definition:
+      union:
+        - "tag:c"
+        - "tag:a"
+        - "tag:b"
+  - name: intersection_order
+    definition:
+      intersection:
+        - tag: zulu
+        - method: tag
+          value: alpha
+        - "tag:mike"
+  - name: nested_order
+    definition:
+      union:
+        - intersection: ["tag:z", "tag:y"]
+        - "tag:x"
+        - exclude: ["tag:w"]
+"#,
+            &io_args,
+        );
+        let values = |expr: SelectExpression| {
+            expr.atoms()
+                .map(|criteria| criteria.value.clone())
+                .collect::<Vec<_>>()
+        };
+
+        for _ in 0..3 {
+            let union = parser.parse_named("union_order")?;
+            assert!(matches!(&union, SelectExpression::Or(exprs) if exprs.len() == 3));
+            assert_eq!(values(union), vec!["c", "a", "b"]);
+
+            let intersection = parser.parse_named("intersection_order")?;
+            assert!(matches!(&intersection, SelectExpression::And(exprs) if exprs.len() == 3));
+            assert_eq!(values(intersection), vec!["zulu", "alpha", "mike"]);
+
+            assert_eq!(
+                values(parser.parse_named("nested_order")?),
+                vec!["z", "y", "x", "w"]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test iterating over the definitions of a parser with three selectors.
+    /// Expects every selector, in name order, paired with its own definition.
+    fn test_iter_definitions() {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(
+            BTreeMap::from([
+                with_default("weekly", "tag:weekly", None),
+                with_default("nightly", "tag:nightly", Some(true)),
+                inherits_from("child", "nightly"),
+            ]),
+            &io_args,
+        );
+
+        let defs = parser.iter_definitions().collect::<Vec<_>>();
+        assert_eq!(
+            defs.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            vec!["child", "nightly", "weekly"]
+        );
+        for (name, def) in defs {
+            assert_eq!(def.name, name);
+        }
+        assert_eq!(
+            parser
+                .iter_definitions()
+                .filter(|(_, def)| def.default == Some(true))
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            vec!["nightly"]
+        );
+        assert_eq!(
+            SelectorParser::new(BTreeMap::new(), &io_args)
+                .iter_definitions()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    /// Test inserting, overwriting and removing definitions after construction.
+    /// Expects the replaced or removed definition back and parsing to follow the changes.
+    fn test_insert_and_remove_definition() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let mut parser = SelectorParser::new(
+            BTreeMap::from([
+                with_default("base", "tag:nightly", None),
+                inherits_from("child", "base"),
+            ]),
+            &io_args,
+        );
+        assert_eq!(parser.get_definition("base").unwrap().name, "base");
+        assert!(parser.get_definition("missing").is_none());
+
+        let (_, weekly) = with_default("weekly", "tag:weekly", None);
+        assert!(parser.insert_definition(weekly).is_none());
+        assert_eq!(parser.parse_named("weekly")?.to_string(), "tag:weekly");
+
+        let (_, hourly) = with_default("base", "tag:hourly", None);
+        let old = parser.insert_definition(hourly).unwrap();
+        assert_eq!(
+            parser.parse_definition(&old.definition)?.to_string(),
+            "tag:nightly"
+        );
+        assert_eq!(parser.parse_named("child")?.to_string(), "tag:hourly");
+
+        let removed = parser.remove_definition("base").unwrap();
+        assert_eq!(removed.name, "base");
+        assert!(parser.remove_definition("base").is_none());
+        assert!(parser.parse_named("base").is_err());
+        assert_eq!(
+            parser.parse_named("child").unwrap_err().selector_kind(),
+            Some(&SelectorErrorKind::UnknownSelector("base".to_string()))
+        );
+
+        parser.insert_definition(removed);
+        assert_eq!(parser.parse_named("child")?.to_string(), "tag:hourly");
+        Ok(())
+    }
+
+    #[test]
+    /// Test renaming a selector that does not exist or onto an existing name.
+    /// Expects a `SelectorError` and the definitions left untouched.
+    fn test_rename_selector_errors() {
+        let io_args = IoArgs::default();
+        let mut parser = SelectorParser::new(
+            BTreeMap::from([
+                with_default("base", "tag:nightly", None),
+                inherits_from("child", "base"),
+            ]),
+            &io_args,
+        );
+
+        let err = parser.rename_selector("missing", "other").unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "Unknown selector `missing`");
+
+        let err = parser.rename_selector("base", "child").unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "`child` is already defined");
+
+        assert_eq!(
+            parser.defs.keys().collect::<Vec<_>>(),
+            vec!["base", "child"]
+        );
+        assert_eq!(
+            parser.parse_named("child").unwrap().to_string(),
+            "tag:nightly"
+        );
+    }
+
+    #[test]
+    /// Test building a parser without constructing an `IoArgs`.
+    /// Expects it to resolve selectors exactly like a parser built with default `IoArgs`.
+    fn test_new_standalone() -> FsResult<()> {
+        let defs = BTreeMap::from([
+            with_default("nightly", "tag:nightly", None),
+            inherits_from("child", "nightly"),
+        ]);
+        let parser = SelectorParser::new_standalone(defs.clone());
+        assert_eq!(parser.parse_named("child")?.to_string(), "tag:nightly");
+
+        let io_args = IoArgs::default();
+        assert_eq!(
+            parser.parse_named("child")?,
+            SelectorParser::new(defs, &io_args).parse_named("child")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test parsing a named selector together with its definition metadata.
+    /// Expects the description to pass through unchanged, and `None` when absent.
+    fn test_parse_named_with_meta() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly
+    description: "Models refreshed every night: *all* of them."
+    definition: tag:nightly
+  - name: undocumented
+    definition: tag:hourly
+"#,
+            &io_args,
+        );
+        let nightly = parser.parse_named_with_meta("nightly")?;
+        assert_eq!(nightly.name, "nightly");
+        assert_eq!(
+            nightly.description.as_deref(),
+            Some("Models refreshed every night: *all* of them.")
+        );
+        assert_eq!(nightly.expression, parser.parse_named("nightly")?);
+
+        assert_eq!(
+            parser.parse_named_with_meta("undocumented")?.description,
+            None
+        );
+        assert!(parser.parse_named_with_meta("missing").is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test that a parsed union keeps the order of its YAML list.
+    /// Expects the first operand to come from the first YAML entry, not the alphabetically first.
+    fn test_union_preserves_yaml_order() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: ordered
+    definition:
+      union:
+        - method: tag
+          value: z_last
+        - method: fqn
+          value: a_first
+"#,
+            &io_args,
+        );
+        match parser.parse_named("ordered")? {
+            SelectExpression::Or(exprs) => {
+                assert_eq!(exprs.len(), 2);
+                assert_eq!(exprs[0].to_string(), "tag:z_last");
+                assert_eq!(exprs[1].to_string(), "fqn:a_first");
+            }
+            other => panic!("Expected Or expression, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test parsing selector strings in the dbt v1 graph operator format.
+    /// Expects the same expressions as the equivalent current-format strings.
+    fn test_parse_v1_syntax() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(BTreeMap::new(), &io_args);
+        for (v1, current) in [
+            ("+1my_model", "1+my_model"),
+            ("+2tag:nightly+", "2+tag:nightly+"),
+            ("+1a,tag:b c+1", "1+a,tag:b c+1"),
+            ("+my_model 1+other", "+my_model 1+other"),
+        ] {
+            assert_eq!(
+                parser.parse_v1_syntax(v1)?,
+                parse_model_specifiers(
+                    &current
+                        .split_whitespace()
+                        .map(str::to_string)
+                        .collect::<Vec<_>>()
+                )?,
+                "{v1}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test explaining a composite selector that inherits from another selector.
+    /// Expects the explanation to mirror the YAML tree, with source lines and inheritance steps.
+    fn test_explain() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly
+    definition: "tag:nightly"
+  - name: nightly_marts
+    definition:
+      union:
+        - method: selector
+          value: nightly
+        - "path:models/marts"
+        - exclude:
+            - "tag:deprecated"
+"#,
+            &io_args,
+        );
+
+        let explanation = parser.explain("nightly_marts")?;
+        assert_eq!(
+            explanation.step,
+            ExplanationStep::Selector("nightly_marts".to_string())
+        );
+        assert_eq!(explanation.expression, parser.parse_named("nightly_marts")?);
+        assert_eq!(explanation.inherited_selectors(), vec!["nightly"]);
+
+        let [union] = explanation.children.as_slice() else {
+            panic!("Expected a single definition, got {explanation:?}");
+        };
+        assert_eq!(union.step, ExplanationStep::Union);
+        let [selector, path, exclude] = union.children.as_slice() else {
+            panic!("Expected three union members, got {union:?}");
+        };
+        assert_eq!(
+            selector.step,
+            ExplanationStep::Selector("nightly".to_string())
+        );
+        assert_eq!(selector.expression, parser.parse_named("nightly")?);
+        assert_eq!(path.step, ExplanationStep::Atom);
+        assert_eq!(path.expression.to_string(), "path:models/marts");
+        assert_eq!(exclude.step, ExplanationStep::Exclude);
+        assert_eq!(exclude.expression.to_string(), "exclude(tag:deprecated)");
+
+        // locations point at each member in the YAML text
+        let line = |e: &SelectorExplanation| e.location.expect("location").line;
+        assert_eq!(line(path), line(selector) + 2);
+        assert_eq!(line(exclude), line(path) + 1);
+        assert_eq!(line(&exclude.children[0]), line(exclude) + 1);
+        // the inherited step is located where it is referenced, its
+        // definition where `nightly` is defined
+        assert!(line(&selector.children[0]) < line(selector));
+        Ok(())
+    }
+
+    #[test]
+    /// Test explaining selectors of a parser that was not built from YAML text.
+    /// Expects the same tree without source locations, and unknown names to fail.
+    fn test_explain_without_sources() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let defs = BTreeMap::from([
+            inherits_from("child", "parent"),
+            with_default("parent", "tag:a", None),
+        ]);
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let explanation = parser.explain("child")?;
+        assert_eq!(explanation.location, None);
+        assert_eq!(explanation.inherited_selectors(), vec!["parent"]);
+        assert!(parser.explain("missing").is_err());
+        Ok(())
+    }
+
+    // Helper to create a string selector
+    fn s(val: &str) -> SelectorDefinitionValue {
+        SelectorDefinitionValue::String(val.to_string())
+    }
+
+    // Helper to create an exclude block
+    fn exclude(vals: Vec<&str>) -> SelectorDefinitionValue {
+        let exclude_vals = vals.into_iter().map(s).collect();
+        SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Exclude(ExcludeAtomExpr {
+            exclude: exclude_vals,
+        })))
+    }
+
+    // Helper to create a composite selector
+    fn composite(kind: &str, items: Vec<SelectorDefinitionValue>) -> SelectorDefinitionValue {
+        let mut m = IndexMap::new();
+        let k = match kind {
+            "union" => CompositeKind::Union(items),
+            "intersection" => CompositeKind::Intersection(items),
+            "difference" => CompositeKind::Difference(items),
+            _ => panic!("Unknown kind"),
+        };
+        m.insert(kind.to_string(), k);
+        SelectorDefinitionValue::Full(SelectorExpr::Composite(CompositeExpr { kind: m }))
+    }
+
+    #[test]
+    /// Test basic union with single exclude: union: [A, exclude: [B]]
+    /// Expects And([Or([A]), Exclude(B)]) - union includes wrapped with exclude.
+    fn test_basic_union_with_exclude() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        // union: [A, exclude: [B]]
+        // Logic: (A) AND NOT (B)
+
+        let def = composite("union", vec![s("tag:A"), exclude(vec!["tag:B"])]);
+        let result = parser.parse_definition(&def)?;
+
+        if let SelectExpression::And(exprs) = result {
+            // [Or([A]), Exclude(B)]
+            assert_eq!(exprs.len(), 2);
+            if let SelectExpression::Or(inc) = &exprs[0] {
+                assert_eq!(inc.len(), 1);
+                if let SelectExpression::Atom(c) = &inc[0] {
+                    assert_eq!(c.value, "A");
+                }
+            }
+            if let SelectExpression::Exclude(exc) = &exprs[1] {
+                if let SelectExpression::Atom(c) = &**exc {
+                    assert_eq!(c.value, "B");
+                }
+            }
+        } else {
+            panic!("Expected And(Or([A]), Exclude(B)), got {:?}", result);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test basic intersection with single exclude: intersection: [A, exclude: [B]]
+    /// Expects And([And([A]), Exclude(B)]) - intersection includes wrapped with exclude.
+    fn test_basic_intersection_with_exclude() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        // intersection: [A, exclude: [B]]
+        // Logic: (A) AND NOT (B)
+
+        let def = composite("intersection", vec![s("tag:A"), exclude(vec!["tag:B"])]);
+        let result = parser.parse_definition(&def)?;
+
+        if let SelectExpression::And(exprs) = result {
+            // [And([A]), Exclude(B)]
+            assert_eq!(exprs.len(), 2);
+            if let SelectExpression::And(inc) = &exprs[0] {
+                if let SelectExpression::Atom(c) = &inc[0] {
+                    assert_eq!(c.value, "A");
+                }
+            }
+            if let SelectExpression::Exclude(exc) = &exprs[1] {
+                if let SelectExpression::Atom(c) = &**exc {
+                    assert_eq!(c.value, "B");
+                }
+            }
+        } else {
+            panic!("Expected And(And([A]), Exclude(B)), got {:?}", result);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test union with multiple exclude blocks: union: [A, exclude: [B], exclude: [C]]
+    /// Expects And([Or([A]), Exclude(Or([B, C]))]) - multiple excludes combined as Or.
+    fn test_multiple_excludes_union() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        // union: [A, exclude: [B], exclude: [C]]
+        // Logic: (A) AND NOT (B OR C)
+
+        let def = composite(
+            "union",
+            vec![s("tag:A"), exclude(vec!["tag:B"]), exclude(vec!["tag:C"])],
+        );
+        let result = parser.parse_definition(&def)?;
+
+        if let SelectExpression::And(exprs) = result {
+            // [Or([A]), Exclude(Or([B, C]))]
+            let ex = &exprs[1];
+            if let SelectExpression::Exclude(inner) = ex {
+                if let SelectExpression::Or(list) = &**inner {
+                    let vals: Vec<String> = list
+                        .iter()
+                        .map(|e| {
+                            if let SelectExpression::Atom(c) = e {
+                                c.value.clone()
+                            } else {
+                                "".to_string()
+                            }
+                        })
+                        .collect();
+                    assert!(vals.contains(&"B".to_string()));
+                    assert!(vals.contains(&"C".to_string()));
+                } else {
+                    panic!("Expected Or inside Exclude");
                 }
             } else {
                 panic!("Expected Exclude");
             }
+        } else {
+            panic!("Expected And");
         }
+        Ok(())
+    }
+
+    #[test]
+    /// Test intersection with multiple exclude blocks: intersection: [A, exclude: [B], exclude: [C]]
+    /// Expects And([And([A]), Exclude(Or([B, C]))]) - multiple excludes combined as Or.
+    fn test_multiple_excludes_intersection() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        // intersection: [A, exclude: [B], exclude: [C]]
+        // Logic: (A) AND NOT (B OR C)
+
+        let def = composite(
+            "intersection",
+            vec![s("tag:A"), exclude(vec!["tag:B"]), exclude(vec!["tag:C"])],
+        );
+        let result = parser.parse_definition(&def)?;
+
+        if let SelectExpression::And(exprs) = result {
+            // [And([A]), Exclude(Or([B, C]))]
+            let ex = &exprs[1];
+            if let SelectExpression::Exclude(inner) = ex {
+                if let SelectExpression::Or(list) = &**inner {
+                    let vals: Vec<String> = list
+                        .iter()
+                        .map(|e| {
+                            if let SelectExpression::Atom(c) = e {
+                                c.value.clone()
+                            } else {
+                                "".to_string()
+                            }
+                        })
+                        .collect();
+                    assert!(vals.contains(&"B".to_string()));
+                    assert!(vals.contains(&"C".to_string()));
+                } else {
+                    panic!("Expected Or inside Exclude");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // ============================================================================
+    // 4. Complex Nested Logic
+    // ============================================================================
+
+    #[test]
+    /// Test complex nested structure: union of intersections with excludes.
+    /// Expects Or([And([A, And([Or([B,C]), Exclude(D)])]), And([E,F])]) - deeply nested excludes.
+    fn test_union_of_intersections_with_exclude() -> FsResult<()> {
+        // Mimics the user case structure but generic
+        // union:
+        //   - intersection: [A, union: [B, C, exclude: [D]]]
+        //   - intersection: [E, F]
+
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let inner_union = composite(
+            "union",
+            vec![s("tag:B"), s("tag:C"), exclude(vec!["tag:D"])],
+        );
+
+        let intersection1 = composite("intersection", vec![s("tag:A"), inner_union]);
+
+        let intersection2 = composite("intersection", vec![s("tag:E"), s("tag:F")]);
+
+        let top = composite("union", vec![intersection1, intersection2]);
+
+        let result = parser.parse_definition(&top)?;
+
+        // Structure:
+        // Or([
+        //   And([ A, And([ Or([B, C]), Exclude(D) ]) ]),  <-- Intersection 1
+        //   And([ E, F ])                                 <-- Intersection 2
+        // ])
+
+        if let SelectExpression::Or(top_list) = result {
+            assert_eq!(top_list.len(), 2);
+
+            // Check Intersection 1
+            if let SelectExpression::And(i1) = &top_list[0] {
+                // A, InnerUnion
+                if let SelectExpression::Atom(a) = &i1[0] {
+                    assert_eq!(a.value, "A");
+                }
+                if let SelectExpression::And(u) = &i1[1] {
+                    // Or([B,C]), Exclude(D)
+                    if let SelectExpression::Or(bc) = &u[0] {
+                        assert_eq!(bc.len(), 2);
+                    }
+                    if let SelectExpression::Exclude(d) = &u[1] {
+                        if let SelectExpression::Atom(da) = &**d {
+                            assert_eq!(da.value, "D");
+                        }
+                    }
+                }
+            }
+
+            // Check Intersection 2
+            if let SelectExpression::And(i2) = &top_list[1] {
+                assert_eq!(i2.len(), 2);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test complex nested structure: intersection of unions with excludes.
+    /// Expects And([And([Or([A]), Exclude(B)]), And([Or([C]), Exclude(D)])]) - multiple union/exclude pairs.
+    fn test_intersection_of_unions_with_exclude() -> FsResult<()> {
+        // intersection:
+        //   - union: [A, exclude: [B]]
+        //   - union: [C, exclude: [D]]
+
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let u1 = composite("union", vec![s("tag:A"), exclude(vec!["tag:B"])]);
+        let u2 = composite("union", vec![s("tag:C"), exclude(vec!["tag:D"])]);
+
+        let top = composite("intersection", vec![u1, u2]);
+
+        let result = parser.parse_definition(&top)?;
+
+        // And([
+        //   And([ Or([A]), Exclude(B) ]),
+        //   And([ Or([C]), Exclude(D) ])
+        // ])
+
+        if let SelectExpression::And(top_list) = result {
+            assert_eq!(top_list.len(), 2);
+            // Verify structure roughly
+            if let SelectExpression::And(u1_res) = &top_list[0] {
+                if let SelectExpression::Exclude(ex) = &u1_res[1] {
+                    if let SelectExpression::Atom(c) = &**ex {
+                        assert_eq!(c.value, "B");
+                    }
+                }
+            }
+            if let SelectExpression::And(u2_res) = &top_list[1] {
+                if let SelectExpression::Exclude(ex) = &u2_res[1] {
+                    if let SelectExpression::Atom(c) = &**ex {
+                        assert_eq!(c.value, "D");
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test deeply nested excludes: union containing exclude of union with exclude.
+    /// Expects Or([Exclude(And([Or([A]), Exclude(B)]))]) - nested exclude structures.
+    fn test_deeply_nested_excludes() -> FsResult<()> {
+        // union:
+        //   - exclude:
+        //       - union:
+        //           - A
+        //           - exclude: [B]
+
+        // Logic: NOT ( (A) AND NOT (B) )
+        // Wait, "exclude" takes a list of definitions.
+        // If I exclude a composite...
+
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let inner = composite("union", vec![s("tag:A"), exclude(vec!["tag:B"])]);
+        // The exclude atom contains a list of definitions. `inner` is a definition (Full).
+
+        let exclude_inner =
+            SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Exclude(ExcludeAtomExpr {
+                exclude: vec![inner],
+            })));
+
+        let top = composite("union", vec![exclude_inner]); // Just an exclude at top level wrapped in union
+
+        let result = parser.parse_definition(&top)?;
+
+        // Union([ Exclude( ... ) ]) -> Or([ Exclude(...) ])
+        // Inside Exclude: The result of parsing `inner`.
+        // `inner` parses to: And([ Or([A]), Exclude(B) ])
+        // So: Or([ Exclude( And([ Or([A]), Exclude(B) ]) ) ])
+
+        if let SelectExpression::Or(list) = result {
+            if let SelectExpression::Exclude(inner_res) = &list[0] {
+                // And([ Or([A]), Exclude(B) ])
+                if let SelectExpression::And(parts) = &**inner_res {
+                    assert_eq!(parts.len(), 2);
+                    if let SelectExpression::Exclude(b) = &parts[1] {
+                        if let SelectExpression::Atom(c) = &**b {
+                            assert_eq!(c.value, "B");
+                        }
+                    }
+                } else {
+                    panic!("Expected And inside Exclude");
+                }
+            } else {
+                panic!("Expected Exclude");
+            }
+        }
+
+        Ok(())
+    }
+
+    // ============================================================================
+    // 5. Advanced Configuration and Graph Features
+    // ============================================================================
+
+    #[test]
+    /// Test graph operators (parents, children, childrens_parents) and depth limits.
+    /// Expects proper configuration of depth flags and indirect selection modes.
+    fn test_graph_operators() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let result = parser.parse_atom(&AtomExpr::Method(MethodAtomExpr {
+            method: "tag".to_string(),
+            value: SelectorValue::from("nightly"),
+            childrens_parents: true,
+            parents: true,
+            children: true,
+            parents_depth: Some(2),
+            children_depth: Some(3),
+            indirect_selection: Some(IndirectSelection::Cautious),
+            exclude: None,
+        }))?;
+
+        if let SelectExpression::Atom(criteria) = result {
+            assert_eq!(criteria.method, MethodName::Tag);
+            assert_eq!(criteria.value, "nightly");
+            assert!(criteria.childrens_parents);
+            assert_eq!(criteria.parents_depth, Some(2));
+            assert_eq!(criteria.children_depth, Some(3));
+            assert_eq!(criteria.indirect, Some(IndirectSelection::Cautious));
+        } else {
+            panic!("Expected Atom expression");
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test that indirect selection modes propagate through nested expression trees.
+    /// Expects all nested atom expressions to have the updated indirect selection setting.
+    fn test_indirect_selection_propagation() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let expr = SelectorExpr::Composite(CompositeExpr {
+            kind: {
+                let mut m = IndexMap::new();
+                m.insert(
+                    "intersection".to_string(),
+                    CompositeKind::Intersection(vec![
+                        SelectorDefinitionValue::String("model_a".to_string()),
+                        SelectorDefinitionValue::String("model_b".to_string()),
+                    ]),
+                );
+                m
+            },
+        });
+
+        let mut result = parser.parse_expr(&expr)?;
+
+        // Set indirect selection mode
+        result.set_indirect_selection(IndirectSelection::Cautious);
+
+        // Verify the change propagated to all nested expressions
+        if let SelectExpression::And(exprs) = &result {
+            for expr in exprs {
+                if let SelectExpression::Atom(criteria) = expr {
+                    assert_eq!(criteria.indirect, Some(IndirectSelection::Cautious));
+                } else {
+                    panic!("Expected Atom expression");
+                }
+            }
+        } else {
+            panic!("Expected And expression");
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test that `indirect_selection: buildany` survives YAML deserialization and parsing.
+    /// Expects the criteria to carry BuildAny and the YAML to serialize back unchanged.
+    fn test_indirect_selection_buildany_round_trip() -> FsResult<()> {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let atom: MethodAtomExpr =
+            dbt_yaml::from_str("method: tag\nvalue: nightly\nindirect_selection: buildany\n")
+                .unwrap();
+        assert_eq!(atom.indirect_selection, Some(IndirectSelection::BuildAny));
+
+        let result = parser.parse_atom(&AtomExpr::Method(atom.clone()))?;
+        if let SelectExpression::Atom(criteria) = result {
+            assert_eq!(criteria.indirect, Some(IndirectSelection::BuildAny));
+        } else {
+            panic!("Expected Atom expression");
+        }
+
+        let yaml = dbt_yaml::to_string(&atom).unwrap();
+        assert_contains!(yaml, "indirect_selection: buildany");
+        Ok(())
+    }
+
+    #[test]
+    /// Test selector inheritance using the "selector" method.
+    /// Expects the inherited selector's expression to be returned.
+    fn test_selector_inheritance() -> FsResult<()> {
+        let mut defs = BTreeMap::new();
+        defs.insert(
+            "foo_and_bar".to_string(),
+            SelectorDefinition {
+                name: "foo_and_bar".to_string(),
+                description: None,
+                default: None,
+                definition: SelectorDefinitionValue::Full(SelectorExpr::Composite(CompositeExpr {
+                    kind: {
+                        let mut m = IndexMap::new();
+                        m.insert(
+                            "intersection".to_string(),
+                            CompositeKind::Intersection(vec![
+                                SelectorDefinitionValue::String("tag:foo".to_string()),
+                                SelectorDefinitionValue::String("tag:bar".to_string()),
+                            ]),
+                        );
+                        m
+                    },
+                })),
+            },
+        );
+
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        // Test basic inheritance with additional exclude
+        let result = parser.parse_atom(&AtomExpr::Method(MethodAtomExpr {
+            method: "selector".to_string(),
+            value: SelectorValue::from("foo_and_bar"),
+            childrens_parents: false,
+            parents: false,
+            children: false,
+            parents_depth: None,
+            children_depth: None,
+            indirect_selection: None,
+            exclude: Some(vec![SelectorDefinitionValue::String(
+                "tag:buzz".to_string(),
+            )]),
+        }))?;
+
+        // Should inherit the intersection from foo_and_bar
+        if let SelectExpression::And(exprs) = result {
+            assert_eq!(exprs.len(), 2);
+            let mut tag_values = Vec::new();
+            for expr in &exprs {
+                if let SelectExpression::Atom(criteria) = expr {
+                    assert_eq!(criteria.method, MethodName::Tag);
+                    tag_values.push(criteria.value.clone());
+                }
+            }
+            tag_values.sort();
+            assert_eq!(tag_values, vec!["bar", "foo"]);
+        } else {
+            panic!("Expected And expression from inherited selector");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test selector inheritance with additional exclude properties.
+    /// Expects the inherited selector to preserve its original structure.
+    fn test_selector_inheritance_with_exclude_combination() -> FsResult<()> {
+        let mut defs = BTreeMap::new();
+        defs.insert(
+            "base_with_exclude".to_string(),
+            SelectorDefinition {
+                name: "base_with_exclude".to_string(),
+                description: None,
+                default: None,
+                definition: SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Method(
+                    MethodAtomExpr {
+                        method: "tag".to_string(),
+                        value: SelectorValue::from("production"),
+                        childrens_parents: false,
+                        parents: false,
+                        children: false,
+                        parents_depth: None,
+                        children_depth: None,
+                        indirect_selection: None,
+                        exclude: Some(vec![SelectorDefinitionValue::String(
+                            "base_exclude".to_string(),
+                        )]),
+                    },
+                ))),
+            },
+        );
+
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        // Reference the base selector and add more excludes
+        let result = parser.parse_atom(&AtomExpr::Method(MethodAtomExpr {
+            method: "selector".to_string(),
+            value: SelectorValue::from("base_with_exclude"),
+            childrens_parents: false,
+            parents: false,
+            children: false,
+            parents_depth: None,
+            children_depth: None,
+            indirect_selection: None,
+            exclude: Some(vec![SelectorDefinitionValue::String(
+                "additional_exclude".to_string(),
+            )]),
+        }))?;
+
+        // Should return the base selector's include expression (which has nested exclude)
+        if let SelectExpression::Atom(criteria) = result {
+            assert_eq!(criteria.method, MethodName::Tag);
+            assert_eq!(criteria.value, "production");
+            // Check that the base exclude is preserved in the nested exclude
+            if let Some(exclude_expr) = criteria.exclude {
+                if let SelectExpression::Atom(exclude_criteria) = *exclude_expr {
+                    assert_eq!(exclude_criteria.method, MethodName::Fqn);
+                    assert_eq!(exclude_criteria.value, "base_exclude");
+                } else {
+                    panic!("Expected Atom expression inside nested exclude");
+                }
+            } else {
+                panic!("Expected nested exclude in criteria");
+            }
+        } else {
+            panic!("Expected Atom expression");
+        }
+
+        Ok(())
+    }
+
+    // ============================================================================
+    // 6. High-level Resolution and Errors
+    // ============================================================================
+
+    #[test]
+    /// Test parsing a selector by name from the provided definitions map.
+    /// Expects the selector definition to be resolved and parsed correctly.
+    fn test_named_selector() -> FsResult<()> {
+        let mut defs = BTreeMap::new();
+        defs.insert(
+            "nightly_models".to_string(),
+            SelectorDefinition {
+                name: "nightly_models".to_string(),
+                description: None,
+                default: None,
+                definition: SelectorDefinitionValue::String("tag:nightly".to_string()),
+            },
+        );
+
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+        let result = parser.parse_named("nightly_models")?;
+
+        if let SelectExpression::Atom(criteria) = result {
+            assert_eq!(criteria.method, MethodName::Tag);
+            assert_eq!(criteria.value, "nightly");
+        } else {
+            panic!("Expected Atom expression");
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test various error scenarios including unknown selectors and inheritance failures.
+    /// Expects appropriate error codes and messages for invalid selector references.
+    fn test_error_handling() {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        // Test unknown selector
+        let result = parser.parse_named("unknown");
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(e.code, ErrorCode::SelectorError);
+            assert_contains!(e.to_string(), "Unknown selector");
+        }
+
+        // Test unknown selector in inheritance
+        let inheritance_result = parser.parse_atom(&AtomExpr::Method(MethodAtomExpr {
+            method: "selector".to_string(),
+            value: SelectorValue::from("unknown_selector"),
+            childrens_parents: false,
+            parents: false,
+            children: false,
+            parents_depth: None,
+            children_depth: None,
+            indirect_selection: None,
+            exclude: None,
+        }));
+        assert!(inheritance_result.is_err());
+    }
+
+    /// Build a definition whose body is a single `selector:` reference.
+    fn inherits_from(name: &str, parent: &str) -> (String, SelectorDefinition) {
+        (
+            name.to_string(),
+            SelectorDefinition {
+                name: name.to_string(),
+                description: None,
+                default: None,
+                definition: SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Method(
+                    MethodAtomExpr {
+                        method: "selector".to_string(),
+                        value: SelectorValue::from(parent),
+                        childrens_parents: false,
+                        parents: false,
+                        children: false,
+                        parents_depth: None,
+                        children_depth: None,
+                        indirect_selection: None,
+                        exclude: None,
+                    },
+                ))),
+            },
+        )
+    }
+
+    #[test]
+    /// Test renaming a selector referenced from selector strings.
+    /// Expects every `selector:` atom rewritten with its graph operators and delimiters kept.
+    fn test_rename_selector_string_reference() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let mut parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: base
+    definition: tag:nightly
+  - name: child
+    definition: "+selector:base,tag:finance selector:base"
+  - name: orphan
+    definition: tag:legacy
+"#,
+            &io_args,
+        );
+
+        let before = parser.parse_named("child")?;
+        assert_eq!(parser.rename_selector("base", "nightly")?, 2);
+        assert!(matches!(
+            &parser.defs["child"].definition,
+            SelectorDefinitionValue::String(s)
+                if s == "+selector:nightly,tag:finance selector:nightly"
+        ));
+        assert_eq!(parser.parse_named("child")?, before);
+        assert!(parser.describe_undefined_references().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    /// Test collecting references to undefined selectors across all definitions.
+    /// Expects one pair per broken reference, and none once every reference resolves.
+    fn test_describe_undefined_references() {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: a
+    definition:
+      union:
+        - method: selector
+          value: missing_1
+        - method: selector
+          value: b
+        - exclude:
+            - selector: missing_2
+  - name: b
+    definition:
+      method: selector
+      value: missing_1
+  - name: c
+    definition: "tag:nightly"
+"#,
+            &io_args,
+        );
+        assert_eq!(
+            parser.describe_undefined_references(),
+            vec![
+                ("a".to_string(), "missing_1".to_string()),
+                ("a".to_string(), "missing_2".to_string()),
+                ("b".to_string(), "missing_1".to_string()),
+            ]
+        );
+
+        let parser = SelectorParser::new(
+            BTreeMap::from([
+                inherits_from("child", "parent"),
+                with_default("parent", "tag:a", None),
+            ]),
+            &io_args,
+        );
+        assert!(parser.describe_undefined_references().is_empty());
+    }
+
+    #[test]
+    /// Test collecting undefined references written in selector strings.
+    /// Expects the unknown name reported once, and defined names ignored.
+    fn test_describe_undefined_references_string_reference() {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: base
+    definition: tag:nightly
+  - name: child
+    definition: "selector:base,selector:missing selector:missing+"
+"#,
+            &io_args,
+        );
+
+        assert_eq!(
+            parser.describe_undefined_references(),
+            vec![("child".to_string(), "missing".to_string())]
+        );
+    }
+
+    #[test]
+    /// Test that two selectors referencing each other are reported as a cycle.
+    /// Expects a SelectorError naming the full chain instead of a stack overflow.
+    fn test_two_node_inheritance_cycle() {
+        let defs = BTreeMap::from([inherits_from("foo", "bar"), inherits_from("bar", "foo")]);
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let err = parser.parse_named("foo").unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "Circular selector inheritance");
+        assert_contains!(err.to_string(), "foo -> bar -> foo");
+    }
+
+    #[test]
+    /// Test that a three-selector cycle is detected regardless of the entry point.
+    /// Expects the reported chain to start at the selector that was requested.
+    fn test_three_node_inheritance_cycle() {
+        let defs = BTreeMap::from([
+            inherits_from("a", "b"),
+            inherits_from("b", "c"),
+            inherits_from("c", "a"),
+        ]);
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let err = parser.parse_named("b").unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "b -> c -> a -> b");
+
+        let err = parser
+            .parse_definition(&SelectorDefinitionValue::Full(SelectorExpr::Atom(
+                AtomExpr::MethodKey(BTreeMap::from([(
+                    "selector".to_string(),
+                    SelectorValue::from("a"),
+                )])),
+            )))
+            .unwrap_err();
+        assert_contains!(err.to_string(), "Circular selector inheritance");
+    }
+
+    #[test]
+    /// Test that an acyclic inheritance chain longer than the configured limit fails.
+    /// Expects a SelectorError mentioning the maximum depth.
+    fn test_inheritance_depth_limit() -> FsResult<()> {
+        let mut defs = BTreeMap::from([
+            inherits_from("a", "b"),
+            inherits_from("b", "c"),
+            inherits_from("c", "leaf"),
+        ]);
+        defs.insert(
+            "leaf".to_string(),
+            SelectorDefinition {
+                name: "leaf".to_string(),
+                description: None,
+                default: None,
+                definition: SelectorDefinitionValue::String("tag:nightly".to_string()),
+            },
+        );
+        let io_args = IoArgs::default();
+
+        let parser = SelectorParser::new(defs.clone(), &io_args);
+        assert!(matches!(
+            parser.parse_named("a")?,
+            SelectExpression::Atom(_)
+        ));
+
+        let parser = SelectorParser::new(defs, &io_args).with_max_depth(3);
+        let err = parser.parse_named("a").unwrap_err();
+        assert_contains!(err.to_string(), "maximum depth of 3");
+        assert_contains!(err.to_string(), "a -> b -> c -> leaf");
+        Ok(())
+    }
+
+    #[test]
+    /// Test that selectors are listed after everything they inherit from.
+    /// Expects independent selectors in alphabetical order and dependents after their bases.
+    fn test_list_all_names_topological_order() -> FsResult<()> {
+        let mut defs = BTreeMap::from([
+            inherits_from("a_top", "m_mid"),
+            inherits_from("m_mid", "z_base"),
+        ]);
+        for name in ["z_base", "b_standalone"] {
+            defs.insert(
+                name.to_string(),
+                SelectorDefinition {
+                    name: name.to_string(),
+                    description: None,
+                    default: None,
+                    definition: SelectorDefinitionValue::String("tag:nightly".to_string()),
+                },
+            );
+        }
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        assert_eq!(
+            parser.list_all_names()?,
+            vec!["b_standalone", "z_base", "m_mid", "a_top"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test that ordering a cyclic set of selectors fails.
+    /// Expects a CircularReference error naming the selectors of the cycle.
+    fn test_list_all_names_cycle() {
+        let defs = BTreeMap::from([
+            inherits_from("a", "b"),
+            inherits_from("b", "a"),
+            inherits_from("c", "a"),
+        ]);
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let err = parser.list_all_names().unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_eq!(
+            err.selector_kind(),
+            Some(&SelectorErrorKind::CircularReference(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ]))
+        );
+        assert_contains!(err.to_string(), "a -> b -> a");
+    }
+
+    #[test]
+    /// Test ordering selectors that inherit through selector strings.
+    /// Expects the referenced selector before the string that references it.
+    fn test_list_all_names_string_reference() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: a_top
+    definition: "selector:z_base tag:finance"
+  - name: z_base
+    definition: tag:nightly
+"#,
+            &io_args,
+        );
+
+        assert_eq!(parser.list_all_names()?, vec!["z_base", "a_top"]);
+        Ok(())
+    }
+
+    #[test]
+    /// Test that a valid definition passes the dry-run check.
+    /// Expects `validate` to succeed without building an expression.
+    fn test_validate_valid_definition() -> FsResult<()> {
+        let defs = BTreeMap::from([inherits_from("base", "other")]);
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        parser.validate(&SelectorDefinitionValue::Full(SelectorExpr::Composite(
+            CompositeExpr {
+                kind: IndexMap::from([(
+                    "union".to_string(),
+                    CompositeKind::Union(vec![
+                        SelectorDefinitionValue::String("tag:nightly".to_string()),
+                        SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::MethodKey(
+                            BTreeMap::from([("selector".to_string(), SelectorValue::from("base"))]),
+                        ))),
+                    ]),
+                )]),
+            },
+        )))
+    }
+
+    #[test]
+    /// Test that every problem in a definition is reported, not only the first.
+    /// Expects a MultiError head followed by one SelectorError per problem.
+    fn test_validate_accumulates_errors() {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let def = SelectorDefinitionValue::Full(SelectorExpr::Composite(CompositeExpr {
+            kind: IndexMap::from([(
+                "union".to_string(),
+                CompositeKind::Union(vec![
+                    SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::MethodKey(
+                        BTreeMap::from([("selector".to_string(), SelectorValue::from("missing"))]),
+                    ))),
+                    SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::MethodKey(
+                        BTreeMap::from([
+                            ("tag".to_string(), SelectorValue::from("a")),
+                            ("bogus_method".to_string(), SelectorValue::from("b")),
+                        ]),
+                    ))),
+                    SelectorDefinitionValue::Full(SelectorExpr::Composite(CompositeExpr {
+                        kind: IndexMap::from([(
+                            "intersection".to_string(),
+                            CompositeKind::Intersection(vec![]),
+                        )]),
+                    })),
+                ]),
+            )]),
+        }));
+
+        let err = parser.validate(&def).unwrap_err();
+        assert_eq!(err.code, ErrorCode::MultiError);
+        assert_contains!(err.to_string(), "4 errors");
+
+        let messages = err
+            .flatten()
+            .into_iter()
+            .skip(1)
+            .map(|e| {
+                assert_eq!(e.code, ErrorCode::SelectorError);
+                e.to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 4);
+        assert_contains!(messages[0], "Unknown selector `missing`");
+        assert_contains!(messages[1], "exactly one key-value pair");
+        assert_contains!(messages[2], "Invalid node selector method: `bogus_method`");
+        assert_contains!(messages[3], "Empty `intersection` list");
+    }
+
+    #[test]
+    /// Test validating `selector:` atoms written in a selector string.
+    /// Expects known references to pass and an unknown one reported as UnknownSelector.
+    fn test_validate_string_reference() -> FsResult<()> {
+        let defs = BTreeMap::from([with_default("base", "tag:nightly", None)]);
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        parser.validate(&SelectorDefinitionValue::String(
+            "selector:base,tag:finance".to_string(),
+        ))?;
+        let err = parser
+            .validate(&SelectorDefinitionValue::String(
+                "selector:base selector:missing".to_string(),
+            ))
+            .unwrap_err();
+        assert_eq!(
+            err.selector_kind(),
+            Some(&SelectorErrorKind::UnknownSelector("missing".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test that a single problem is returned without a MultiError wrapper.
+    /// Expects the SelectorError itself.
+    fn test_validate_single_error() {
+        let defs = BTreeMap::new();
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let err = parser
+            .validate(&SelectorDefinitionValue::String("bogus:foo".to_string()))
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert!(!err.is_multiple_errors());
+    }
+
+    #[test]
+    /// Test matching on the machine-readable kind of selector errors.
+    /// Expects each failure to carry its `SelectorErrorKind`, with the message unchanged.
+    fn test_selector_error_kinds() {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(
+            BTreeMap::from([
+                inherits_from("a", "b"),
+                inherits_from("b", "a"),
+                with_default("bogus", "tag:x", None),
+            ]),
+            &io_args,
+        );
+
+        let err = parser.parse_named("missing").unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert!(matches!(
+            err.selector_kind(),
+            Some(SelectorErrorKind::UnknownSelector(name)) if name == "missing"
+        ));
+        assert_eq!(err.to_string(), "Unknown selector `missing`");
+
+        let err = parser.parse_named("a").unwrap_err();
+        match err.selector_kind() {
+            Some(SelectorErrorKind::CircularReference(chain)) => {
+                assert_eq!(chain, &["a", "b", "a"])
+            }
+            kind => panic!("Expected a circular reference, got {kind:?}"),
+        }
+        assert_contains!(err.to_string(), "a -> b -> a");
+
+        let err = parser
+            .parse_composite(&CompositeExpr {
+                kind: IndexMap::new(),
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.selector_kind(),
+            Some(&SelectorErrorKind::EmptyComposite)
+        );
+
+        let empty_exclude =
+            SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Exclude(ExcludeAtomExpr {
+                exclude: vec![],
+            })));
+        let err = parser.parse_definition(&empty_exclude).unwrap_err();
+        assert_eq!(err.selector_kind(), Some(&SelectorErrorKind::EmptyExclude));
+
+        let err = parser
+            .validate(&SelectorDefinitionValue::Full(SelectorExpr::Atom(
+                AtomExpr::MethodKey(BTreeMap::from([(
+                    "bogus_method".to_string(),
+                    SelectorValue::from("b"),
+                )])),
+            )))
+            .unwrap_err();
+        assert_eq!(
+            err.selector_kind(),
+            Some(&SelectorErrorKind::UnsupportedMethod(
+                "bogus_method".to_string()
+            ))
+        );
+
+        // errors without a kind of their own only carry a message
+        let err = parser.with_max_depth(0).parse_named("bogus").unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_eq!(err.selector_kind(), None);
+    }
+
+    #[test]
+    /// Test that a parser survives a JSON round trip through `SelectorParserSeed`.
+    /// Expects the restored parser to resolve selectors identically.
+    fn test_parser_serde_round_trip() -> FsResult<()> {
+        let mut defs = BTreeMap::from([inherits_from("child", "base")]);
+        defs.insert(
+            "base".to_string(),
+            SelectorDefinition {
+                name: "base".to_string(),
+                description: Some("base selector".to_string()),
+                default: None,
+                definition: SelectorDefinitionValue::String("tag:nightly".to_string()),
+            },
+        );
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args).with_max_depth(4);
+
+        let json = serde_json::to_string(&parser).unwrap();
+        let restored = SelectorParserSeed(&io_args)
+            .deserialize(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+
+        assert_eq!(restored.max_depth, 4);
+        assert_eq!(restored.parse_named("child")?, parser.parse_named("child")?);
+        Ok(())
+    }
+
+    #[test]
+    /// Test building a set difference from two named selectors.
+    /// Expects `And([include, Exclude(exclude)])` and an error for unknown names.
+    fn test_parse_difference() -> FsResult<()> {
+        let defs = BTreeMap::from([
+            (
+                "nightly".to_string(),
+                SelectorDefinition {
+                    name: "nightly".to_string(),
+                    description: None,
+                    default: None,
+                    definition: SelectorDefinitionValue::String("tag:nightly".to_string()),
+                },
+            ),
+            (
+                "slow".to_string(),
+                SelectorDefinition {
+                    name: "slow".to_string(),
+                    description: None,
+                    default: None,
+                    definition: SelectorDefinitionValue::String("tag:slow".to_string()),
+                },
+            ),
+        ]);
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        let result = parser.parse_difference("nightly", "slow")?;
+        assert_eq!(
+            result,
+            SelectExpression::And(vec![
+                parser.parse_named("nightly")?,
+                SelectExpression::Exclude(Box::new(parser.parse_named("slow")?)),
+            ])
+        );
+        assert!(parser.parse_difference("nightly", "unknown").is_err());
+        Ok(())
+    }
+
+    fn with_default(
+        name: &str,
+        selector: &str,
+        default: Option<bool>,
+    ) -> (String, SelectorDefinition) {
+        (
+            name.to_string(),
+            SelectorDefinition {
+                name: name.to_string(),
+                description: None,
+                default,
+                definition: SelectorDefinitionValue::String(selector.to_string()),
+            },
+        )
+    }
+
+    #[test]
+    /// Test resolving the selector marked `default: true`.
+    /// Expects `default: false` and missing flags to be ignored.
+    fn test_default_selector() -> FsResult<()> {
+        let defs = BTreeMap::from([
+            with_default("a_not_default", "tag:a", Some(false)),
+            with_default("b_default", "tag:b", Some(true)),
+            with_default("c_unset", "tag:c", None),
+        ]);
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        assert_eq!(parser.default_selector(), Some("b_default"));
+        assert_eq!(parser.parse_default()?, parser.parse_named("b_default")?);
+
+        let parser =
+            SelectorParser::new(BTreeMap::from([with_default("a", "tag:a", None)]), &io_args);
+        assert_eq!(parser.default_selector(), None);
+        assert_contains!(
+            parser.parse_default().unwrap_err().to_string(),
+            "No selector has `default: true`"
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test two selectors both claiming `default: true`.
+    /// Expects `default_selector` to pick the first by name and `parse_default` to fail.
+    fn test_multiple_default_selectors() {
+        let defs = BTreeMap::from([
+            with_default("zeta", "tag:z", Some(true)),
+            with_default("alpha", "tag:a", Some(true)),
+        ]);
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::new(defs, &io_args);
+
+        assert_eq!(parser.default_selector(), Some("alpha"));
+        let err = parser.parse_default().unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "alpha, zeta");
+    }
+
+    #[test]
+    /// Test building a parser with `SelectorParserBuilder`.
+    /// Expects the definitions and options to carry over to the parser.
+    fn test_builder() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = SelectorParser::builder()
+            .add_definition(with_default("nightly", "tag:nightly", Some(true)).1)
+            .add_definition(inherits_from("finance", "nightly").1)
+            .with_io_args(&io_args)
+            .warn_on_empty(true)
+            .max_depth(1)
+            .build()?;
+
+        assert_eq!(parser.default_selector(), Some("nightly"));
+        assert_eq!(
+            parser.parse_default()?,
+            parse_model_specifiers(&["tag:nightly".to_string()])?
+        );
+        assert!(parser.warn_on_empty_result);
+        assert_eq!(parser.max_depth, 1);
+        assert!(std::ptr::eq(parser.io_args, &io_args));
+        assert_contains!(
+            parser.parse_named("finance").unwrap_err().to_string(),
+            "exceeds maximum depth of 1"
+        );
+
+        let standalone = SelectorParser::builder().build()?;
+        assert_eq!(standalone.default_selector(), None);
+        assert_eq!(standalone.max_depth, DEFAULT_MAX_SELECTOR_DEPTH);
+        assert!(!standalone.warn_on_empty_result);
+        Ok(())
+    }
+
+    #[test]
+    /// Test building a parser from two definitions marked `default: true`.
+    /// Expects the build to fail and name both selectors.
+    fn test_builder_rejects_multiple_defaults() {
+        let err = SelectorParser::builder()
+            .add_definition(with_default("zeta", "tag:z", Some(true)).1)
+            .add_definition(with_default("other", "tag:o", Some(false)).1)
+            .add_definition(with_default("alpha", "tag:a", Some(true)).1)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "zeta, alpha");
+    }
+
+    #[test]
+    /// Test building a parser from two definitions with the same name.
+    /// Expects the build to fail instead of dropping one of them.
+    fn test_builder_rejects_duplicate_names() {
+        let err = SelectorParser::builder()
+            .add_definition(with_default("nightly", "tag:nightly", None).1)
+            .add_definition(with_default("nightly", "tag:daily", None).1)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(
+            err.to_string(),
+            "Selector `nightly` is defined more than once"
+        );
+    }
+
+    #[test]
+    /// Test parsing and evaluating exclude-only selectors.
+    /// Expects every node except the excluded ones to be selected.
+    fn test_parse_exclude_only() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: slow
+    definition: "tag:slow"
+"#,
+            &io_args,
+        );
+        let nodes = [
+            test_node("a", "staging", &["slow"]),
+            test_node("b", "staging", &["nightly"]),
+            test_node("c", "marts", &[]),
+            test_node("d", "marts", &["slow", "nightly"]),
+        ];
+        let all_nodes = nodes.iter().map(Node::from).collect::<Vec<_>>();
+        let selected = |expr: &SelectExpression| {
+            evaluate_select_expression(expr, &all_nodes)
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+
+        let expr = parser
+            .parse_exclude_only(&[SelectorDefinitionValue::String("tag:slow".to_string())])?;
+        assert_eq!(
+            expr,
+            SelectExpression::And(vec![
+                SelectExpression::Atom(SelectionCriteria::with_method(MethodName::Wildcard, "*")),
+                SelectExpression::Exclude(Box::new(parse_model_specifiers(&[
+                    "tag:slow".to_string()
+                ])?)),
+            ])
+        );
+        assert_eq!(selected(&expr), vec!["model.pkg.b", "model.pkg.c"]);
+
+        let expr = parser.parse_exclude_only(&[
+            inherits_from("slow_ref", "slow").1.definition,
+            SelectorDefinitionValue::String("fqn:c".to_string()),
+        ])?;
+        assert_eq!(selected(&expr), vec!["model.pkg.b"]);
+
+        assert_eq!(
+            parser.parse_exclude_only(&[]).unwrap_err().selector_kind(),
+            Some(&SelectorErrorKind::EmptyExclude)
+        );
+        Ok(())
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    /// Test parsing a definition with `parse_definition_traced` under a `fmt` subscriber.
+    /// Expects a `TRACE` span for each step, and the same result as `parse_definition`.
+    fn test_parse_definition_traced() -> FsResult<()> {
+        use tracing_subscriber::fmt::format::FmtSpan;
+
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: base
+    definition:
+      tag: nightly
+  - name: traced
+    definition:
+      union:
+        - method: selector
+          value: base
+        - "path:models/marts"
+        - exclude:
+            - fqn: orders
+"#,
+            &io_args,
+        );
+        let def = &parser.defs["traced"].definition;
+
+        let output = CapturedOutput::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_span_events(FmtSpan::NEW)
+            .with_ansi(false)
+            .with_writer({
+                let output = output.clone();
+                move || output.clone()
+            })
+            .finish();
+        let expr =
+            tracing::subscriber::with_default(subscriber, || parser.parse_definition_traced(def))?;
+        assert_eq!(expr, parser.parse_definition(def)?);
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let spans = output
+            .lines()
+            .filter(|line| line.contains("parse_selector{"))
+            .collect::<Vec<_>>();
+        assert!(spans.iter().all(|line| line.contains("TRACE")), "{output}");
+        for fields in [
+            "method= depth=0 expression_kind=union",
+            "method=selector depth=0 expression_kind=atom",
+            "method=tag depth=1 expression_kind=atom",
+            "method= depth=0 expression_kind=string",
+            "method= depth=0 expression_kind=exclude",
+            "method=fqn depth=0 expression_kind=atom",
+        ] {
+            assert!(
+                spans
+                    .iter()
+                    .any(|line| line.contains(&format!("parse_selector{{{fields}}}"))),
+                "missing {fields} in\n{output}"
+            );
+        }
+        assert_eq!(spans.len(), 6, "{output}");
+
+        // untraced parses open no spans
+        let output = CapturedOutput::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_span_events(FmtSpan::NEW)
+            .with_writer({
+                let output = output.clone();
+                move || output.clone()
+            })
+            .finish();
+        tracing::subscriber::with_default(subscriber, || parser.parse_definition(def))?;
+        assert!(output.0.lock().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    /// Test expanding all selectors with `resolve_all`.
+    /// Expects every `selector:` atom, including those of selector strings, to be replaced.
+    fn test_resolve_all() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: base
+    definition:
+      tag: nightly
+  - name: string_ref
+    definition: "selector:base,path:models/marts"
+  - name: full_ref
+    definition:
+      union:
+        - method: selector
+          value: string_ref
+        - "tag:hourly"
+  - name: excluding
+    definition:
+      method: fqn
+      value: "*"
+      exclude:
+        - "selector:full_ref"
+"#,
+            &io_args,
+        );
+        let resolved = parser.resolve_all()?;
+        assert_eq!(
+            resolved.keys().collect::<Vec<_>>(),
+            vec!["base", "excluding", "full_ref", "string_ref"]
+        );
+        for (name, expr) in &resolved {
+            assert!(
+                expr.atoms()
+                    .all(|criteria| criteria.method != MethodName::Selector),
+                "{name}: {expr}"
+            );
+        }
+
+        for (name, expr) in &resolved {
+            assert_eq!(&parser.parse_named(name)?, expr, "{name}");
+        }
+        let string_ref = parse_model_specifiers(&["tag:nightly,path:models/marts".to_string()])?;
+        assert_eq!(resolved["string_ref"], string_ref);
+        assert_eq!(
+            resolved["full_ref"],
+            SelectExpression::Or(vec![
+                string_ref,
+                parse_model_specifiers(&["tag:hourly".to_string()])?
+            ])
+        );
+        let SelectExpression::Atom(excluding) = &resolved["excluding"] else {
+            panic!("expected an atom: {}", resolved["excluding"]);
+        };
+        assert_eq!(excluding.exclude.as_deref(), Some(&resolved["full_ref"]));
+        Ok(())
+    }
+
+    #[test]
+    /// Test `resolve_all` with broken references in selector strings.
+    /// Expects cycles and unknown selectors to be reported.
+    fn test_resolve_all_errors() {
+        let io_args = IoArgs::default();
+        let cyclic = parser_from_yaml(
+            r#"
+selectors:
+  - name: a
+    definition: "selector:b"
+  - name: b
+    definition: "tag:x,selector:a"
+"#,
+            &io_args,
+        );
+        assert_eq!(
+            cyclic.resolve_all().unwrap_err().selector_kind(),
+            Some(&SelectorErrorKind::CircularReference(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ]))
+        );
+
+        let unknown = parser_from_yaml(
+            r#"
+selectors:
+  - name: a
+    definition: "tag:x,selector:missing"
+"#,
+            &io_args,
+        );
+        assert_eq!(
+            unknown.resolve_all().unwrap_err().selector_kind(),
+            Some(&SelectorErrorKind::UnknownSelector("missing".to_string()))
+        );
+        assert_eq!(
+            unknown.parse_named("a").unwrap_err().selector_kind(),
+            Some(&SelectorErrorKind::UnknownSelector("missing".to_string()))
+        );
+        assert_eq!(
+            cyclic.parse_raw("selector:a").unwrap_err().selector_kind(),
+            Some(&SelectorErrorKind::CircularReference(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    /// Test checking the `version` of a `selectors.yml`.
+    /// Expects equal and newer versions to pass, and older or missing versions to fail.
+    fn test_check_schema_version() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let selectors = "selectors:\n  - name: nightly\n    definition: \"tag:nightly\"\n";
+        let versioned = |version: &str| format!("version: {version}\n{selectors}");
+
+        let parser = parser_from_yaml(&versioned("2"), &io_args);
+        assert_eq!(parser.schema_version(), Some(2));
+        parser.check_schema_version(1)?;
+        parser.check_schema_version(2)?;
+        let err = parser.check_schema_version(3).unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(
+            err.to_string(),
+            "selectors.yml version 2 is older than the required version 3"
+        );
+
+        // quoted and float versions count when they are whole numbers
+        for version in ["\"2\"", "2.0"] {
+            assert_eq!(
+                parser_from_yaml(&versioned(version), &io_args).schema_version(),
+                Some(2),
+                "{version}"
+            );
+        }
+
+        for parser in [
+            parser_from_yaml(selectors, &io_args),
+            parser_from_yaml(&versioned("2.5"), &io_args),
+            SelectorParser::new(BTreeMap::new(), &io_args),
+        ] {
+            assert_eq!(parser.schema_version(), None);
+            assert_contains!(
+                parser.check_schema_version(1).unwrap_err().to_string(),
+                "does not declare a `version`"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test loading selectors with `from_yaml_str_versioned`.
+    /// Expects the selectors to load only when the file is recent enough.
+    fn test_from_yaml_str_versioned() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let yaml = "version: 2\nselectors:\n  - name: nightly\n    definition: \"tag:nightly\"\n";
+
+        let parser = SelectorParser::from_yaml_str_versioned(yaml, 2, &io_args)?;
+        assert_eq!(
+            parser.parse_named("nightly")?,
+            parse_model_specifiers(&["tag:nightly".to_string()])?
+        );
+        assert_contains!(
+            SelectorParser::from_yaml_str_versioned(yaml, 3, &io_args)
+                .unwrap_err()
+                .to_string(),
+            "older than the required version 3"
+        );
+        assert_contains!(
+            SelectorParser::from_yaml_str_versioned("selectors: [", 1, &io_args)
+                .unwrap_err()
+                .to_string(),
+            "Failed to parse selectors YAML"
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test combining named selectors with an optional extra selector string.
+    /// Expects the union of all of them, and errors for unknown names or invalid strings.
+    fn test_parse_union_override() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let parser = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly
+    definition: "tag:nightly"
+  - name: marts
+    definition:
+      method: path
+      value: models/marts
+"#,
+            &io_args,
+        );
+        let nightly = parser.parse_named("nightly")?;
+        let marts = parser.parse_named("marts")?;
+
+        assert_eq!(
+            parser.parse_union_override(&["nightly", "marts"], None)?,
+            SelectExpression::Or(vec![nightly.clone(), marts.clone()])
+        );
+        assert_eq!(
+            parser.parse_union_override(&["marts", "nightly"], Some("state:modified+"))?,
+            SelectExpression::Or(vec![
+                marts,
+                nightly,
+                parse_model_specifiers(&["state:modified+".to_string()])?
+            ])
+        );
+        assert_eq!(
+            parser.parse_union_override(&[], Some("tag:adhoc"))?,
+            SelectExpression::Or(vec![parse_model_specifiers(&["tag:adhoc".to_string()])?])
+        );
+        assert_eq!(
+            parser.parse_union_override(&[], None)?,
+            SelectExpression::Empty
+        );
+
+        let err = parser
+            .parse_union_override(&["nightly"], Some("tag:"))
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "Invalid selector spec: `tag:`");
+        assert_eq!(
+            parser
+                .parse_union_override(&["nightly", "weekly"], Some("tag:adhoc"))
+                .unwrap_err()
+                .selector_kind(),
+            Some(&SelectorErrorKind::UnknownSelector("weekly".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test adding the tests of selected models with `parse_with_downstream_tests`.
+    /// Expects only tests attached to models the base selects to be added, each once.
+    fn test_parse_with_downstream_tests() -> FsResult<()> {
+        let parser = SelectorParser::new_standalone(BTreeMap::new());
+        let tests = [
+            ("model.jaffle.orders", "test.jaffle.not_null_orders_id"),
+            ("model.jaffle.orders", "test.jaffle.unique_orders_id"),
+            (
+                "model.jaffle.customers",
+                "test.jaffle.not_null_customers_id",
+            ),
+            // a relationship test on both models
+            ("model.jaffle.orders", "test.jaffle.relationships_orders"),
+            ("model.jaffle.customers", "test.jaffle.relationships_orders"),
+        ];
+        let node_id = |id: &str| {
+            SelectExpression::Atom(SelectionCriteria::with_method(MethodName::NodeId, id))
+        };
 
+        assert_eq!(
+            parser.parse_with_downstream_tests("orders+", &tests)?,
+            SelectExpression::Or(vec![
+                parse_model_specifiers(&["orders+".to_string()])?,
+                node_id("test.jaffle.not_null_orders_id"),
+                node_id("test.jaffle.unique_orders_id"),
+                node_id("test.jaffle.relationships_orders"),
+            ])
+        );
+        assert_eq!(
+            parser.parse_with_downstream_tests("fqn:customers", &tests)?,
+            SelectExpression::Or(vec![
+                parse_model_specifiers(&["fqn:customers".to_string()])?,
+                node_id("test.jaffle.not_null_customers_id"),
+                node_id("test.jaffle.relationships_orders"),
+            ])
+        );
+        let both = parser.parse_with_downstream_tests("package:jaffle", &tests)?;
+        assert_eq!(both.count_atoms(), 5);
+
+        // no tests on the selected models: the base selector alone
+        for base in ["fqn:payments", "tag:nightly"] {
+            assert_eq!(
+                parser.parse_with_downstream_tests(base, &tests)?,
+                parse_model_specifiers(&[base.to_string()])?
+            );
+        }
+        assert_eq!(
+            parser.parse_with_downstream_tests("orders", &[])?,
+            parse_model_specifiers(&["orders".to_string()])?
+        );
+        assert!(parser.parse_with_downstream_tests("tag:", &tests).is_err());
         Ok(())
     }
 
-    // ============================================================================
-    // 5. Advanced Configuration and Graph Features
-    // ============================================================================
+    /// Trimmed `manifest.json` of a project with two selectors.
+    const MANIFEST_JSON: &str = r#"{
+  "metadata": {
+    "dbt_schema_version": "https://schemas.getdbt.com/dbt/manifest/v12.json",
+    "dbt_version": "1.8.0"
+  },
+  "nodes": {},
+  "sources": {},
+  "selectors": {
+    "nightly": {
+      "name": "nightly",
+      "description": "Nightly models",
+      "default": true,
+      "definition": {
+        "union": [
+          {"method": "tag", "value": "nightly"},
+          {"exclude": ["tag:deprecated"]}
+        ]
+      }
+    },
+    "marts": {
+      "name": "marts",
+      "definition": "path:models/marts+"
+    }
+  },
+  "disabled": {}
+}"#;
 
     #[test]
-    /// Test graph operators (parents, children, childrens_parents) and depth limits.
-    /// Expects proper configuration of depth flags and indirect selection modes.
-    fn test_graph_operators() -> FsResult<()> {
-        let defs = BTreeMap::new();
+    /// Test loading the selectors embedded in a `manifest.json`.
+    /// Expects every selector to be defined and to parse as it would from `selectors.yml`.
+    fn test_from_manifest_json() -> FsResult<()> {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
-
-        let result = parser.parse_atom(&AtomExpr::Method(MethodAtomExpr {
-            method: "tag".to_string(),
-            value: SelectorValue::from("nightly"),
-            childrens_parents: true,
-            parents: true,
-            children: true,
-            parents_depth: Some(2),
-            children_depth: Some(3),
-            indirect_selection: Some(IndirectSelection::Cautious),
-            exclude: None,
-        }))?;
+        let parser = SelectorParser::from_manifest_json(MANIFEST_JSON, &io_args)?;
+
+        assert_eq!(
+            parser
+                .iter_definitions()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            vec!["marts", "nightly"]
+        );
+        assert_eq!(parser.default_selector(), Some("nightly"));
+        assert_eq!(
+            parser
+                .parse_named_with_meta("nightly")?
+                .description
+                .as_deref(),
+            Some("Nightly models")
+        );
 
-        if let SelectExpression::Atom(criteria) = result {
-            assert_eq!(criteria.method, MethodName::Tag);
-            assert_eq!(criteria.value, "nightly");
-            assert!(criteria.childrens_parents);
-            assert_eq!(criteria.parents_depth, Some(2));
-            assert_eq!(criteria.children_depth, Some(3));
-            assert_eq!(criteria.indirect, Some(IndirectSelection::Cautious));
-        } else {
-            panic!("Expected Atom expression");
+        let from_yaml = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly
+    default: true
+    definition:
+      union:
+        - method: tag
+          value: nightly
+        - exclude:
+            - "tag:deprecated"
+  - name: marts
+    definition: "path:models/marts+"
+"#,
+            &io_args,
+        );
+        for name in ["nightly", "marts"] {
+            assert_eq!(
+                parser.parse_named(name)?,
+                from_yaml.parse_named(name)?,
+                "{name}"
+            );
         }
         Ok(())
     }
 
     #[test]
-    /// Test that indirect selection modes propagate through nested expression trees.
-    /// Expects all nested atom expressions to have the updated indirect selection setting.
-    fn test_indirect_selection_propagation() -> FsResult<()> {
-        let defs = BTreeMap::new();
+    /// Test loading manifests without selectors, and malformed manifests.
+    /// Expects an empty parser for missing selectors and `SelectorError` otherwise.
+    fn test_from_manifest_json_without_selectors() {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
+        for manifest in [
+            r#"{"nodes": {}}"#,
+            r#"{"selectors": null}"#,
+            r#"{"selectors": {}}"#,
+        ] {
+            let parser = SelectorParser::from_manifest_json(manifest, &io_args).unwrap();
+            assert_eq!(parser.iter_definitions().count(), 0, "{manifest}");
+            assert_eq!(parser.default_selector(), None);
+        }
 
-        let expr = SelectorExpr::Composite(CompositeExpr {
-            kind: {
-                let mut m = BTreeMap::new();
-                m.insert(
-                    "intersection".to_string(),
-                    CompositeKind::Intersection(vec![
-                        SelectorDefinitionValue::String("model_a".to_string()),
-                        SelectorDefinitionValue::String("model_b".to_string()),
-                    ]),
-                );
-                m
-            },
-        });
+        for (manifest, message) in [
+            ("{\"selectors\": ", "Failed to parse manifest JSON"),
+            (
+                r#"{"selectors": ["nightly"]}"#,
+                "Failed to parse the selectors of the manifest",
+            ),
+            (
+                r#"{"selectors": {"nightly": {"name": "nightly"}}}"#,
+                "Failed to parse the selectors of the manifest",
+            ),
+        ] {
+            let err = SelectorParser::from_manifest_json(manifest, &io_args).unwrap_err();
+            assert_eq!(err.code, ErrorCode::SelectorError);
+            assert_contains!(err.to_string(), message);
+        }
+    }
 
-        let mut result = parser.parse_expr(&expr)?;
+    fn parser_from_yaml<'a>(yaml: &str, io_args: &'a IoArgs) -> SelectorParser<'a> {
+        SelectorParser::from_yaml_str(yaml, io_args).unwrap()
+    }
 
-        // Set indirect selection mode
-        result.set_indirect_selection(IndirectSelection::Cautious);
+    #[test]
+    /// Test merging parsers built from two packages' selectors.yml files.
+    /// Expects references across packages to resolve after the merge.
+    fn test_merge_resolves_cross_package_references() -> FsResult<()> {
+        let io_args = IoArgs::default();
+        let package_a = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly
+    definition: "tag:nightly"
+"#,
+            &io_args,
+        );
+        let package_b = parser_from_yaml(
+            r#"
+selectors:
+  - name: nightly_models
+    definition:
+      intersection:
+        - method: selector
+          value: nightly
+        - method: resource_type
+          value: model
+"#,
+            &io_args,
+        );
+        assert!(package_b.parse_named("nightly_models").is_err());
 
-        // Verify the change propagated to all nested expressions
-        if let SelectExpression::And(exprs) = &result {
-            for expr in exprs {
-                if let SelectExpression::Atom(criteria) = expr {
-                    assert_eq!(criteria.indirect, Some(IndirectSelection::Cautious));
-                } else {
-                    panic!("Expected Atom expression");
-                }
-            }
+        let merged = package_a.merge(package_b)?;
+        let result = merged.parse_named("nightly_models")?;
+        if let SelectExpression::And(exprs) = result {
+            assert_eq!(exprs.len(), 2);
+            assert_eq!(exprs[0], merged.parse_named("nightly")?);
         } else {
             panic!("Expected And expression");
         }
@@ -1063,198 +6323,251 @@ mod tests {
     }
 
     #[test]
-    /// Test selector inheritance using the "selector" method.
-    /// Expects the inherited selector's expression to be returned.
-    fn test_selector_inheritance() -> FsResult<()> {
-        let mut defs = BTreeMap::new();
-        defs.insert(
-            "foo_and_bar".to_string(),
-            SelectorDefinition {
-                name: "foo_and_bar".to_string(),
-                description: None,
-                default: None,
-                definition: SelectorDefinitionValue::Full(SelectorExpr::Composite(CompositeExpr {
-                    kind: {
-                        let mut m = BTreeMap::new();
-                        m.insert(
-                            "intersection".to_string(),
-                            CompositeKind::Intersection(vec![
-                                SelectorDefinitionValue::String("tag:foo".to_string()),
-                                SelectorDefinitionValue::String("tag:bar".to_string()),
-                            ]),
-                        );
-                        m
-                    },
-                })),
-            },
+    /// Test merging parsers that both define the same selector name.
+    /// Expects a SelectorError naming the conflicting selector.
+    fn test_merge_conflict() {
+        let io_args = IoArgs::default();
+        let yaml = r#"
+selectors:
+  - name: nightly
+    definition: "tag:nightly"
+"#;
+        let err = parser_from_yaml(yaml, &io_args)
+            .merge(parser_from_yaml(yaml, &io_args))
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(
+            err.to_string(),
+            "Selector `nightly` is defined more than once"
         );
+    }
 
+    #[test]
+    /// Test building a parser from a realistic multi-selector YAML file.
+    /// Expects every selector to resolve, including `selector:` references.
+    fn test_from_yaml_str() -> FsResult<()> {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
+        let parser = SelectorParser::from_yaml_str(
+            r#"
+selectors:
+  - name: nightly
+    description: Models refreshed nightly
+    definition: "tag:nightly"
+  - name: marts_without_staging
+    default: true
+    definition:
+      union:
+        - method: path
+          value: models/marts
+          children: true
+        - exclude:
+            - method: tag
+              value: staging
+  - name: nightly_marts
+    definition:
+      intersection:
+        - method: selector
+          value: nightly
+        - method: selector
+          value: marts_without_staging
+"#,
+            &io_args,
+        )?;
+
+        assert_eq!(
+            parser.list_all_names()?,
+            vec!["marts_without_staging", "nightly", "nightly_marts"]
+        );
+        assert_eq!(parser.default_selector(), Some("marts_without_staging"));
 
-        // Test basic inheritance with additional exclude
-        let result = parser.parse_atom(&AtomExpr::Method(MethodAtomExpr {
-            method: "selector".to_string(),
-            value: SelectorValue::from("foo_and_bar"),
-            childrens_parents: false,
-            parents: false,
-            children: false,
-            parents_depth: None,
-            children_depth: None,
-            indirect_selection: None,
-            exclude: Some(vec![SelectorDefinitionValue::String(
-                "tag:buzz".to_string(),
-            )]),
-        }))?;
+        let nightly = parser.parse_named("nightly")?;
+        assert_eq!(nightly.to_string(), "tag:nightly");
 
-        // Should inherit the intersection from foo_and_bar
-        if let SelectExpression::And(exprs) = result {
+        let marts = parser.parse_named("marts_without_staging")?;
+        if let SelectExpression::And(exprs) = &marts {
             assert_eq!(exprs.len(), 2);
-            let mut tag_values = Vec::new();
-            for expr in &exprs {
-                if let SelectExpression::Atom(criteria) = expr {
-                    assert_eq!(criteria.method, MethodName::Tag);
-                    tag_values.push(criteria.value.clone());
-                }
-            }
-            tag_values.sort();
-            assert_eq!(tag_values, vec!["bar", "foo"]);
+            assert_eq!(exprs[0].to_string(), "path:models/marts+");
+            assert_eq!(exprs[1].to_string(), "exclude(tag:staging)");
         } else {
-            panic!("Expected And expression from inherited selector");
+            panic!("Expected And expression, got {marts:?}");
         }
 
+        let combined = parser.parse_named("nightly_marts")?;
+        assert_eq!(combined, SelectExpression::And(vec![nightly, marts]));
         Ok(())
     }
 
     #[test]
-    /// Test selector inheritance with additional exclude properties.
-    /// Expects the inherited selector to preserve its original structure.
-    fn test_selector_inheritance_with_exclude_combination() -> FsResult<()> {
-        let mut defs = BTreeMap::new();
-        defs.insert(
-            "base_with_exclude".to_string(),
-            SelectorDefinition {
-                name: "base_with_exclude".to_string(),
-                description: None,
-                default: None,
-                definition: SelectorDefinitionValue::Full(SelectorExpr::Atom(AtomExpr::Method(
-                    MethodAtomExpr {
-                        method: "tag".to_string(),
-                        value: SelectorValue::from("production"),
-                        childrens_parents: false,
-                        parents: false,
-                        children: false,
-                        parents_depth: None,
-                        children_depth: None,
-                        indirect_selection: None,
-                        exclude: Some(vec![SelectorDefinitionValue::String(
-                            "base_exclude".to_string(),
-                        )]),
-                    },
-                ))),
-            },
-        );
-
+    /// Test building a parser from malformed YAML.
+    /// Expects a SelectorError wrapping the YAML parse error.
+    fn test_from_yaml_str_invalid() {
         let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
-
-        // Reference the base selector and add more excludes
-        let result = parser.parse_atom(&AtomExpr::Method(MethodAtomExpr {
-            method: "selector".to_string(),
-            value: SelectorValue::from("base_with_exclude"),
-            childrens_parents: false,
-            parents: false,
-            children: false,
-            parents_depth: None,
-            children_depth: None,
-            indirect_selection: None,
-            exclude: Some(vec![SelectorDefinitionValue::String(
-                "additional_exclude".to_string(),
-            )]),
-        }))?;
+        let err = SelectorParser::from_yaml_str("selectors: [name: nightly", &io_args).unwrap_err();
+        assert_eq!(err.code, ErrorCode::SelectorError);
+        assert_contains!(err.to_string(), "Failed to parse selectors YAML");
+    }
 
-        // Should return the base selector's include expression (which has nested exclude)
-        if let SelectExpression::Atom(criteria) = result {
-            assert_eq!(criteria.method, MethodName::Tag);
-            assert_eq!(criteria.value, "production");
-            // Check that the base exclude is preserved in the nested exclude
-            if let Some(exclude_expr) = criteria.exclude {
-                if let SelectExpression::Atom(exclude_criteria) = *exclude_expr {
-                    assert_eq!(exclude_criteria.method, MethodName::Fqn);
-                    assert_eq!(exclude_criteria.value, "base_exclude");
-                } else {
-                    panic!("Expected Atom expression inside nested exclude");
+    mod integration {
+        use super::*;
+
+        const JAFFLE_SHOP: &str = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/selectors/jaffle_shop.yml"
+        ));
+        const DBT_UTILS: &str = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/selectors/dbt_utils.yml"
+        ));
+        const CUSTOM_PROJECT: &str = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/selectors/custom_project.yml"
+        ));
+
+        #[test]
+        /// Test loading `selectors.yml` files written like those of real projects.
+        /// Expects every selector to validate and resolve to a non-empty expression.
+        fn test_sample_project_selectors() {
+            let io_args = IoArgs::default();
+            for (project, yaml, count) in [
+                ("jaffle_shop", JAFFLE_SHOP, 4),
+                ("dbt_utils", DBT_UTILS, 4),
+                ("custom_project", CUSTOM_PROJECT, 7),
+            ] {
+                let parser = SelectorParser::from_yaml_str(yaml, &io_args)
+                    .unwrap_or_else(|e| panic!("{project}: {e}"));
+                assert_eq!(parser.defs.len(), count, "{project}");
+                for (name, def) in &parser.defs {
+                    parser
+                        .validate(&def.definition)
+                        .unwrap_or_else(|e| panic!("{project}/{name}: {e}"));
+                    let expr = parser
+                        .parse_named(name)
+                        .unwrap_or_else(|e| panic!("{project}/{name}: {e}"));
+                    assert_ne!(expr, SelectExpression::Empty, "{project}/{name}");
+                    assert!(expr.atoms().next().is_some(), "{project}/{name}");
                 }
-            } else {
-                panic!("Expected nested exclude in criteria");
             }
-        } else {
-            panic!("Expected Atom expression");
         }
 
-        Ok(())
-    }
-
-    // ============================================================================
-    // 6. High-level Resolution and Errors
-    // ============================================================================
-
-    #[test]
-    /// Test parsing a selector by name from the provided definitions map.
-    /// Expects the selector definition to be resolved and parsed correctly.
-    fn test_named_selector() -> FsResult<()> {
-        let mut defs = BTreeMap::new();
-        defs.insert(
-            "nightly_models".to_string(),
-            SelectorDefinition {
-                name: "nightly_models".to_string(),
-                description: None,
-                default: None,
-                definition: SelectorDefinitionValue::String("tag:nightly".to_string()),
-            },
-        );
-
-        let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
-        let result = parser.parse_named("nightly_models")?;
-
-        if let SelectExpression::Atom(criteria) = result {
-            assert_eq!(criteria.method, MethodName::Tag);
-            assert_eq!(criteria.value, "nightly");
-        } else {
-            panic!("Expected Atom expression");
+        #[test]
+        /// Test canonicalizing every selector of the sample projects.
+        /// Expects the canonical form to select the same nodes and to canonicalize to itself.
+        fn test_canonicalize_round_trip() {
+            let io_args = IoArgs::default();
+            for yaml in [JAFFLE_SHOP, DBT_UTILS, CUSTOM_PROJECT] {
+                let parser = parser_from_yaml(yaml, &io_args);
+                for (name, def) in &parser.defs {
+                    let canonical = SelectorParser::canonicalize(&def.definition)
+                        .unwrap_or_else(|e| panic!("{name}: {e}"));
+                    assert_eq!(
+                        parser.parse_definition(&canonical).unwrap().stable_hash(),
+                        parser
+                            .parse_definition(&def.definition)
+                            .unwrap()
+                            .stable_hash(),
+                        "{name}"
+                    );
+                    assert_eq!(
+                        canonical_json(&canonical),
+                        serde_json::to_value(&canonical).unwrap(),
+                        "{name}"
+                    );
+                }
+            }
         }
-        Ok(())
     }
 
-    #[test]
-    /// Test various error scenarios including unknown selectors and inheritance failures.
-    /// Expects appropriate error codes and messages for invalid selector references.
-    fn test_error_handling() {
-        let defs = BTreeMap::new();
-        let io_args = IoArgs::default();
-        let parser = SelectorParser::new(defs, &io_args);
-
-        // Test unknown selector
-        let result = parser.parse_named("unknown");
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert_eq!(e.code, ErrorCode::SelectorError);
-            assert_contains!(e.to_string(), "Unknown selector");
+    mod snapshots {
+        use super::*;
+
+        const SELECTORS: &str = r#"
+selectors:
+  - name: simple_tag
+    definition: "tag:nightly"
+  - name: string_intersection
+    definition: "tag:nightly,resource_type:model"
+  - name: string_depth_operators
+    definition: "2+path:models/marts+1"
+  - name: string_childrens_parents
+    definition: "@fqn:jaffle_shop.orders"
+  - name: string_method_args
+    definition: "config.materialized:incremental"
+  - name: string_default_method
+    definition: "orders+"
+  - name: union
+    definition:
+      union:
+        - method: tag
+          value: daily
+        - method: tag
+          value: hourly
+  - name: intersection
+    definition:
+      intersection:
+        - "tag:finance"
+        - method: resource_type
+          value: model
+  - name: union_with_exclude
+    definition:
+      union:
+        - "path:models/staging"
+        - exclude:
+            - "tag:deprecated"
+  - name: intersection_with_excludes
+    definition:
+      intersection:
+        - "tag:core"
+        - exclude:
+            - "tag:wip"
+        - exclude:
+            - "tag:deprecated"
+  - name: atom_with_exclude
+    definition:
+      method: path
+      value: models
+      children: true
+      exclude:
+        - "tag:wip"
+  - name: atom_depth_limits
+    definition:
+      method: fqn
+      value: customers
+      parents: true
+      parents_depth: 1
+      children: true
+      children_depth: 2
+      indirect_selection: cautious
+  - name: inherits_selector
+    definition:
+      intersection:
+        - method: selector
+          value: simple_tag
+        - resource_type: model
+  - name: nested_inheritance
+    definition:
+      union:
+        - method: selector
+          value: inherits_selector
+        - tag: hourly
+  - name: top_level_exclude
+    definition:
+      exclude:
+        - "tag:deprecated"
+"#;
+
+        #[test]
+        /// Test parsing representative selectors against the snapshots in `src/snapshots`.
+        /// Expects every selector's `SelectExpression` to match its stored debug output.
+        fn test_parse_definition_snapshots() {
+            let io_args = IoArgs::default();
+            let parser = parser_from_yaml(SELECTORS, &io_args);
+            assert_eq!(parser.defs.len(), 15);
+            for (name, def) in &parser.defs {
+                let expr = parser
+                    .parse_definition(&def.definition)
+                    .unwrap_or_else(|e| panic!("{name}: {e}"));
+                insta::assert_debug_snapshot!(name.as_str(), expr);
+            }
         }
-
-        // Test unknown selector in inheritance
-        let inheritance_result = parser.parse_atom(&AtomExpr::Method(MethodAtomExpr {
-            method: "selector".to_string(),
-            value: SelectorValue::from("unknown_selector"),
-            childrens_parents: false,
-            parents: false,
-            children: false,
-            parents_depth: None,
-            children_depth: None,
-            indirect_selection: None,
-            exclude: None,
-        }));
-        assert!(inheritance_result.is_err());
     }
 }